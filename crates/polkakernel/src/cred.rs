@@ -0,0 +1,349 @@
+use alloc::vec::Vec;
+
+use crate::{libc::*, Error, Metadata, MAIN_THREAD_ID};
+
+/// Process credentials and identifiers.
+///
+/// The effective IDs are used in permission checks. The process is privileged if its effective
+/// user ID is zero. The main thread ID equals the process ID, see
+/// [`KernelState::with_credentials`](crate::KernelState::with_credentials).
+///
+/// See [credentials(7)](https://man7.org/linux/man-pages/man7/credentials.7.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+	/// Real user ID.
+	pub uid: u32,
+	/// Effective user ID.
+	pub euid: u32,
+	/// Saved set-user-ID.
+	pub suid: u32,
+	/// Real group ID.
+	pub gid: u32,
+	/// Effective group ID.
+	pub egid: u32,
+	/// Saved set-group-ID.
+	pub sgid: u32,
+	/// Supplementary group IDs.
+	pub groups: Vec<u32>,
+	/// Process ID.
+	pub pid: u32,
+	/// Parent process ID.
+	pub ppid: u32,
+	/// Process group ID.
+	pub pgid: u32,
+	/// Session ID.
+	pub sid: u32,
+}
+
+impl Credentials {
+	/// Create credentials of the superuser process that leads its own session.
+	///
+	/// There are no supplementary groups.
+	pub fn new() -> Self {
+		Self {
+			uid: 0,
+			euid: 0,
+			suid: 0,
+			gid: 0,
+			egid: 0,
+			sgid: 0,
+			groups: Vec::new(),
+			pid: DEFAULT_PID,
+			ppid: 0,
+			pgid: DEFAULT_PID,
+			sid: DEFAULT_PID,
+		}
+	}
+
+	/// Set real, effective and saved user IDs.
+	pub fn with_uid(mut self, uid: u32) -> Self {
+		self.uid = uid;
+		self.euid = uid;
+		self.suid = uid;
+		self
+	}
+
+	/// Set real, effective and saved group IDs.
+	pub fn with_gid(mut self, gid: u32) -> Self {
+		self.gid = gid;
+		self.egid = gid;
+		self.sgid = gid;
+		self
+	}
+
+	/// Set supplementary group IDs.
+	pub fn with_groups(mut self, groups: impl Into<Vec<u32>>) -> Self {
+		self.groups = groups.into();
+		self
+	}
+
+	/// Set process ID, process group ID and session ID.
+	pub fn with_pid(mut self, pid: u32) -> Self {
+		self.pid = pid;
+		self.pgid = pid;
+		self.sid = pid;
+		self
+	}
+
+	/// Set parent process ID.
+	pub fn with_ppid(mut self, ppid: u32) -> Self {
+		self.ppid = ppid;
+		self
+	}
+
+	/// Check if the process is privileged.
+	pub fn is_privileged(&self) -> bool {
+		self.euid == 0
+	}
+
+	/// Check if the credentials allow accessing the file with the provided metadata.
+	///
+	/// `mode` is either `F_OK` or a combination of `R_OK`, `W_OK` and `X_OK`. The real IDs are
+	/// used instead of the effective ones if `real` is true. Fails with `EACCES` if the access is
+	/// denied.
+	///
+	/// See [access(2)](https://man7.org/linux/man-pages/man2/access.2.html).
+	pub fn check_access(&self, metadata: &Metadata, mode: u32, real: bool) -> Result<(), Error> {
+		let (uid, gid) = if real { (self.uid, self.gid) } else { (self.euid, self.egid) };
+		let allowed = if uid == 0 {
+			// The superuser can execute the file only if somebody else can.
+			mode & X_OK == 0 || metadata.mode & S_IFMT == S_IFDIR || metadata.mode & 0o111 != 0
+		} else {
+			let bits = if metadata.uid == uid {
+				metadata.mode >> 6
+			} else if metadata.gid == gid || self.groups.contains(&metadata.gid) {
+				metadata.mode >> 3
+			} else {
+				metadata.mode
+			};
+			bits & mode & 0o7 == mode & 0o7
+		};
+		if !allowed {
+			return Err(Error(EACCES));
+		}
+		Ok(())
+	}
+
+	/// Set the effective user ID, and also the real and saved ones if the process is privileged.
+	///
+	/// See [setuid(2)](https://man7.org/linux/man-pages/man2/setuid.2.html).
+	pub fn set_uid(&mut self, uid: u32) -> Result<(), Error> {
+		if uid == KEEP_ID {
+			return Err(Error(EINVAL));
+		}
+		if self.is_privileged() {
+			self.uid = uid;
+			self.suid = uid;
+		} else if uid != self.uid && uid != self.suid {
+			return Err(Error(EPERM));
+		}
+		self.euid = uid;
+		Ok(())
+	}
+
+	/// Set the effective group ID, and also the real and saved ones if the process is privileged.
+	///
+	/// See [setgid(2)](https://man7.org/linux/man-pages/man2/setgid.2.html).
+	pub fn set_gid(&mut self, gid: u32) -> Result<(), Error> {
+		if gid == KEEP_ID {
+			return Err(Error(EINVAL));
+		}
+		if self.is_privileged() {
+			self.gid = gid;
+			self.sgid = gid;
+		} else if gid != self.gid && gid != self.sgid {
+			return Err(Error(EPERM));
+		}
+		self.egid = gid;
+		Ok(())
+	}
+
+	/// Set real, effective and saved user IDs.
+	///
+	/// `u32::MAX` (-1) leaves the corresponding ID unchanged. Unprivileged process can only set
+	/// each ID to one of its current user IDs.
+	///
+	/// See [setresuid(2)](https://man7.org/linux/man-pages/man2/setresuid.2.html).
+	pub fn set_res_uid(&mut self, uid: u32, euid: u32, suid: u32) -> Result<(), Error> {
+		let current = [self.uid, self.euid, self.suid];
+		if !self.is_privileged() &&
+			[uid, euid, suid].iter().any(|id| *id != KEEP_ID && !current.contains(id))
+		{
+			return Err(Error(EPERM));
+		}
+		update_id(&mut self.uid, uid);
+		update_id(&mut self.euid, euid);
+		update_id(&mut self.suid, suid);
+		Ok(())
+	}
+
+	/// Set real, effective and saved group IDs.
+	///
+	/// `u32::MAX` (-1) leaves the corresponding ID unchanged. Unprivileged process can only set
+	/// each ID to one of its current group IDs.
+	///
+	/// See [setresgid(2)](https://man7.org/linux/man-pages/man2/setresgid.2.html).
+	pub fn set_res_gid(&mut self, gid: u32, egid: u32, sgid: u32) -> Result<(), Error> {
+		let current = [self.gid, self.egid, self.sgid];
+		if !self.is_privileged() &&
+			[gid, egid, sgid].iter().any(|id| *id != KEEP_ID && !current.contains(id))
+		{
+			return Err(Error(EPERM));
+		}
+		update_id(&mut self.gid, gid);
+		update_id(&mut self.egid, egid);
+		update_id(&mut self.sgid, sgid);
+		Ok(())
+	}
+
+	/// Replace supplementary group IDs.
+	///
+	/// Only privileged process can do that.
+	///
+	/// See [setgroups(2)](https://man7.org/linux/man-pages/man2/setgroups.2.html).
+	pub fn set_groups(&mut self, groups: Vec<u32>) -> Result<(), Error> {
+		if !self.is_privileged() {
+			return Err(Error(EPERM));
+		}
+		self.groups = groups;
+		Ok(())
+	}
+
+	/// Create new session and process group led by the process.
+	///
+	/// Fails with `EPERM` if the process already leads its process group.
+	///
+	/// See [setsid(2)](https://man7.org/linux/man-pages/man2/setsid.2.html).
+	pub fn set_sid(&mut self) -> Result<u32, Error> {
+		if self.pgid == self.pid {
+			return Err(Error(EPERM));
+		}
+		self.pgid = self.pid;
+		self.sid = self.pid;
+		Ok(self.sid)
+	}
+}
+
+impl Default for Credentials {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+fn update_id(id: &mut u32, new_id: u32) {
+	if new_id != KEEP_ID {
+		*id = new_id;
+	}
+}
+
+/// The ID that `setres*id` leave unchanged (-1).
+const KEEP_ID: u32 = u32::MAX;
+
+/// The default process ID.
+///
+/// The main thread ID is the process ID.
+const DEFAULT_PID: u32 = MAIN_THREAD_ID;
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec;
+
+	use super::*;
+
+	fn file(uid: u32, gid: u32, mode: u32) -> Metadata {
+		Metadata { uid, gid, mode: S_IFREG | mode, ..Default::default() }
+	}
+
+	#[test]
+	fn unprivileged_user_is_denied_root_group_file() {
+		let cred = Credentials::new().with_uid(1000).with_gid(1000);
+		let secret = file(0, 0, 0o640);
+		assert_eq!(Err(Error(EACCES)), cred.check_access(&secret, R_OK, false));
+		assert_eq!(Ok(()), Credentials::new().check_access(&secret, R_OK | W_OK, false));
+	}
+
+	#[test]
+	fn permission_classes() {
+		let cred = Credentials::new().with_uid(1000).with_gid(1000).with_groups([20]);
+		// The owner class is used even if it grants less than the others.
+		assert_eq!(Err(Error(EACCES)), cred.check_access(&file(1000, 0, 0o077), R_OK, false));
+		assert_eq!(Ok(()), cred.check_access(&file(0, 1000, 0o040), R_OK, false));
+		assert_eq!(Ok(()), cred.check_access(&file(0, 20, 0o060), R_OK | W_OK, false));
+		assert_eq!(Err(Error(EACCES)), cred.check_access(&file(0, 20, 0o040), W_OK, false));
+		assert_eq!(Ok(()), cred.check_access(&file(0, 0, 0o001), X_OK, false));
+		assert_eq!(Ok(()), cred.check_access(&file(0, 0, 0o000), F_OK, false));
+	}
+
+	#[test]
+	fn superuser_executes_only_executable_files() {
+		let cred = Credentials::new();
+		assert_eq!(Err(Error(EACCES)), cred.check_access(&file(1, 1, 0o666), X_OK, false));
+		assert_eq!(Ok(()), cred.check_access(&file(1, 1, 0o100), X_OK, false));
+		let dir = Metadata { mode: S_IFDIR, ..Default::default() };
+		assert_eq!(Ok(()), cred.check_access(&dir, X_OK, false));
+	}
+
+	#[test]
+	fn real_ids_are_used_on_request() {
+		let mut cred = Credentials::new().with_uid(1000);
+		cred.euid = 0;
+		let secret = file(0, 0, 0o600);
+		assert_eq!(Ok(()), cred.check_access(&secret, R_OK, false));
+		assert_eq!(Err(Error(EACCES)), cred.check_access(&secret, R_OK, true));
+	}
+
+	#[test]
+	fn set_uid() {
+		let mut cred = Credentials::new();
+		assert_eq!(Ok(()), cred.set_uid(1000));
+		assert_eq!((1000, 1000, 1000), (cred.uid, cred.euid, cred.suid));
+		// The privileges are dropped for good.
+		assert_eq!(Err(Error(EPERM)), cred.set_uid(0));
+		assert_eq!(Err(Error(EINVAL)), cred.set_uid(KEEP_ID));
+	}
+
+	#[test]
+	fn set_res_uid() {
+		let mut cred = Credentials::new();
+		assert_eq!(Ok(()), cred.set_res_uid(1000, 1001, KEEP_ID));
+		assert_eq!((1000, 1001, 0), (cred.uid, cred.euid, cred.suid));
+		// The saved ID allows regaining the privileges.
+		assert_eq!(Ok(()), cred.set_res_uid(KEEP_ID, 0, KEEP_ID));
+		assert_eq!(Ok(()), cred.set_res_uid(1000, 1000, 1000));
+		assert_eq!(Err(Error(EPERM)), cred.set_res_uid(KEEP_ID, 0, KEEP_ID));
+		assert_eq!(Err(Error(EPERM)), cred.set_uid(1001));
+		assert_eq!((1000, 1000, 1000), (cred.uid, cred.euid, cred.suid));
+	}
+
+	#[test]
+	fn set_res_gid() {
+		let mut cred = Credentials::new().with_uid(1000).with_gid(100);
+		assert_eq!(Err(Error(EPERM)), cred.set_res_gid(0, KEEP_ID, KEEP_ID));
+		assert_eq!(Err(Error(EPERM)), cred.set_gid(0));
+		assert_eq!(Ok(()), cred.set_res_gid(KEEP_ID, 100, 100));
+		let mut cred = Credentials::new();
+		assert_eq!(Ok(()), cred.set_gid(100));
+		assert_eq!((100, 100, 100), (cred.gid, cred.egid, cred.sgid));
+	}
+
+	#[test]
+	fn set_groups() {
+		let mut cred = Credentials::new();
+		assert_eq!(Ok(()), cred.set_groups(vec![1, 2]));
+		assert_eq!(vec![1, 2], cred.groups);
+		let mut cred = cred.with_uid(1000);
+		assert_eq!(Err(Error(EPERM)), cred.set_groups(vec![0]));
+		assert_eq!(vec![1, 2], cred.groups);
+	}
+
+	#[test]
+	fn set_sid() {
+		let mut cred = Credentials::new().with_pid(10);
+		// The process group leader can't create a session.
+		assert_eq!(Err(Error(EPERM)), cred.set_sid());
+		cred.pgid = 5;
+		cred.sid = 5;
+		assert_eq!(Ok(10), cred.set_sid());
+		assert_eq!((10, 10), (cred.pgid, cred.sid));
+	}
+}
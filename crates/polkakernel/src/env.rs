@@ -2,6 +2,13 @@ use crate::Error;
 
 /// Execution environment of a user-space program.
 pub trait Environment {
+	/// Read data from the standard input stream into the provided buffer.
+	///
+	/// Returns the number of bytes read. Zero means end of file. Fails with `EAGAIN` if no data is
+	/// available yet and the host prefers to suspend the program until there is
+	/// (see [`WaitReason::Input`](crate::WaitReason::Input)).
+	fn read_from_stdin(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+
 	/// Write the provided data to the standard output stream.
 	///
 	/// Returns the number of bytes written.
@@ -19,6 +26,13 @@ pub struct StdEnv;
 
 #[cfg(feature = "std")]
 impl Environment for StdEnv {
+	fn read_from_stdin(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+		use std::io::Read;
+		let stdin = std::io::stdin();
+		let mut stdin = stdin.lock();
+		Ok(stdin.read(buf)?)
+	}
+
 	fn write_to_stdout(&mut self, data: &[u8]) -> Result<u64, Error> {
 		use std::io::Write;
 		let stdout = std::io::stdout();
@@ -18,15 +18,35 @@ impl Error {
 	pub(crate) const fn as_str(self) -> Option<&'static str> {
 		Some(match self.0 {
 			EACCES => "EACCES",
+			EAGAIN => "EAGAIN",
 			EBADF => "EBADF",
+			EBUSY => "EBUSY",
+			EDEADLK => "EDEADLK",
+			EEXIST => "EEXIST",
 			EFAULT => "EFAULT",
+			EFBIG => "EFBIG",
 			EINVAL => "EINVAL",
 			EIO => "EIO",
+			ENODEV => "ENODEV",
 			ENOENT => "ENOENT",
+			ENOMEM => "ENOMEM",
 			ENOSYS => "ENOSYS",
 			EISDIR => "EISDIR",
+			ELOOP => "ELOOP",
+			EMFILE => "EMFILE",
+			EMLINK => "EMLINK",
+			ENAMETOOLONG => "ENAMETOOLONG",
 			ENOTDIR => "ENOTDIR",
+			ENOTEMPTY => "ENOTEMPTY",
+			ENOTTY => "ENOTTY",
+			EOVERFLOW => "EOVERFLOW",
+			EPERM => "EPERM",
+			EPIPE => "EPIPE",
 			ERANGE => "RANGE",
+			EROFS => "EROFS",
+			ESPIPE => "ESPIPE",
+			ETIMEDOUT => "ETIMEDOUT",
+			EXDEV => "EXDEV",
 			_ => return None,
 		})
 	}
@@ -54,13 +74,36 @@ impl core::fmt::Debug for Error {
 impl From<std::io::Error> for Error {
 	fn from(e: std::io::Error) -> Self {
 		use std::io::ErrorKind::*;
+		// Preserve the error code of the failed host syscall. The codes differ between operating
+		// systems, hence this is only done on Linux.
+		#[cfg(target_os = "linux")]
+		if let Some(errno) = e.raw_os_error() {
+			return Self(errno as u64);
+		}
 		Self(match e.kind() {
-			InvalidData => EINVAL,
-			InvalidInput => EINVAL,
 			NotFound => ENOENT,
 			PermissionDenied => EACCES,
+			AlreadyExists => EEXIST,
+			WouldBlock => EAGAIN,
+			NotADirectory => ENOTDIR,
+			IsADirectory => EISDIR,
+			DirectoryNotEmpty => ENOTEMPTY,
+			ReadOnlyFilesystem => EROFS,
+			StorageFull => ENOSPC,
+			NotSeekable => ESPIPE,
+			FileTooLarge => EFBIG,
+			ResourceBusy => EBUSY,
+			CrossesDevices => EXDEV,
+			TooManyLinks => EMLINK,
+			InvalidFilename => ENAMETOOLONG,
+			Deadlock => EDEADLK,
+			BrokenPipe => EPIPE,
+			Interrupted => EINTR,
+			TimedOut => ETIMEDOUT,
+			OutOfMemory => ENOMEM,
 			Unsupported => ENOSYS,
-			_ => EINVAL,
+			InvalidData | InvalidInput => EINVAL,
+			_ => EIO,
 		})
 	}
 }
@@ -0,0 +1,409 @@
+use alloc::{
+	collections::{BTreeMap, VecDeque},
+	ffi::CString,
+};
+
+use crate::{libc::*, Error};
+
+/// The maximum number of open file descriptors (`RLIMIT_NOFILE`).
+pub const MAX_FDS: u32 = 1024;
+
+/// The maximum number of bytes a pipe can hold.
+pub const PIPE_CAPACITY: usize = 65536;
+
+/// The object an [`OpenFile`] refers to.
+#[derive(Debug)]
+pub enum FileObject<Fd> {
+	/// A file or a directory opened via [`FileSystem`](crate::FileSystem).
+	File {
+		/// File system handle.
+		fd: Fd,
+		/// Normalized path the file was opened with.
+		path: CString,
+	},
+	/// Standard input stream of the [`Environment`](crate::Environment).
+	Stdin,
+	/// Standard output stream of the [`Environment`](crate::Environment).
+	Stdout,
+	/// Standard error stream of the [`Environment`](crate::Environment).
+	Stderr,
+	/// The terminal opened for reading and writing.
+	///
+	/// Reads from the standard input stream and writes to the standard output stream of the
+	/// [`Environment`](crate::Environment).
+	Tty,
+	/// `/dev/urandom` served from [`KernelState::random`](crate::KernelState::random).
+	Urandom,
+	/// The read end of the pipe with the provided identifier.
+	PipeReader(u64),
+	/// The write end of the pipe with the provided identifier.
+	PipeWriter(u64),
+}
+
+impl<Fd> FileObject<Fd> {
+	/// Check if the object is the terminal served by the [`Environment`](crate::Environment).
+	pub const fn is_terminal(&self) -> bool {
+		matches!(self, Self::Stdin | Self::Stdout | Self::Stderr | Self::Tty)
+	}
+
+	/// Get the identifier of the pipe if the object is one of its ends.
+	pub const fn pipe_id(&self) -> Option<u64> {
+		match self {
+			Self::PipeReader(id) | Self::PipeWriter(id) => Some(*id),
+			_ => None,
+		}
+	}
+}
+
+/// Open file description.
+///
+/// Shared by the file descriptors duplicated via `dup`, `dup3` and `fcntl(F_DUPFD)`.
+#[derive(Debug)]
+pub struct OpenFile<Fd> {
+	/// The underlying object.
+	pub object: FileObject<Fd>,
+	/// Access mode and file status flags (`O_*`).
+	pub flags: u64,
+}
+
+/// File descriptor table entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdEntry {
+	/// The identifier of the open file description.
+	pub file: u64,
+	/// `FD_CLOEXEC` flag.
+	pub cloexec: bool,
+}
+
+/// In-kernel pipe buffer.
+///
+/// See [pipe(7)](https://man7.org/linux/man-pages/man7/pipe.7.html).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pipe {
+	/// Buffered data.
+	pub buf: VecDeque<u8>,
+	/// The number of open file descriptions that refer to the read end.
+	pub readers: u32,
+	/// The number of open file descriptions that refer to the write end.
+	pub writers: u32,
+}
+
+impl Pipe {
+	/// Move buffered data into `buf`.
+	///
+	/// Returns the number of bytes read.
+	pub fn read(&mut self, buf: &mut [u8]) -> usize {
+		let n = buf.len().min(self.buf.len());
+		for (dst, src) in buf.iter_mut().zip(self.buf.drain(..n)) {
+			*dst = src;
+		}
+		n
+	}
+
+	/// Append as much of `data` as the remaining capacity allows.
+	///
+	/// Returns the number of bytes written.
+	pub fn write(&mut self, data: &[u8]) -> usize {
+		let n = data.len().min(PIPE_CAPACITY - self.buf.len());
+		self.buf.extend(&data[..n]);
+		n
+	}
+}
+
+/// File descriptor table.
+///
+/// File descriptors refer to open file descriptions by identifier, hence duplicated descriptors
+/// share the file offset and status flags like on Linux.
+#[derive(Debug)]
+pub struct FdTable<Fd> {
+	/// File descriptors.
+	pub fds: BTreeMap<u32, FdEntry>,
+	/// Open file descriptions by identifier.
+	pub files: BTreeMap<u64, OpenFile<Fd>>,
+	/// Pipes by identifier.
+	pub pipes: BTreeMap<u64, Pipe>,
+	/// The identifier of the next open file description or pipe.
+	pub next_id: u64,
+}
+
+impl<Fd> FdTable<Fd> {
+	/// Create new table with the standard streams open as file descriptors 0, 1 and 2.
+	pub fn new() -> Self {
+		let mut table = Self {
+			fds: BTreeMap::new(),
+			files: BTreeMap::new(),
+			pipes: BTreeMap::new(),
+			next_id: 0,
+		};
+		let streams = [
+			(FILENO_STDIN, FileObject::Stdin, O_RDONLY),
+			(FILENO_STDOUT, FileObject::Stdout, O_WRONLY),
+			(FILENO_STDERR, FileObject::Stderr, O_WRONLY),
+		];
+		for (fd, object, flags) in streams {
+			let id = table.next_id;
+			table.next_id += 1;
+			table.files.insert(id, OpenFile { object, flags });
+			table.fds.insert(fd, FdEntry { file: id, cloexec: false });
+		}
+		table
+	}
+
+	/// Check if the file descriptor is open.
+	pub fn contains(&self, fd: u32) -> bool {
+		self.fds.contains_key(&fd)
+	}
+
+	/// Get the open file description the file descriptor refers to.
+	pub fn get(&self, fd: u32) -> Option<&OpenFile<Fd>> {
+		self.files.get(&self.fds.get(&fd)?.file)
+	}
+
+	/// Get the open file description the file descriptor refers to.
+	pub fn get_mut(&mut self, fd: u32) -> Option<&mut OpenFile<Fd>> {
+		self.files.get_mut(&self.fds.get(&fd)?.file)
+	}
+
+	/// Get the file system handle the file descriptor refers to.
+	///
+	/// Fails with `error` if the descriptor doesn't refer to a file system object.
+	pub fn file_mut(&mut self, fd: u32, error: Error) -> Result<&mut Fd, Error> {
+		match self.get_mut(fd) {
+			Some(OpenFile { object: FileObject::File { fd, .. }, .. }) => Ok(fd),
+			Some(..) => Err(error),
+			None => Err(Error(EBADF)),
+		}
+	}
+
+	/// Get the path of the file the file descriptor refers to.
+	///
+	/// Fails with `ENOTDIR` if the descriptor doesn't refer to a file system object.
+	pub fn path(&self, fd: u32) -> Result<&CString, Error> {
+		match self.get(fd) {
+			Some(OpenFile { object: FileObject::File { path, .. }, .. }) => Ok(path),
+			Some(..) => Err(Error(ENOTDIR)),
+			None => Err(Error(EBADF)),
+		}
+	}
+
+	/// Find the lowest-numbered free file descriptor that is greater than or equal to `min_fd`.
+	pub fn free_fd(&self, min_fd: u32) -> Result<u32, Error> {
+		let mut fd = min_fd;
+		for used in self.fds.range(min_fd..).map(|(fd, _)| *fd) {
+			if used != fd {
+				break;
+			}
+			fd += 1;
+		}
+		if fd >= MAX_FDS {
+			return Err(Error(EMFILE));
+		}
+		Ok(fd)
+	}
+
+	/// Create new open file description and the lowest-numbered file descriptor that refers to it.
+	pub fn open(&mut self, file: OpenFile<Fd>, min_fd: u32, cloexec: bool) -> Result<u32, Error> {
+		let fd = self.free_fd(min_fd)?;
+		let id = self.next_id;
+		self.next_id += 1;
+		match file.object {
+			FileObject::PipeReader(pipe) => self.pipes.entry(pipe).or_default().readers += 1,
+			FileObject::PipeWriter(pipe) => self.pipes.entry(pipe).or_default().writers += 1,
+			_ => {},
+		}
+		self.files.insert(id, file);
+		self.fds.insert(fd, FdEntry { file: id, cloexec });
+		Ok(fd)
+	}
+
+	/// Create new pipe.
+	///
+	/// Returns the read and write end file descriptors. `flags` may contain `O_NONBLOCK`.
+	pub fn pipe(&mut self, flags: u64, cloexec: bool) -> Result<(u32, u32), Error> {
+		// Check the limit upfront to not leave a half-open pipe.
+		let fd0 = self.free_fd(0)?;
+		self.free_fd(fd0 + 1)?;
+		let id = self.next_id;
+		self.next_id += 1;
+		let reader = OpenFile { object: FileObject::PipeReader(id), flags: flags | O_RDONLY };
+		let writer = OpenFile { object: FileObject::PipeWriter(id), flags: flags | O_WRONLY };
+		let fd0 = self.open(reader, 0, cloexec)?;
+		let fd1 = self.open(writer, 0, cloexec)?;
+		Ok((fd0, fd1))
+	}
+
+	/// Create the lowest-numbered file descriptor greater than or equal to `min_fd` that refers
+	/// to the same open file description as `old_fd`.
+	pub fn dup(&mut self, old_fd: u32, min_fd: u32, cloexec: bool) -> Result<u32, Error> {
+		let file = self.fds.get(&old_fd).ok_or(Error(EBADF))?.file;
+		let fd = self.free_fd(min_fd)?;
+		self.fds.insert(fd, FdEntry { file, cloexec });
+		Ok(fd)
+	}
+
+	/// Make `new_fd` refer to the same open file description as `old_fd`.
+	///
+	/// The file previously referred to by `new_fd` is closed silently.
+	pub fn dup_to(&mut self, old_fd: u32, new_fd: u32, cloexec: bool) -> Result<(), Error> {
+		let file = self.fds.get(&old_fd).ok_or(Error(EBADF))?.file;
+		if new_fd >= MAX_FDS {
+			return Err(Error(EBADF));
+		}
+		if let Some(old) = self.fds.insert(new_fd, FdEntry { file, cloexec }) {
+			self.release(old.file);
+		}
+		Ok(())
+	}
+
+	/// Close the file descriptor.
+	pub fn close(&mut self, fd: u32) -> Result<(), Error> {
+		let entry = self.fds.remove(&fd).ok_or(Error(EBADF))?;
+		self.release(entry.file);
+		Ok(())
+	}
+
+	/// Drop the open file description unless other file descriptors refer to it.
+	fn release(&mut self, id: u64) {
+		if self.fds.values().any(|entry| entry.file == id) {
+			return;
+		}
+		let Some(file) = self.files.remove(&id) else {
+			return;
+		};
+		let (pipe_id, is_reader) = match file.object {
+			FileObject::PipeReader(pipe_id) => (pipe_id, true),
+			FileObject::PipeWriter(pipe_id) => (pipe_id, false),
+			_ => return,
+		};
+		let Some(pipe) = self.pipes.get_mut(&pipe_id) else {
+			return;
+		};
+		if is_reader {
+			pipe.readers -= 1;
+		} else {
+			pipe.writers -= 1;
+		}
+		if pipe.readers == 0 && pipe.writers == 0 {
+			self.pipes.remove(&pipe_id);
+		}
+	}
+}
+
+impl<Fd> Default for FdTable<Fd> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn file() -> OpenFile<()> {
+		OpenFile { object: FileObject::File { fd: (), path: c"/file".into() }, flags: O_RDWR }
+	}
+
+	#[test]
+	fn standard_streams_are_open() {
+		let table = FdTable::<()>::new();
+		assert!(matches!(
+			table.get(FILENO_STDIN),
+			Some(OpenFile { object: FileObject::Stdin, .. })
+		));
+		assert!(matches!(
+			table.get(FILENO_STDOUT),
+			Some(OpenFile { object: FileObject::Stdout, .. })
+		));
+		assert!(matches!(
+			table.get(FILENO_STDERR),
+			Some(OpenFile { object: FileObject::Stderr, .. })
+		));
+		assert_eq!(3, table.files.len());
+	}
+
+	#[test]
+	fn lowest_free_fd_is_used() {
+		let mut table = FdTable::new();
+		assert_eq!(Ok(3), table.open(file(), 0, false));
+		assert_eq!(Ok(10), table.open(file(), 10, true));
+		assert_eq!(Ok(()), table.close(FILENO_STDOUT));
+		assert_eq!(Ok(1), table.open(file(), 0, false));
+		assert_eq!(Ok(4), table.free_fd(0));
+		assert_eq!(Ok(11), table.free_fd(10));
+		assert_eq!(Err(Error(EMFILE)), table.free_fd(MAX_FDS));
+		assert_eq!(Err(Error(EBADF)), table.close(5));
+	}
+
+	#[test]
+	fn duplicates_share_open_file_description() {
+		let mut table = FdTable::new();
+		let fd = table.open(file(), 0, false).unwrap();
+		assert_eq!(Ok(5), table.dup(fd, 5, true));
+		assert_eq!(
+			Some(FdEntry { file: table.fds[&fd].file, cloexec: true }),
+			table.fds.get(&5).copied()
+		);
+		table.get_mut(5).unwrap().flags |= O_APPEND;
+		assert_eq!(O_RDWR | O_APPEND, table.get(fd).unwrap().flags);
+		assert_eq!(Ok(()), table.close(fd));
+		assert_eq!(Ok(&c"/file".into()), table.path(5));
+		assert_eq!(Ok(()), table.close(5));
+		assert_eq!(3, table.files.len());
+		assert_eq!(Err(Error(EBADF)), table.dup(fd, 0, false));
+	}
+
+	#[test]
+	fn dup_to_replaces_file() {
+		let mut table = FdTable::new();
+		let fd = table.open(file(), 0, false).unwrap();
+		assert_eq!(Ok(()), table.dup_to(fd, FILENO_STDOUT, false));
+		assert!(matches!(
+			table.get(FILENO_STDOUT),
+			Some(OpenFile { object: FileObject::File { .. }, .. })
+		));
+		// The standard output stream is released.
+		assert_eq!(3, table.files.len());
+		assert_eq!(Err(Error(EBADF)), table.dup_to(fd, MAX_FDS, false));
+		assert_eq!(Err(Error(EBADF)), table.dup_to(MAX_FDS, 0, false));
+		assert_eq!(Err(Error(ENOTDIR)), table.path(FILENO_STDERR));
+		assert_eq!(Err(Error(ESPIPE)), table.file_mut(FILENO_STDERR, Error(ESPIPE)).map(|_| ()));
+	}
+
+	#[test]
+	fn pipe_ends_are_counted() {
+		let mut table = FdTable::<()>::new();
+		assert_eq!(Ok((3, 4)), table.pipe(O_NONBLOCK, false));
+		let id = table.get(3).unwrap().object.pipe_id().unwrap();
+		assert_eq!(O_NONBLOCK | O_WRONLY, table.get(4).unwrap().flags);
+		table.dup(4, 0, false).unwrap();
+		assert_eq!((1, 1), (table.pipes[&id].readers, table.pipes[&id].writers));
+		table.close(4).unwrap();
+		assert_eq!(1, table.pipes[&id].writers);
+		table.close(5).unwrap();
+		assert_eq!((1, 0), (table.pipes[&id].readers, table.pipes[&id].writers));
+		table.close(3).unwrap();
+		assert!(table.pipes.is_empty());
+	}
+
+	#[test]
+	fn pipe_fails_without_two_free_fds() {
+		let mut table = FdTable::new();
+		for _ in 3..MAX_FDS - 1 {
+			table.open(file(), 0, false).unwrap();
+		}
+		assert_eq!(Err(Error(EMFILE)), table.pipe(0, false));
+		assert!(table.pipes.is_empty());
+	}
+
+	#[test]
+	fn pipe_buffer_is_bounded() {
+		let mut pipe = Pipe::default();
+		assert_eq!(PIPE_CAPACITY - 1, pipe.write(&[1; PIPE_CAPACITY - 1]));
+		assert_eq!(1, pipe.write(&[2, 3]));
+		assert_eq!(0, pipe.write(&[3]));
+		let mut buf = [0; PIPE_CAPACITY + 1];
+		assert_eq!(PIPE_CAPACITY, pipe.read(&mut buf));
+		assert_eq!([1, 2], buf[PIPE_CAPACITY - 2..PIPE_CAPACITY]);
+		assert_eq!(0, pipe.read(&mut buf));
+	}
+}
@@ -0,0 +1,462 @@
+//! Synthetic `/dev` [`FileSystem`].
+
+use alloc::{ffi::CString, format, vec, vec::Vec};
+use core::ffi::CStr;
+
+use crate::{
+	libc::*, normalize_path, to_cstring, write_dir_entry, Error, FileSystem, FileType, Metadata,
+	SeekFrom, WriteDirEntryErr,
+};
+
+/// `/dev/null` device number.
+pub const NULL_DEVICE: u64 = makedev(1, 3);
+
+/// `/dev/zero` device number.
+pub const ZERO_DEVICE: u64 = makedev(1, 5);
+
+/// `/dev/full` device number.
+pub const FULL_DEVICE: u64 = makedev(1, 7);
+
+/// `/dev/urandom` device number.
+pub const URANDOM_DEVICE: u64 = makedev(1, 9);
+
+/// `/dev/tty` device number.
+pub const TTY_DEVICE: u64 = makedev(5, 0);
+
+/// `/dev/pts/0` device number.
+///
+/// This is the terminal the standard streams refer to.
+pub const PTS_DEVICE: u64 = makedev(136, 0);
+
+/// An implementation of [`FileSystem`] that provides character devices.
+///
+/// The file system is meant to be mounted at `/dev` via
+/// [`MountTable`](crate::mount::MountTable) and provides `null`, `zero`, `full`, `urandom`, `tty`
+/// and `pts/0` devices as well as `stdin`, `stdout` and `stderr` symbolic links to
+/// `/proc/self/fd`, see [`ProcFileSystem`](crate::proc::ProcFileSystem).
+///
+/// `tty` and `urandom` are served by [`Kernel`](crate::Kernel) via
+/// [`Environment`](crate::Environment) and [`KernelState::random`](crate::KernelState::random)
+/// respectively, hence reading them and the other terminal directly yields end of file and the
+/// writes are discarded. The file system can't be modified, such attempts fail with `EACCES`.
+///
+/// `pts/0` is owned by the superuser unless configured otherwise via
+/// [`with_tty_owner`](Self::with_tty_owner).
+///
+/// See [null(4)](https://man7.org/linux/man-pages/man4/null.4.html),
+/// [random(4)](https://man7.org/linux/man-pages/man4/random.4.html),
+/// [tty(4)](https://man7.org/linux/man-pages/man4/tty.4.html).
+#[derive(Debug, Clone, Default)]
+pub struct DevFileSystem {
+	/// The user ID that owns `pts/0`.
+	pub tty_uid: u32,
+	/// The group ID that owns `pts/0`.
+	pub tty_gid: u32,
+}
+
+impl DevFileSystem {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the owner of `pts/0`.
+	///
+	/// This should be the user the program runs as, i.e. the real IDs of
+	/// [`KernelState::credentials`](crate::KernelState::credentials).
+	pub fn with_tty_owner(mut self, uid: u32, gid: u32) -> Self {
+		self.tty_uid = uid;
+		self.tty_gid = gid;
+		self
+	}
+
+	/// Get the metadata of the node.
+	fn node_metadata(&self, node: DevNode) -> Metadata {
+		let (size, nlink) = match node {
+			DevNode::Root => (0, 3),
+			DevNode::Pts => (0, 2),
+			DevNode::Stream(..) =>
+				(node.link_target().map_or(0, |target| target.as_bytes().len()) as u64, 1),
+			DevNode::Device(..) => (0, 1),
+		};
+		let (uid, gid) = match node {
+			DevNode::Device(Device::Pts0) => (self.tty_uid, self.tty_gid),
+			_ => (0, 0),
+		};
+		Metadata {
+			id: node.id(),
+			size,
+			mode: node.mode(),
+			block_size: PAGE_SIZE,
+			nlink,
+			uid,
+			gid,
+			device: DEV_DEVICE,
+			rdev: node.rdev(),
+			..Default::default()
+		}
+	}
+}
+
+/// [`DevFileSystem`] node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DevNode {
+	/// `/dev`.
+	Root,
+	/// `/dev/pts`.
+	Pts,
+	/// Character device.
+	Device(Device),
+	/// `/dev/stdin`, `/dev/stdout` or `/dev/stderr` link to the file descriptor.
+	Stream(u32),
+}
+
+/// [`DevFileSystem`] character device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+	Null,
+	Zero,
+	Full,
+	Urandom,
+	Tty,
+	Pts0,
+}
+
+impl DevNode {
+	/// Find the node the path refers to.
+	fn lookup(path: &CStr) -> Result<Self, Error> {
+		let path = normalize_path(path);
+		let node = match path.to_bytes() {
+			b"/" => Self::Root,
+			b"/pts" => Self::Pts,
+			b"/pts/0" => Self::Device(Device::Pts0),
+			b"/null" => Self::Device(Device::Null),
+			b"/zero" => Self::Device(Device::Zero),
+			b"/full" => Self::Device(Device::Full),
+			b"/urandom" => Self::Device(Device::Urandom),
+			b"/tty" => Self::Device(Device::Tty),
+			b"/stdin" => Self::Stream(FILENO_STDIN),
+			b"/stdout" => Self::Stream(FILENO_STDOUT),
+			b"/stderr" => Self::Stream(FILENO_STDERR),
+			_ => return Err(Error(ENOENT)),
+		};
+		Ok(node)
+	}
+
+	/// Get the inode number.
+	const fn id(self) -> u64 {
+		match self {
+			Self::Root => 1,
+			Self::Pts => 2,
+			Self::Device(Device::Null) => 3,
+			Self::Device(Device::Zero) => 4,
+			Self::Device(Device::Full) => 5,
+			Self::Device(Device::Urandom) => 6,
+			Self::Device(Device::Tty) => 7,
+			Self::Device(Device::Pts0) => 8,
+			Self::Stream(fd) => 16 + fd as u64,
+		}
+	}
+
+	/// Get the file type and permissions.
+	const fn mode(self) -> u32 {
+		match self {
+			Self::Root | Self::Pts => S_IFDIR | 0o755,
+			Self::Device(Device::Pts0) => S_IFCHR | 0o620,
+			Self::Device(..) => S_IFCHR | 0o666,
+			Self::Stream(..) => S_IFLNK | 0o777,
+		}
+	}
+
+	/// Get the device number.
+	const fn rdev(self) -> u64 {
+		match self {
+			Self::Device(Device::Null) => NULL_DEVICE,
+			Self::Device(Device::Zero) => ZERO_DEVICE,
+			Self::Device(Device::Full) => FULL_DEVICE,
+			Self::Device(Device::Urandom) => URANDOM_DEVICE,
+			Self::Device(Device::Tty) => TTY_DEVICE,
+			Self::Device(Device::Pts0) => PTS_DEVICE,
+			_ => 0,
+		}
+	}
+
+	/// Get the target of the symbolic link.
+	fn link_target(self) -> Option<CString> {
+		let Self::Stream(fd) = self else {
+			return None;
+		};
+		Some(to_cstring(format!("/proc/self/fd/{fd}")))
+	}
+
+	/// Get the entries of the directory.
+	fn dir_entries(self) -> Vec<(&'static CStr, Self)> {
+		match self {
+			Self::Root => vec![
+				(c".", Self::Root),
+				(c"..", Self::Root),
+				(c"full", Self::Device(Device::Full)),
+				(c"null", Self::Device(Device::Null)),
+				(c"pts", Self::Pts),
+				(c"stderr", Self::Stream(FILENO_STDERR)),
+				(c"stdin", Self::Stream(FILENO_STDIN)),
+				(c"stdout", Self::Stream(FILENO_STDOUT)),
+				(c"tty", Self::Device(Device::Tty)),
+				(c"urandom", Self::Device(Device::Urandom)),
+				(c"zero", Self::Device(Device::Zero)),
+			],
+			Self::Pts =>
+				vec![(c".", Self::Pts), (c"..", Self::Root), (c"0", Self::Device(Device::Pts0))],
+			_ => Vec::new(),
+		}
+	}
+}
+
+impl FileSystem for DevFileSystem {
+	type Fd = DevFd;
+
+	fn open(&mut self, path: &CStr, flags: u64, _mode: u32) -> Result<Self::Fd, Error> {
+		let node = match DevNode::lookup(path) {
+			Ok(..) if flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL => return Err(Error(EEXIST)),
+			Ok(node) => node,
+			Err(Error(ENOENT)) if flags & O_CREAT != 0 => return Err(Error(EACCES)),
+			Err(e) => return Err(e),
+		};
+		match node {
+			DevNode::Root | DevNode::Pts if flags & O_ACCMODE != O_RDONLY => Err(Error(EISDIR)),
+			DevNode::Root | DevNode::Pts => Ok(DevFd { node, flags, position: 0 }),
+			_ if flags & O_DIRECTORY != 0 => Err(Error(ENOTDIR)),
+			// The kernel follows symbolic links before opening the file.
+			DevNode::Stream(..) => Err(Error(ELOOP)),
+			DevNode::Device(..) => Ok(DevFd { node, flags, position: 0 }),
+		}
+	}
+
+	fn seek(&mut self, fd: &mut Self::Fd, from: SeekFrom) -> Result<u64, Error> {
+		if let DevNode::Device(..) = fd.node {
+			// Seeking character devices has no effect.
+			return Ok(0);
+		}
+		fd.position = match from {
+			SeekFrom::Start(offset) => offset,
+			SeekFrom::Current(offset) =>
+				fd.position.checked_add_signed(offset).ok_or(Error(EINVAL))?,
+			SeekFrom::End(..) => return Err(Error(EINVAL)),
+		};
+		Ok(fd.position)
+	}
+
+	fn read(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		let DevNode::Device(device) = fd.node else {
+			return Err(Error(EISDIR));
+		};
+		if fd.flags & O_ACCMODE == O_WRONLY {
+			return Err(Error(EBADF));
+		}
+		match device {
+			Device::Null | Device::Urandom | Device::Tty | Device::Pts0 => Ok(0),
+			Device::Zero | Device::Full => {
+				buf.fill(0_u8);
+				Ok(buf.len())
+			},
+		}
+	}
+
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], _offset: u64) -> Result<usize, Error> {
+		self.read(fd, buf)
+	}
+
+	fn write(&mut self, fd: &mut Self::Fd, buf: &[u8]) -> Result<usize, Error> {
+		let DevNode::Device(device) = fd.node else {
+			return Err(Error(EBADF));
+		};
+		if fd.flags & O_ACCMODE == O_RDONLY {
+			return Err(Error(EBADF));
+		}
+		match device {
+			Device::Full => Err(Error(ENOSPC)),
+			_ => Ok(buf.len()),
+		}
+	}
+
+	fn truncate(&mut self, _fd: &mut Self::Fd, _length: u64) -> Result<(), Error> {
+		Err(Error(EINVAL))
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		let entries = match fd.node {
+			DevNode::Root | DevNode::Pts => fd.node.dir_entries(),
+			_ => return Err(Error(ENOTDIR)),
+		};
+		let mut offset = 0;
+		for (i, (name, node)) in entries.into_iter().enumerate().skip(fd.position as usize) {
+			let next_position = i as u64 + 1;
+			let file_type = FileType::from_mode(node.mode());
+			match write_dir_entry(node.id(), next_position, file_type, name, &mut buf[offset..]) {
+				Ok(n) => {
+					offset += n;
+					fd.position = next_position;
+				},
+				Err(WriteDirEntryErr::BufferTooSmall) if offset != 0 => break,
+				Err(_) => return Err(Error(EINVAL)),
+			}
+		}
+		Ok(offset)
+	}
+
+	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		match DevNode::lookup(path)? {
+			// The targets of the links are outside of this file system.
+			DevNode::Stream(..) => Err(Error(ENOENT)),
+			node => Ok(self.node_metadata(node)),
+		}
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		Ok(self.node_metadata(DevNode::lookup(path)?))
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		DevNode::lookup(path)?.link_target().ok_or(Error(EINVAL))
+	}
+
+	fn symlink(&mut self, _target: &CStr, _path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn link(&mut self, _old_path: &CStr, _new_path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn unlink(&mut self, _path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn mkdir(&mut self, _path: &CStr, _mode: u32) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn rmdir(&mut self, _path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn rename(&mut self, _old_path: &CStr, _new_path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+}
+
+/// [`DevFileSystem`] file handle.
+#[derive(Debug)]
+pub struct DevFd {
+	node: DevNode,
+	/// Access mode and file status flags (`O_*`).
+	flags: u64,
+	/// The index of the next directory entry.
+	position: u64,
+}
+
+/// The device `/dev` belongs to.
+const DEV_DEVICE: u64 = makedev(0, 5);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn list(fs: &mut DevFileSystem, path: &CStr) -> Vec<CString> {
+		let mut fd = fs.open(path, O_RDONLY | O_DIRECTORY, 0).unwrap();
+		let mut names = Vec::new();
+		// Small buffer to read the listing in several calls.
+		let mut buf = [0_u8; 64];
+		loop {
+			let n = fs.read_dir(&mut fd, &mut buf).unwrap();
+			if n == 0 {
+				return names;
+			}
+			let mut offset = 0;
+			while offset < n {
+				let entry = &buf[offset..n];
+				names.push(CStr::from_bytes_until_nul(&entry[19..]).unwrap().into());
+				offset += u16::from_le_bytes([entry[16], entry[17]]) as usize;
+			}
+		}
+	}
+
+	#[test]
+	fn device_reads_and_writes() {
+		let mut fs = DevFileSystem::new();
+		let mut buf = [1_u8; 8];
+		let mut null = fs.open(c"/null", O_RDWR, 0).unwrap();
+		assert_eq!(Ok(0), fs.read(&mut null, &mut buf));
+		assert_eq!(Ok(3), fs.write(&mut null, b"abc"));
+		let mut zero = fs.open(c"/zero", O_RDWR, 0).unwrap();
+		assert_eq!(Ok(8), fs.read(&mut zero, &mut buf));
+		assert_eq!([0; 8], buf);
+		assert_eq!(Ok(3), fs.write(&mut zero, b"abc"));
+		let mut full = fs.open(c"/full", O_RDWR, 0).unwrap();
+		buf.fill(1);
+		assert_eq!(Ok(8), fs.read_at(&mut full, &mut buf, 100));
+		assert_eq!([0; 8], buf);
+		assert_eq!(Err(Error(ENOSPC)), fs.write(&mut full, b"abc"));
+		assert_eq!(Ok(0), fs.seek(&mut full, SeekFrom::End(10)));
+		// These are served by the kernel.
+		let mut tty = fs.open(c"/tty", O_RDWR, 0).unwrap();
+		assert_eq!(Ok(0), fs.read(&mut tty, &mut buf));
+		let mut urandom = fs.open(c"/urandom", O_RDONLY, 0).unwrap();
+		assert_eq!(Ok(0), fs.read(&mut urandom, &mut buf));
+	}
+
+	#[test]
+	fn access_mode_is_enforced() {
+		let mut fs = DevFileSystem::new();
+		let mut buf = [0_u8; 8];
+		let mut reader = fs.open(c"/zero", O_RDONLY, 0).unwrap();
+		assert_eq!(Err(Error(EBADF)), fs.write(&mut reader, b"abc"));
+		let mut writer = fs.open(c"/zero", O_WRONLY, 0).unwrap();
+		assert_eq!(Err(Error(EBADF)), fs.read(&mut writer, &mut buf));
+		let mut dir = fs.open(c"/", O_RDONLY, 0).unwrap();
+		assert_eq!(Err(Error(EISDIR)), fs.read(&mut dir, &mut buf));
+		assert_eq!(Err(Error(ENOTDIR)), fs.read_dir(&mut writer, &mut buf));
+	}
+
+	#[test]
+	fn file_system_is_read_only() {
+		let mut fs = DevFileSystem::new();
+		assert_eq!(Err(Error(EACCES)), fs.open(c"/new", O_WRONLY | O_CREAT, 0o644).map(|_| ()));
+		assert_eq!(Err(Error(EEXIST)), fs.open(c"/null", O_CREAT | O_EXCL, 0o644).map(|_| ()));
+		assert_eq!(Err(Error(EISDIR)), fs.open(c"/pts", O_WRONLY, 0).map(|_| ()));
+		assert_eq!(Err(Error(ENOTDIR)), fs.open(c"/null", O_DIRECTORY, 0).map(|_| ()));
+		assert_eq!(Err(Error(ELOOP)), fs.open(c"/stdin", O_RDONLY, 0).map(|_| ()));
+		assert_eq!(Err(Error(EACCES)), fs.unlink(c"/null"));
+		assert_eq!(Err(Error(EACCES)), fs.mkdir(c"/shm", 0o755));
+		assert_eq!(Err(Error(EACCES)), fs.rename(c"/null", c"/null2"));
+	}
+
+	#[test]
+	fn metadata_and_links() {
+		let mut fs = DevFileSystem::new().with_tty_owner(1000, 5);
+		let null = fs.metadata(c"/null").unwrap();
+		assert_eq!((S_IFCHR | 0o666, NULL_DEVICE, 0), (null.mode, null.rdev, null.uid));
+		let pts = fs.metadata(c"/pts/0").unwrap();
+		assert_eq!((S_IFCHR | 0o620, PTS_DEVICE), (pts.mode, pts.rdev));
+		assert_eq!((1000, 5), (pts.uid, pts.gid));
+		assert_eq!(TTY_DEVICE, fs.metadata(c"/tty").unwrap().rdev);
+		assert_eq!(Ok(c"/proc/self/fd/2".into()), fs.readlink(c"/stderr"));
+		assert_eq!(15, fs.symlink_metadata(c"/stdout").unwrap().size);
+		assert_eq!(Err(Error(ENOENT)), fs.metadata(c"/stdout"));
+		assert_eq!(Err(Error(EINVAL)), fs.readlink(c"/null"));
+		assert_eq!(Err(Error(ENOENT)), fs.metadata(c"/pts/1"));
+	}
+
+	#[test]
+	fn directory_listings() {
+		let mut fs = DevFileSystem::new();
+		let names: Vec<CString> = [
+			c".", c"..", c"full", c"null", c"pts", c"stderr", c"stdin", c"stdout", c"tty",
+			c"urandom", c"zero",
+		]
+		.map(CString::from)
+		.into();
+		assert_eq!(names, list(&mut fs, c"/"));
+		let names: Vec<CString> = [c".", c"..", c"0"].map(CString::from).into();
+		assert_eq!(names, list(&mut fs, c"/pts"));
+	}
+}
@@ -1,15 +1,297 @@
 //! In-memory [`FileSystem`].
 
-use alloc::{borrow::Cow, collections::BTreeMap, ffi::CString, sync::Arc};
-use core::ffi::CStr;
+use alloc::{borrow::Cow, collections::BTreeMap, ffi::CString, sync::Arc, vec, vec::Vec};
+use core::{
+	ffi::CStr,
+	ops::Bound::{Included, Unbounded},
+	time::Duration,
+};
 
-use crate::{libc::*, normalize_path, Error, FileSystem, Metadata, SeekFrom};
+use crate::{
+	dir_prefix, libc::*, normalize_path, parent_dir, resolve_links, to_cstring, write_dir_entry,
+	Error, FileSystem, FileType, Metadata, SeekFrom, WriteDirEntryErr,
+};
 
 /// In-memory file contents.
 pub type FileBlob = Cow<'static, [u8]>;
 
 /// An implementation of [`FileSystem`] that uses memory to store files.
-pub type InMemoryFileSystem = BTreeMap<CString, Arc<FileBlob>>;
+#[derive(Debug, Clone)]
+pub struct InMemoryFileSystem {
+	/// Inode numbers of the files, the directories and the symbolic links by normalized path.
+	///
+	/// Hard links are the entries with the same inode number. The root directory is implicit and
+	/// has [`ROOT_ID`] inode number. Its inode is only stored once it has subdirectories.
+	pub entries: BTreeMap<CString, u64>,
+	/// Files, directories and symbolic links by inode number.
+	pub inodes: BTreeMap<u64, InMemoryInode>,
+	/// The inode number of the most recently created node.
+	pub last_id: u64,
+	/// Metadata overrides by normalized path of the file or the directory.
+	pub attrs: BTreeMap<CString, InMemoryAttrs>,
+	/// Metadata of the files and the directories without overrides.
+	pub default_attrs: InMemoryAttrs,
+	/// The maximum size of a file in bytes.
+	///
+	/// Writes and truncations beyond this size fail with `EFBIG`.
+	pub max_file_size: u64,
+}
+
+impl InMemoryFileSystem {
+	pub fn new() -> Self {
+		Self {
+			entries: BTreeMap::new(),
+			inodes: BTreeMap::new(),
+			last_id: 0,
+			attrs: BTreeMap::new(),
+			default_attrs: InMemoryAttrs::default(),
+			max_file_size: DEFAULT_MAX_FILE_SIZE,
+		}
+	}
+
+	/// Set the maximum size of a file in bytes.
+	///
+	/// The default is [`DEFAULT_MAX_FILE_SIZE`].
+	pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+		self.max_file_size = max_file_size;
+		self
+	}
+
+	/// Add the file with the provided contents or replace the existing one.
+	///
+	/// Missing parent directories are created.
+	pub fn insert(&mut self, path: &CStr, contents: impl Into<FileBlob>) {
+		let path = normalize_path(path);
+		self.insert_parents(&path);
+		self.create(path, InMemoryNode::File(Arc::new(contents.into())));
+	}
+
+	/// Add the symbolic link that points to `target` or replace the existing file.
+	///
+	/// Missing parent directories are created.
+	pub fn insert_symlink(&mut self, path: &CStr, target: &CStr) {
+		let path = normalize_path(path);
+		self.insert_parents(&path);
+		self.create(path, InMemoryNode::Symlink(target.into()));
+	}
+
+	/// Add the directory and its missing parents.
+	pub fn insert_dir(&mut self, path: &CStr) {
+		let path = normalize_path(path);
+		self.insert_parents(&path);
+		if !is_dir(self, &path) {
+			self.create(path, InMemoryNode::Directory);
+		}
+	}
+
+	/// Create missing parent directories replacing the files that are in the way.
+	fn insert_parents(&mut self, path: &CStr) {
+		let path = path.to_bytes();
+		for (i, _) in path.iter().enumerate().skip(1).filter(|(_, byte)| **byte == b'/') {
+			let parent = to_cstring(&path[..i]);
+			if !is_dir(self, &parent) {
+				self.create(parent, InMemoryNode::Directory);
+			}
+		}
+	}
+
+	/// Get the inode the path refers to without following symbolic links.
+	fn inode(&self, path: &CStr) -> Option<(u64, &InMemoryNode)> {
+		self.inode_with_links(path).map(|(id, inode)| (id, &inode.node))
+	}
+
+	/// Get the inode the path refers to along with the link count.
+	fn inode_with_links(&self, path: &CStr) -> Option<(u64, &InMemoryInode)> {
+		if path.to_bytes() == b"/" {
+			return Some((ROOT_ID, self.inodes.get(&ROOT_ID).unwrap_or(&ROOT_INODE)));
+		}
+		let id = *self.entries.get(path)?;
+		Some((id, self.inodes.get(&id)?))
+	}
+
+	/// Check that the parent of the path is an existing directory.
+	fn check_parent(&self, path: &CStr) -> Result<(), Error> {
+		let parent = to_cstring(parent_dir(path.to_bytes()));
+		match self.inode(&parent) {
+			Some((_, InMemoryNode::Directory)) => Ok(()),
+			Some(..) => Err(Error(ENOTDIR)),
+			None => Err(Error(ENOENT)),
+		}
+	}
+
+	/// Create new file or directory with the specified permissions.
+	fn create_with_mode(&mut self, path: CString, node: InMemoryNode, mode: u32) -> u64 {
+		let attrs = InMemoryAttrs { mode: Some(mode & 0o7777), ..self.default_attrs };
+		self.attrs.insert(path.clone(), attrs);
+		self.create(path, node)
+	}
+
+	/// Allocate new inode and make `path` refer to it.
+	fn create(&mut self, path: CString, node: InMemoryNode) -> u64 {
+		self.last_id = self.last_id.max(ROOT_ID) + 1;
+		let id = self.last_id;
+		// `.` and the entry in the parent.
+		let nlink = if matches!(node, InMemoryNode::Directory) { 2 } else { 0 };
+		self.inodes.insert(id, InMemoryInode { node, nlink });
+		self.add_entry(path, id);
+		id
+	}
+
+	/// Make `path` refer to the inode replacing the previous entry.
+	fn add_entry(&mut self, path: CString, id: u64) {
+		match self.inodes.get_mut(&id) {
+			Some(InMemoryInode { node: InMemoryNode::Directory, .. }) =>
+				self.link_parent(&path, true),
+			Some(inode) => inode.nlink += 1,
+			None => {},
+		}
+		if let Some(old_id) = self.entries.insert(path.clone(), id) {
+			self.release(&path, old_id);
+		}
+	}
+
+	/// Remove the entry and the inode it refers to unless other hard links exist.
+	fn remove(&mut self, path: &CStr) {
+		if let Some(id) = self.entries.remove(path) {
+			self.release(path, id);
+		}
+	}
+
+	/// Account for the removed entry and drop the inode unless other hard links refer to it.
+	fn release(&mut self, path: &CStr, id: u64) {
+		let Some(inode) = self.inodes.get_mut(&id) else {
+			return;
+		};
+		if matches!(inode.node, InMemoryNode::Directory) {
+			self.inodes.remove(&id);
+			self.link_parent(path, false);
+			return;
+		}
+		inode.nlink -= 1;
+		if inode.nlink == 0 {
+			self.inodes.remove(&id);
+		}
+	}
+
+	/// Account for `..` of the added or the removed subdirectory in the link count of its parent.
+	fn link_parent(&mut self, path: &CStr, added: bool) {
+		let parent = to_cstring(parent_dir(path.to_bytes()));
+		let inode = if parent.to_bytes() == b"/" {
+			self.inodes.entry(ROOT_ID).or_insert(ROOT_INODE)
+		} else {
+			let Some(inode) = self.entries.get(&parent).and_then(|id| self.inodes.get_mut(id))
+			else {
+				return;
+			};
+			inode
+		};
+		if added {
+			inode.nlink += 1;
+		} else {
+			inode.nlink -= 1;
+		}
+	}
+
+	/// Get the metadata overrides of the existing file creating them from the defaults.
+	fn attrs_mut(&mut self, path: &CStr) -> Result<&mut InMemoryAttrs, Error> {
+		let path = normalize_path(path);
+		if self.inode(&path).is_none() {
+			return Err(Error(ENOENT));
+		}
+		Ok(self.attrs.entry(path).or_insert(self.default_attrs))
+	}
+
+	/// Build metadata from the overrides or the defaults.
+	fn metadata_of(
+		&self,
+		path: &CStr,
+		(id, inode): (u64, &InMemoryInode),
+		mode: u32,
+		size: u64,
+		block_size: u64,
+	) -> Metadata {
+		let attrs = self.attrs.get(path).unwrap_or(&self.default_attrs);
+		let mode = match attrs.mode {
+			// Symbolic links permissions are not used.
+			Some(permissions) if mode & S_IFMT != S_IFLNK => mode & S_IFMT | permissions & 0o7777,
+			_ => mode,
+		};
+		Metadata {
+			id,
+			size,
+			mode,
+			block_size,
+			nlink: inode.nlink,
+			uid: attrs.uid,
+			gid: attrs.gid,
+			device: attrs.device,
+			rdev: 0,
+			atime: attrs.atime,
+			mtime: attrs.mtime,
+			ctime: attrs.ctime,
+		}
+	}
+}
+
+impl Default for InMemoryFileSystem {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// [`InMemoryFileSystem`] inode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InMemoryInode {
+	/// File type and contents.
+	pub node: InMemoryNode,
+	/// The number of hard links.
+	///
+	/// Directories count `.`, the entry in the parent and `..` of each subdirectory.
+	pub nlink: u32,
+}
+
+/// The type and the contents of [`InMemoryInode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InMemoryNode {
+	/// Regular file contents.
+	File(Arc<FileBlob>),
+	/// Symbolic link target.
+	Symlink(CString),
+	/// Directory.
+	///
+	/// Directory entries are the [`InMemoryFileSystem::entries`] with the directory path prefix.
+	Directory,
+}
+
+/// The default maximum size of [`InMemoryFileSystem`] file in bytes.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1 << 30;
+
+/// The inode number of the [`InMemoryFileSystem`] root directory.
+pub const ROOT_ID: u64 = 1;
+
+/// The root directory without subdirectories.
+const ROOT_INODE: InMemoryInode = InMemoryInode { node: InMemoryNode::Directory, nlink: 2 };
+
+/// Configurable metadata of [`InMemoryFileSystem`] files and directories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InMemoryAttrs {
+	/// Permission bits.
+	///
+	/// Files have `0o644` and directories have `0o755` permissions by default.
+	pub mode: Option<u32>,
+	/// Owner user ID.
+	pub uid: u32,
+	/// Owner group ID.
+	pub gid: u32,
+	/// The ID of the device containing the file.
+	pub device: u64,
+	/// Last access time since the Unix epoch.
+	pub atime: Duration,
+	/// Last modification time since the Unix epoch.
+	pub mtime: Duration,
+	/// Last status change time since the Unix epoch.
+	pub ctime: Duration,
+}
 
 /// In-memory-specific file system error.
 #[derive(Debug)]
@@ -20,15 +302,65 @@ pub enum InMemoryError {
 impl FileSystem for InMemoryFileSystem {
 	type Fd = InMemoryFd;
 
-	fn open(&mut self, path: &CStr, _flags: u64) -> Result<Self::Fd, Error> {
+	fn open(&mut self, path: &CStr, flags: u64, mode: u32) -> Result<Self::Fd, Error> {
 		let path = normalize_path(path);
-		Self::get(self, &path)
-			.cloned()
-			.map(|blob| InMemoryFd { position: 0, blob })
-			.ok_or(Error(ENOENT))
+		if is_dir(self, &path) {
+			if flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL {
+				return Err(Error(EEXIST));
+			}
+			if flags & O_ACCMODE != O_RDONLY {
+				return Err(Error(EISDIR));
+			}
+			return Ok(InMemoryFd::Dir(InMemoryDir { position: 0, path }));
+		}
+		let inode = self
+			.inode(&path)
+			.map(|(id, inode)| (id, matches!(inode, InMemoryNode::File(..))));
+		if flags & O_DIRECTORY != 0 {
+			return Err(Error(if inode.is_some() { ENOTDIR } else { ENOENT }));
+		}
+		let writable = flags & O_ACCMODE != O_RDONLY;
+		let (id, blob) = match inode {
+			Some(..) if flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL => {
+				return Err(Error(EEXIST));
+			},
+			// The kernel follows symbolic links before opening the file.
+			Some((_, false)) => return Err(Error(ELOOP)),
+			Some((id, true)) => {
+				let Some(InMemoryInode { node: InMemoryNode::File(blob), .. }) =
+					self.inodes.get_mut(&id)
+				else {
+					return Err(Error(ENOENT));
+				};
+				if writable && flags & O_TRUNC != 0 {
+					*blob = Arc::<FileBlob>::default();
+				}
+				(id, blob.clone())
+			},
+			None if flags & O_CREAT != 0 => {
+				self.check_parent(&path)?;
+				let blob = Arc::<FileBlob>::default();
+				(self.create_with_mode(path, InMemoryNode::File(blob.clone()), mode), blob)
+			},
+			None => return Err(Error(ENOENT)),
+		};
+		Ok(InMemoryFd::File(InMemoryFile { position: 0, blob, id, flags }))
 	}
 
 	fn seek(&mut self, fd: &mut InMemoryFd, from: SeekFrom) -> Result<u64, Error> {
+		let fd = match fd {
+			InMemoryFd::File(fd) => fd,
+			InMemoryFd::Dir(dir) => {
+				dir.position = match from {
+					SeekFrom::Start(offset) => offset,
+					SeekFrom::Current(offset) =>
+						dir.position.checked_add_signed(offset).ok_or(Error(EINVAL))?,
+					SeekFrom::End(..) => return Err(Error(EINVAL)),
+				};
+				return Ok(dir.position);
+			},
+		};
+		fd.refresh(self);
 		match from {
 			SeekFrom::Start(offset) => fd.position = offset,
 			SeekFrom::Current(offset) =>
@@ -42,6 +374,11 @@ impl FileSystem for InMemoryFileSystem {
 	}
 
 	fn read(&mut self, fd: &mut InMemoryFd, buf: &mut [u8]) -> Result<usize, Error> {
+		let fd = fd.as_file_mut()?;
+		if fd.flags & O_ACCMODE == O_WRONLY {
+			return Err(Error(EBADF));
+		}
+		fd.refresh(self);
 		let size = fd.size();
 		let end = core::cmp::min(fd.position.wrapping_add(buf.len() as u64), size);
 		if fd.position >= end || fd.position >= size {
@@ -50,7 +387,7 @@ impl FileSystem for InMemoryFileSystem {
 		}
 		let slice = &fd.blob[fd.position as usize..end as usize];
 		let num_bytes_read = slice.len();
-		buf.copy_from_slice(slice);
+		buf[..num_bytes_read].copy_from_slice(slice);
 		log::trace!(
 			"  -> offset={}, length={}, new offset={}",
 			fd.position,
@@ -61,30 +398,402 @@ impl FileSystem for InMemoryFileSystem {
 		Ok(num_bytes_read)
 	}
 
+	fn read_at(
+		&mut self,
+		fd: &mut InMemoryFd,
+		buf: &mut [u8],
+		offset: u64,
+	) -> Result<usize, Error> {
+		let fd = fd.as_file_mut()?;
+		if fd.flags & O_ACCMODE == O_WRONLY {
+			return Err(Error(EBADF));
+		}
+		fd.refresh(self);
+		let size = fd.size();
+		if offset >= size {
+			return Ok(0);
+		}
+		let end = core::cmp::min(offset.saturating_add(buf.len() as u64), size);
+		let slice = &fd.blob[offset as usize..end as usize];
+		buf[..slice.len()].copy_from_slice(slice);
+		Ok(slice.len())
+	}
+
+	fn write(&mut self, fd: &mut InMemoryFd, buf: &[u8]) -> Result<usize, Error> {
+		let InMemoryFd::File(fd) = fd else {
+			return Err(Error(EBADF));
+		};
+		if fd.flags & O_ACCMODE == O_RDONLY {
+			return Err(Error(EBADF));
+		}
+		let flags = fd.flags;
+		let position = fd.position;
+		let max_file_size = self.max_file_size;
+		let new_position = fd.modify(self, |data| {
+			let start = if flags & O_APPEND != 0 { data.len() as u64 } else { position };
+			let end = start.checked_add(buf.len() as u64).ok_or(Error(EFBIG))?;
+			// Check before resizing to not exhaust the host memory.
+			if end > max_file_size {
+				return Err(Error(EFBIG));
+			}
+			let end: usize = end.try_into().map_err(|_| Error(EFBIG))?;
+			let start = start as usize;
+			if data.len() < end {
+				data.resize(end, 0_u8);
+			}
+			data[start..end].copy_from_slice(buf);
+			Ok(end as u64)
+		})?;
+		log::trace!("  -> offset={}, length={}, new offset={new_position}", fd.position, buf.len());
+		fd.position = new_position;
+		Ok(buf.len())
+	}
+
+	fn truncate(&mut self, fd: &mut InMemoryFd, length: u64) -> Result<(), Error> {
+		let InMemoryFd::File(fd) = fd else {
+			return Err(Error(EINVAL));
+		};
+		if fd.flags & O_ACCMODE == O_RDONLY {
+			return Err(Error(EINVAL));
+		}
+		if length > self.max_file_size {
+			return Err(Error(EFBIG));
+		}
+		let length = length.try_into().map_err(|_| Error(EFBIG))?;
+		fd.modify(self, |data| {
+			data.resize(length, 0_u8);
+			Ok(())
+		})
+	}
+
 	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		let path = resolve_links(self, c"/", path, true)?;
+		self.symlink_metadata(&path)
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		let path = normalize_path(path);
+		let inode = self.inode_with_links(&path).ok_or(Error(ENOENT))?;
+		Ok(match inode.1.node {
+			InMemoryNode::File(ref blob) => {
+				let size = blob.len() as u64;
+				self.metadata_of(&path, inode, 0o100644, size, size)
+			},
+			InMemoryNode::Symlink(ref target) => {
+				let size = target.as_bytes().len() as u64;
+				self.metadata_of(&path, inode, S_IFLNK | 0o777, size, 0)
+			},
+			InMemoryNode::Directory => self.metadata_of(&path, inode, 0o40755, 0, DIR_BLOCK_SIZE),
+		})
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		let InMemoryFd::Dir(dir) = fd else {
+			return Err(Error(ENOTDIR));
+		};
+		let entries = dir_entries(self, &dir.path);
+		let mut offset = 0;
+		for (i, (name, file_type, id)) in
+			entries.into_iter().enumerate().skip(dir.position as usize)
+		{
+			let next_position = i as u64 + 1;
+			match write_dir_entry(id, next_position, file_type, &name, &mut buf[offset..]) {
+				Ok(n) => {
+					offset += n;
+					dir.position = next_position;
+				},
+				Err(WriteDirEntryErr::BufferTooSmall) if offset != 0 => break,
+				Err(_) => return Err(Error(EINVAL)),
+			}
+		}
+		Ok(offset)
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		match self.inode(&normalize_path(path)) {
+			Some((_, InMemoryNode::Symlink(target))) => Ok(target.clone()),
+			Some(..) => Err(Error(EINVAL)),
+			None => Err(Error(ENOENT)),
+		}
+	}
+
+	fn symlink(&mut self, target: &CStr, path: &CStr) -> Result<(), Error> {
+		let path = normalize_path(path);
+		if self.inode(&path).is_some() {
+			return Err(Error(EEXIST));
+		}
+		self.check_parent(&path)?;
+		self.create(path, InMemoryNode::Symlink(target.into()));
+		Ok(())
+	}
+
+	fn link(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		let old_path = normalize_path(old_path);
+		let new_path = normalize_path(new_path);
+		let id = match self.inode(&old_path) {
+			Some((_, InMemoryNode::Directory)) => return Err(Error(EPERM)),
+			Some((id, _)) => id,
+			None => return Err(Error(ENOENT)),
+		};
+		if self.inode(&new_path).is_some() {
+			return Err(Error(EEXIST));
+		}
+		self.check_parent(&new_path)?;
+		self.add_entry(new_path, id);
+		Ok(())
+	}
+
+	fn unlink(&mut self, path: &CStr) -> Result<(), Error> {
+		let path = normalize_path(path);
+		match self.inode(&path) {
+			Some((_, InMemoryNode::Directory)) => return Err(Error(EISDIR)),
+			Some(..) => {},
+			None => return Err(Error(ENOENT)),
+		}
+		self.remove(&path);
+		self.attrs.remove(&path);
+		Ok(())
+	}
+
+	fn mkdir(&mut self, path: &CStr, mode: u32) -> Result<(), Error> {
 		let path = normalize_path(path);
-		Self::get(self, &path)
-			.map(|blob| Metadata {
-				id: blob.as_ptr() as u64,
-				mode: 0o100644,
-				size: blob.len() as u64,
-				block_size: blob.len() as u64,
-			})
-			.ok_or(Error(ENOENT))
+		if self.inode(&path).is_some() {
+			return Err(Error(EEXIST));
+		}
+		self.check_parent(&path)?;
+		self.create_with_mode(path, InMemoryNode::Directory, mode);
+		Ok(())
 	}
 
-	fn read_dir(&mut self, _fd: &mut Self::Fd, _buf: &mut [u8]) -> Result<usize, Error> {
-		Err(Error(ENOSYS))
+	fn rmdir(&mut self, path: &CStr) -> Result<(), Error> {
+		let path = normalize_path(path);
+		match self.inode(&path) {
+			Some((ROOT_ID, _)) => return Err(Error(EBUSY)),
+			Some((_, InMemoryNode::Directory)) => {},
+			Some(..) => return Err(Error(ENOTDIR)),
+			None => return Err(Error(ENOENT)),
+		}
+		if children(self, &path).next().is_some() {
+			return Err(Error(ENOTEMPTY));
+		}
+		self.remove(&path);
+		self.attrs.remove(&path);
+		Ok(())
+	}
+
+	fn rename(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		let old_path = normalize_path(old_path);
+		let new_path = normalize_path(new_path);
+		let (id, is_dir) = match self.inode(&old_path) {
+			Some((ROOT_ID, _)) => return Err(Error(EBUSY)),
+			Some((id, inode)) => (id, matches!(inode, InMemoryNode::Directory)),
+			None => return Err(Error(ENOENT)),
+		};
+		let old_prefix = dir_prefix(&old_path);
+		let new_prefix = dir_prefix(&new_path);
+		if is_dir && new_prefix.to_bytes().starts_with(old_prefix.to_bytes()) {
+			// Renaming the directory to itself is a no-op, moving it into itself is not allowed.
+			return if old_path == new_path { Ok(()) } else { Err(Error(EINVAL)) };
+		}
+		match self.inode(&new_path) {
+			// Both paths refer to the same file.
+			Some((new_id, _)) if new_id == id => return Ok(()),
+			Some((ROOT_ID, _)) => return Err(Error(EBUSY)),
+			Some((_, InMemoryNode::Directory)) if !is_dir => return Err(Error(EISDIR)),
+			Some((_, InMemoryNode::Directory)) if children(self, &new_path).next().is_some() =>
+				return Err(Error(ENOTEMPTY)),
+			Some((_, InMemoryNode::Directory)) => {},
+			Some(..) if is_dir => return Err(Error(ENOTDIR)),
+			Some(..) => {},
+			None => self.check_parent(&new_path)?,
+		}
+		self.entries.remove(&old_path);
+		if is_dir {
+			self.link_parent(&old_path, false);
+			self.link_parent(&new_path, true);
+		}
+		if let Some(old_id) = self.entries.insert(new_path.clone(), id) {
+			self.release(&new_path, old_id);
+		}
+		match self.attrs.remove(&old_path) {
+			Some(attrs) => self.attrs.insert(new_path, attrs),
+			None => self.attrs.remove(&new_path),
+		};
+		if is_dir {
+			let moved_entries = take_prefixed(&mut self.entries, &old_prefix);
+			let moved_attrs = take_prefixed(&mut self.attrs, &old_prefix);
+			for (path, id) in moved_entries {
+				self.entries.insert(rebase(&path, &old_prefix, &new_prefix), id);
+			}
+			for (path, attrs) in moved_attrs {
+				self.attrs.insert(rebase(&path, &old_prefix, &new_prefix), attrs);
+			}
+		}
+		Ok(())
+	}
+
+	fn set_owner(&mut self, path: &CStr, uid: u32, gid: u32) -> Result<(), Error> {
+		let attrs = self.attrs_mut(path)?;
+		attrs.uid = uid;
+		attrs.gid = gid;
+		Ok(())
+	}
+
+	fn set_times(&mut self, path: &CStr, atime: Duration, mtime: Duration) -> Result<(), Error> {
+		let attrs = self.attrs_mut(path)?;
+		attrs.atime = atime;
+		attrs.mtime = mtime;
+		Ok(())
+	}
+}
+
+pub enum InMemoryFd {
+	File(InMemoryFile),
+	Dir(InMemoryDir),
+}
+
+impl InMemoryFd {
+	fn as_file_mut(&mut self) -> Result<&mut InMemoryFile, Error> {
+		match self {
+			Self::File(file) => Ok(file),
+			Self::Dir(..) => Err(Error(EISDIR)),
+		}
 	}
 }
 
-pub struct InMemoryFd {
+pub struct InMemoryFile {
 	pub position: u64,
 	pub blob: Arc<FileBlob>,
+	/// Inode number.
+	id: u64,
+	flags: u64,
 }
 
-impl InMemoryFd {
+impl InMemoryFile {
 	fn size(&self) -> u64 {
 		self.blob.len() as u64
 	}
+
+	/// Pick up the changes made to the file via other file descriptors.
+	fn refresh(&mut self, fs: &InMemoryFileSystem) {
+		if let Some(InMemoryInode { node: InMemoryNode::File(blob), .. }) = fs.inodes.get(&self.id)
+		{
+			self.blob = blob.clone();
+		}
+	}
+
+	/// Modify file contents in place and share the result with other file descriptors.
+	fn modify<T>(
+		&mut self,
+		fs: &mut InMemoryFileSystem,
+		f: impl FnOnce(&mut Vec<u8>) -> Result<T, Error>,
+	) -> Result<T, Error> {
+		let Some(InMemoryInode { node: InMemoryNode::File(blob), .. }) =
+			fs.inodes.get_mut(&self.id)
+		else {
+			// The file was removed: keep modifying the private copy like Linux does.
+			return f(Arc::make_mut(&mut self.blob).to_mut());
+		};
+		// Release our reference to not make a redundant copy of the contents.
+		self.blob = Arc::default();
+		let result = f(Arc::make_mut(blob).to_mut());
+		self.blob = blob.clone();
+		result
+	}
+}
+
+pub struct InMemoryDir {
+	/// The index of the next directory entry.
+	pub position: u64,
+	path: CString,
+}
+
+fn is_dir(fs: &InMemoryFileSystem, path: &CStr) -> bool {
+	matches!(fs.inode(path), Some((_, InMemoryNode::Directory)))
+}
+
+/// Iterate over the names and the inode numbers of the directory entries except `.` and `..`.
+fn children<'a>(
+	fs: &'a InMemoryFileSystem,
+	path: &CStr,
+) -> impl Iterator<Item = (&'a [u8], u64)> + 'a {
+	let prefix = dir_prefix(path);
+	fs.entries
+		.range::<CStr, _>((Included(prefix.as_c_str()), Unbounded))
+		.map_while(move |(key, id)| {
+			let name = key.to_bytes().strip_prefix(prefix.to_bytes())?;
+			Some((name, *id))
+		})
+		.filter(|(name, _)| !name.contains(&b'/'))
+}
+
+/// List directory entries including `.` and `..`.
+fn dir_entries(fs: &InMemoryFileSystem, path: &CStr) -> Vec<(CString, FileType, u64)> {
+	let id_of = |path: &[u8]| fs.inode(&to_cstring(path)).map_or(ROOT_ID, |(id, _)| id);
+	let mut entries = vec![
+		(c".".into(), FileType::Directory, id_of(path.to_bytes())),
+		(c"..".into(), FileType::Directory, id_of(parent_dir(path.to_bytes()))),
+	];
+	for (name, id) in children(fs, path) {
+		let file_type = match fs.inodes.get(&id).map(|inode| &inode.node) {
+			Some(InMemoryNode::Symlink(..)) => FileType::Symlink,
+			Some(InMemoryNode::Directory) => FileType::Directory,
+			_ => FileType::Regular,
+		};
+		entries.push((to_cstring(name), file_type, id));
+	}
+	entries
+}
+
+/// Remove the entries with the keys that start with `prefix`.
+fn take_prefixed<T>(map: &mut BTreeMap<CString, T>, prefix: &CStr) -> Vec<(CString, T)> {
+	let keys: Vec<CString> = map
+		.range::<CStr, _>((Included(prefix), Unbounded))
+		.map(|(key, _)| key)
+		.take_while(|key| key.to_bytes().starts_with(prefix.to_bytes()))
+		.cloned()
+		.collect();
+	keys.into_iter()
+		.filter_map(|key| {
+			let value = map.remove(&key)?;
+			Some((key, value))
+		})
+		.collect()
+}
+
+/// Replace `old_prefix` of the path with `new_prefix`.
+fn rebase(path: &CStr, old_prefix: &CStr, new_prefix: &CStr) -> CString {
+	let rest = &path.to_bytes()[old_prefix.to_bytes().len()..];
+	let mut rebased = Vec::with_capacity(new_prefix.to_bytes().len() + rest.len());
+	rebased.extend_from_slice(new_prefix.to_bytes());
+	rebased.extend_from_slice(rest);
+	to_cstring(rebased)
+}
+
+const DIR_BLOCK_SIZE: u64 = 4096;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn writes_beyond_max_file_size_fail() {
+		let mut fs = InMemoryFileSystem::new().with_max_file_size(16);
+		let mut fd = fs.open(c"/file", O_RDWR | O_CREAT, 0o644).unwrap();
+		fs.seek(&mut fd, SeekFrom::Start(1 << 40)).unwrap();
+		assert_eq!(Err(Error(EFBIG)), fs.write(&mut fd, b"x"));
+		fs.seek(&mut fd, SeekFrom::Start(15)).unwrap();
+		assert_eq!(Ok(1), fs.write(&mut fd, b"x"));
+		assert_eq!(Err(Error(EFBIG)), fs.write(&mut fd, b"x"));
+		assert_eq!(16, fs.metadata(c"/file").unwrap().size);
+	}
+
+	#[test]
+	fn truncation_beyond_max_file_size_fails() {
+		let mut fs = InMemoryFileSystem::new().with_max_file_size(16);
+		let mut fd = fs.open(c"/file", O_RDWR | O_CREAT, 0o644).unwrap();
+		assert_eq!(Err(Error(EFBIG)), fs.truncate(&mut fd, 1 << 40));
+		assert_eq!(Ok(()), fs.truncate(&mut fd, 16));
+		assert_eq!(16, fs.metadata(c"/file").unwrap().size);
+	}
 }
@@ -1,21 +1,32 @@
 #[cfg(feature = "std")]
 pub mod std_io;
 
+pub mod dev;
 pub mod in_memory;
+pub mod mount;
+pub mod overlay;
+pub mod proc;
 
-use alloc::{ffi::CString, vec::Vec};
-use core::ffi::CStr;
+use alloc::{collections::VecDeque, ffi::CString, vec::Vec};
+use core::{ffi::CStr, time::Duration};
 
-use crate::Error;
+use crate::{libc::*, Error};
 
 /// File system of a user-space program.
+///
+/// [`Kernel`](crate::Kernel) resolves relative paths against the current working directory and
+/// follows symbolic links, i.e. the paths passed to the methods of this trait are always absolute,
+/// normalized and free of symbolic links except for the last component. The last component is a
+/// symbolic link only if the syscall operates on the link itself, or asked not to follow it.
 pub trait FileSystem {
 	type Fd: Sized;
 
 	/// Open file under the provided path.
 	///
+	/// `mode` specifies file permissions if the file is created (`O_CREAT` flag).
+	///
 	/// See [open(2)](https://man7.org/linux/man-pages/man2/open.2.html).
-	fn open(&mut self, path: &CStr, flags: u64) -> Result<Self::Fd, Error>;
+	fn open(&mut self, path: &CStr, flags: u64, mode: u32) -> Result<Self::Fd, Error>;
 
 	/// Set the current read/write offset of the opened file.
 	///
@@ -29,27 +40,218 @@ pub trait FileSystem {
 	/// See [read(2)](https://man7.org/linux/man-pages/man2/read.2.html).
 	fn read(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error>;
 
+	/// Read data from the opened file at the specified offset to the provided buffer.
+	///
+	/// Doesn't change the current read/write offset. Returns the number of bytes read.
+	///
+	/// See [pread(2)](https://man7.org/linux/man-pages/man2/pread.2.html).
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], offset: u64) -> Result<usize, Error>;
+
+	/// Write data from the provided buffer to the opened file.
+	///
+	/// Returns the number of bytes written.
+	///
+	/// See [write(2)](https://man7.org/linux/man-pages/man2/write.2.html).
+	fn write(&mut self, fd: &mut Self::Fd, buf: &[u8]) -> Result<usize, Error>;
+
+	/// Truncate or extend the opened file to the specified length.
+	///
+	/// See [ftruncate(2)](https://man7.org/linux/man-pages/man2/ftruncate.2.html).
+	fn truncate(&mut self, fd: &mut Self::Fd, length: u64) -> Result<(), Error>;
+
 	/// Read directory contents into the provided buffer.
 	///
-	/// The implementation is expected to call [`write_dir_entry`] with `buf` as the last argument
-	/// for each entry in the directory.
+	/// The implementation is expected to call [`write_dir_entry`] for as many entries as fit in
+	/// `buf` and to remember the position of the first entry that didn't fit, so that the next
+	/// call resumes from there. Entry offsets are the positions of the next entries and should be
+	/// accepted by [`seek`](Self::seek) with [`SeekFrom::Start`].
 	///
-	/// See [readdir(2)](https://man7.org/linux/man-pages/man2/readdir.2.html).
+	/// Returns the number of bytes written. Zero means the end of the directory. Fails with
+	/// `EINVAL` if the buffer is too small to hold the next entry.
+	///
+	/// See [getdents(2)](https://man7.org/linux/man-pages/man2/getdents.2.html).
 	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error>;
 
 	/// Read file metadata from the provided path.
+	///
+	/// Symbolic links are followed.
+	///
+	/// See [stat(2)](https://man7.org/linux/man-pages/man2/stat.2.html).
 	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error>;
+
+	/// Read file metadata from the provided path without following the symbolic link.
+	///
+	/// The default implementation is for file systems without symbolic links.
+	///
+	/// See [lstat(2)](https://man7.org/linux/man-pages/man2/lstat.2.html).
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		self.metadata(path)
+	}
+
+	/// Read the target of the symbolic link.
+	///
+	/// Fails with `EINVAL` if the file is not a symbolic link.
+	///
+	/// See [readlink(2)](https://man7.org/linux/man-pages/man2/readlink.2.html).
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error>;
+
+	/// Create symbolic link at `path` that points to `target`.
+	///
+	/// The target is stored as is, i.e. it can be relative and doesn't have to exist.
+	///
+	/// See [symlink(2)](https://man7.org/linux/man-pages/man2/symlink.2.html).
+	fn symlink(&mut self, target: &CStr, path: &CStr) -> Result<(), Error>;
+
+	/// Create hard link at `new_path` that refers to the same file as `old_path`.
+	///
+	/// See [link(2)](https://man7.org/linux/man-pages/man2/link.2.html).
+	fn link(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error>;
+
+	/// Remove the file or the symbolic link.
+	///
+	/// The file is deleted once the last hard link is removed.
+	///
+	/// See [unlink(2)](https://man7.org/linux/man-pages/man2/unlink.2.html).
+	fn unlink(&mut self, path: &CStr) -> Result<(), Error>;
+
+	/// Create new directory.
+	///
+	/// See [mkdir(2)](https://man7.org/linux/man-pages/man2/mkdir.2.html).
+	fn mkdir(&mut self, path: &CStr, mode: u32) -> Result<(), Error>;
+
+	/// Remove the empty directory.
+	///
+	/// See [rmdir(2)](https://man7.org/linux/man-pages/man2/rmdir.2.html).
+	fn rmdir(&mut self, path: &CStr) -> Result<(), Error>;
+
+	/// Move the file or the directory to `new_path`, replacing the existing file or the empty
+	/// directory.
+	///
+	/// See [rename(2)](https://man7.org/linux/man-pages/man2/rename.2.html).
+	fn rename(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error>;
+
+	/// Change the owner and the group of the file without following the symbolic link.
+	///
+	/// The default implementation is for file systems without configurable owners.
+	///
+	/// See [lchown(2)](https://man7.org/linux/man-pages/man2/lchown.2.html).
+	fn set_owner(&mut self, _path: &CStr, _uid: u32, _gid: u32) -> Result<(), Error> {
+		Err(Error(EPERM))
+	}
+
+	/// Change the last access and modification times of the file without following the symbolic
+	/// link.
+	///
+	/// The default implementation is for file systems without configurable timestamps.
+	///
+	/// See [utimensat(2)](https://man7.org/linux/man-pages/man2/utimensat.2.html).
+	fn set_times(&mut self, _path: &CStr, _atime: Duration, _mtime: Duration) -> Result<(), Error> {
+		Err(Error(EPERM))
+	}
+
+	/// Mount new file system of type `fs_type` at the `target` directory on behalf of the guest.
+	///
+	/// Fails with `EPERM` unless the file system supports mounting, see
+	/// [`MountTable`](crate::mount::MountTable).
+	///
+	/// See [mount(2)](https://man7.org/linux/man-pages/man2/mount.2.html).
+	fn guest_mount(
+		&mut self,
+		_source: &CStr,
+		_target: &CStr,
+		_fs_type: &CStr,
+		_flags: u64,
+	) -> Result<(), Error> {
+		Err(Error(EPERM))
+	}
+
+	/// Unmount the file system mounted at `target` on behalf of the guest.
+	///
+	/// Fails with `EPERM` unless the file system supports mounting.
+	///
+	/// See [umount(2)](https://man7.org/linux/man-pages/man2/umount.2.html).
+	fn guest_unmount(&mut self, _target: &CStr, _flags: u64) -> Result<(), Error> {
+		Err(Error(EPERM))
+	}
 }
 
 /// File system node metadata.
 ///
 /// See [stat(3type)](https://man7.org/linux/man-pages/man3/stat.3type.html).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Metadata {
+	/// Inode number.
 	pub id: u64,
+	/// File size in bytes.
 	pub size: u64,
+	/// File type and permissions (`S_IF*` and permission bits).
 	pub mode: u32,
+	/// Preferred I/O block size.
 	pub block_size: u64,
+	/// The number of hard links.
+	pub nlink: u32,
+	/// Owner user ID.
+	pub uid: u32,
+	/// Owner group ID.
+	pub gid: u32,
+	/// The ID of the device containing the file (see [`makedev`](crate::libc::makedev)).
+	pub device: u64,
+	/// The ID of the device the file represents if it is a character or block device.
+	pub rdev: u64,
+	/// Last access time since the Unix epoch.
+	pub atime: Duration,
+	/// Last modification time since the Unix epoch.
+	pub mtime: Duration,
+	/// Last status change time since the Unix epoch.
+	pub ctime: Duration,
+}
+
+/// File system node type.
+///
+/// The values are the ones used in `d_type` field of the directory entry.
+///
+/// See [getdents(2)](https://man7.org/linux/man-pages/man2/getdents.2.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum FileType {
+	Unknown = DT_UNKNOWN,
+	Fifo = DT_FIFO,
+	CharDevice = DT_CHR,
+	Directory = DT_DIR,
+	BlockDevice = DT_BLK,
+	Regular = DT_REG,
+	Symlink = DT_LNK,
+	Socket = DT_SOCK,
+}
+
+impl FileType {
+	/// Get file type from `st_mode` field of [`Metadata`].
+	pub const fn from_mode(mode: u32) -> Self {
+		match mode & S_IFMT {
+			S_IFIFO => Self::Fifo,
+			S_IFCHR => Self::CharDevice,
+			S_IFDIR => Self::Directory,
+			S_IFBLK => Self::BlockDevice,
+			S_IFREG => Self::Regular,
+			S_IFLNK => Self::Symlink,
+			S_IFSOCK => Self::Socket,
+			_ => Self::Unknown,
+		}
+	}
+
+	/// Get file type from `d_type` field of the directory entry.
+	pub const fn from_dir_entry_type(d_type: u8) -> Self {
+		match d_type {
+			DT_FIFO => Self::Fifo,
+			DT_CHR => Self::CharDevice,
+			DT_DIR => Self::Directory,
+			DT_BLK => Self::BlockDevice,
+			DT_REG => Self::Regular,
+			DT_LNK => Self::Symlink,
+			DT_SOCK => Self::Socket,
+			_ => Self::Unknown,
+		}
+	}
 }
 
 /// File read/write position anchor.
@@ -76,10 +278,18 @@ pub const fn dir_entry_len(name_len: usize) -> usize {
 
 /// Write directory entry for the specified file name to the provided buffer.
 ///
+/// `offset` is the position of the next entry in the directory.
+///
 /// Meant to be used in the implementation of [`FileSystem::read_dir`].
 ///
 /// See [getdents(2)](https://man7.org/linux/man-pages/man2/getdents.2.html).
-pub fn write_dir_entry(id: u64, name: &CStr, buf: &mut [u8]) -> Result<usize, WriteDirEntryErr> {
+pub fn write_dir_entry(
+	id: u64,
+	offset: u64,
+	file_type: FileType,
+	name: &CStr,
+	buf: &mut [u8],
+) -> Result<usize, WriteDirEntryErr> {
 	use WriteDirEntryErr::*;
 	let name_bytes = name.to_bytes_with_nul();
 	let name_len = name_bytes.len();
@@ -88,12 +298,10 @@ pub fn write_dir_entry(id: u64, name: &CStr, buf: &mut [u8]) -> Result<usize, Wr
 		return Err(BufferTooSmall);
 	}
 	let entry_len_u16: u16 = entry_len.try_into().map_err(|_| NameTooLong)?;
-	let off = 0_u64;
-	let d_type = 0_u8;
 	buf[0..8].copy_from_slice(id.to_le_bytes().as_slice());
-	buf[8..16].copy_from_slice(off.to_le_bytes().as_slice());
+	buf[8..16].copy_from_slice(offset.to_le_bytes().as_slice());
 	buf[16..18].copy_from_slice(entry_len_u16.to_le_bytes().as_slice());
-	buf[18] = d_type;
+	buf[18] = file_type as u8;
 	let n = 19 + name_len;
 	buf[19..n].copy_from_slice(name_bytes);
 	Ok(entry_len)
@@ -105,6 +313,170 @@ pub enum WriteDirEntryErr {
 	BufferTooSmall,
 }
 
+/// The maximum number of symbolic links followed during path resolution.
+pub const MAX_SYMLINKS: usize = 40;
+
+/// Resolve `path` relative to `base` directory following symbolic links.
+///
+/// `base` must be absolute, normalized and free of symbolic links. The last component of the path
+/// is followed only if `follow` is true or the path ends with a slash. Unlike [`normalize_path`],
+/// `..` refers to the parent of the directory the preceding components resolve to.
+pub(crate) fn resolve_links<F: FileSystem + ?Sized>(
+	fs: &mut F,
+	base: &CStr,
+	path: &CStr,
+	follow: bool,
+) -> Result<CString, Error> {
+	resolve_links_with(base, path, follow, |path| {
+		Ok(match fs.symlink_metadata(path)?.mode & S_IFMT {
+			S_IFLNK => PathNode::Symlink(fs.readlink(path)?),
+			S_IFDIR => PathNode::Dir,
+			_ => PathNode::Other,
+		})
+	})
+}
+
+/// The type of the path component as seen by [`resolve_links_with`].
+pub(crate) enum PathNode {
+	Dir,
+	Symlink(CString),
+	/// Magic link to the file descriptor, e.g. `/proc/self/fd/0`.
+	///
+	/// The link is followed to `target` unless it is the last component.
+	FdLink {
+		fd: u32,
+		target: CString,
+	},
+	Other,
+}
+
+/// The file the path resolves to, see [`resolve_target_with`].
+pub(crate) enum Target {
+	Path(CString),
+	/// The file descriptor the magic link at the end of the path refers to.
+	Fd(u32),
+}
+
+/// Same as [`resolve_links`] but uses `lookup` to inspect the path components.
+pub(crate) fn resolve_links_with(
+	base: &CStr,
+	path: &CStr,
+	follow: bool,
+	lookup: impl FnMut(&CStr) -> Result<PathNode, Error>,
+) -> Result<CString, Error> {
+	match resolve_target_with(base, path, follow, lookup)? {
+		Target::Path(path) => Ok(path),
+		// The magic links are resolved by the kernel only.
+		Target::Fd(..) => Err(Error(ENOENT)),
+	}
+}
+
+/// Same as [`resolve_links_with`] but resolves the magic link at the end of the path to the file
+/// descriptor.
+pub(crate) fn resolve_target_with(
+	base: &CStr,
+	path: &CStr,
+	follow: bool,
+	mut lookup: impl FnMut(&CStr) -> Result<PathNode, Error>,
+) -> Result<Target, Error> {
+	let path = path.to_bytes();
+	let mut resolved = Vec::new();
+	if path.first() != Some(&b'/') && base.to_bytes() != b"/" {
+		resolved.extend_from_slice(base.to_bytes());
+	}
+	let follow = follow || path.ends_with(b"/");
+	let mut pending = VecDeque::new();
+	push_components(&mut pending, path);
+	let mut num_links = 0;
+	while let Some(comp) = pending.pop_front() {
+		if comp == b".." {
+			let parent_len = resolved.iter().rposition(|byte| *byte == b'/').unwrap_or(0);
+			resolved.truncate(parent_len);
+			continue;
+		}
+		let parent_len = resolved.len();
+		resolved.push(b'/');
+		resolved.extend_from_slice(&comp);
+		let is_last = pending.is_empty();
+		if is_last && !follow {
+			break;
+		}
+		let candidate = to_cstring(resolved.clone());
+		let node = match lookup(&candidate) {
+			Ok(node) => node,
+			// The last component may be created.
+			Err(Error(ENOENT)) if is_last => break,
+			Err(e) => return Err(e),
+		};
+		match node {
+			PathNode::FdLink { fd, .. } if is_last => return Ok(Target::Fd(fd)),
+			PathNode::Symlink(target) | PathNode::FdLink { target, .. } => {
+				num_links += 1;
+				if num_links > MAX_SYMLINKS {
+					return Err(Error(ELOOP));
+				}
+				let target = target.to_bytes();
+				if target.is_empty() {
+					return Err(Error(ENOENT));
+				}
+				if target.first() == Some(&b'/') {
+					resolved.clear();
+				} else {
+					resolved.truncate(parent_len);
+				}
+				let mut target_components = VecDeque::new();
+				push_components(&mut target_components, target);
+				target_components.append(&mut pending);
+				pending = target_components;
+			},
+			PathNode::Dir => {},
+			PathNode::Other if !is_last => return Err(Error(ENOTDIR)),
+			PathNode::Other => {},
+		}
+	}
+	if resolved.is_empty() {
+		resolved.push(b'/');
+	}
+	Ok(Target::Path(to_cstring(resolved)))
+}
+
+/// Append the path components except `.` and empty ones.
+fn push_components(components: &mut VecDeque<Vec<u8>>, path: &[u8]) {
+	for comp in path.split(|byte| *byte == b'/') {
+		if !matches!(comp, b"" | b".") {
+			components.push_back(comp.to_vec());
+		}
+	}
+}
+
+pub(crate) fn to_cstring(path: impl Into<Vec<u8>>) -> CString {
+	// SAFETY: Paths can't contain NUL bytes.
+	unsafe { CString::from_vec_unchecked(path.into()) }
+}
+
+/// Get the parent directory of the path.
+///
+/// Returns an empty path for a relative path without slashes.
+pub(crate) fn parent_dir(path: &[u8]) -> &[u8] {
+	match path.iter().rposition(|byte| *byte == b'/') {
+		Some(0) => b"/",
+		Some(i) => &path[..i],
+		None => b"",
+	}
+}
+
+/// Get the prefix shared by the paths of the directory entries, i.e. the path with a trailing
+/// slash.
+pub(crate) fn dir_prefix(path: &CStr) -> CString {
+	let path = path.to_bytes();
+	let mut prefix = Vec::with_capacity(path.len() + 1);
+	prefix.extend_from_slice(path);
+	if !path.is_empty() && !path.ends_with(b"/") {
+		prefix.push(b'/');
+	}
+	to_cstring(prefix)
+}
+
 pub(crate) fn normalize_path(path: &CStr) -> CString {
 	let path = path.to_bytes();
 	let mut components = Vec::new();
@@ -131,6 +503,10 @@ pub(crate) fn normalize_path(path: &CStr) -> CString {
 		normal.push(b'/');
 		normal.extend_from_slice(comp);
 	}
+	if normal.is_empty() {
+		// Current directory.
+		normal.push(b'.');
+	}
 	normal.push(0_u8);
 	// SAFETY: We add NUL byte ourselves.
 	unsafe { CString::from_vec_with_nul_unchecked(normal) }
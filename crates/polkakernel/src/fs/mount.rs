@@ -0,0 +1,526 @@
+//! Mount table [`FileSystem`].
+
+use alloc::{boxed::Box, collections::BTreeMap, ffi::CString, rc::Rc};
+use core::{any::Any, cell::RefCell, ffi::CStr, time::Duration};
+
+use crate::{
+	in_memory::InMemoryFileSystem, libc::*, normalize_path, resolve_links, to_cstring, Error,
+	FileSystem, Metadata, SeekFrom,
+};
+
+/// Type-erased file system handle.
+pub type AnyFd = Box<dyn Any>;
+
+/// Type-erased file system shared by [`MountTable`] and the open files.
+pub type SharedFileSystem = Rc<RefCell<dyn FileSystem<Fd = AnyFd>>>;
+
+/// Constructs new file system from the mount source, see [`MountTable::add_fs_type`].
+pub type FsFactory = Box<dyn FnMut(&CStr) -> Result<SharedFileSystem, Error>>;
+
+/// An implementation of [`FileSystem`] that dispatches each path to the file system mounted at
+/// the longest matching prefix.
+///
+/// The paths are passed to the mounted file systems relative to their mount points. Hard links
+/// and renames across file systems fail with `EXDEV`. The mount point should exist as a directory
+/// in the parent file system to be visible in the directory listings.
+///
+/// The guest can mount the registered file system types via `mount` syscall, `tmpfs` is
+/// registered by default. The file systems mounted with `MS_RDONLY` flag reject modifications
+/// with `EROFS`.
+pub struct MountTable {
+	/// File systems by normalized mount point.
+	mounts: BTreeMap<CString, Mount>,
+	/// File system constructors by type name.
+	fs_types: BTreeMap<CString, FsFactory>,
+}
+
+impl MountTable {
+	/// Create new table with `root` file system mounted at `/`.
+	pub fn new<F: FileSystem + 'static>(root: F) -> Self {
+		let mut table = Self { mounts: BTreeMap::new(), fs_types: BTreeMap::new() };
+		table.mounts.insert(c"/".into(), Mount { fs: share(root), read_only: false });
+		table.add_fs_type(c"tmpfs", |_source| Ok(InMemoryFileSystem::new()));
+		table
+	}
+
+	/// Mount the file system at `path`.
+	///
+	/// Fails with `EBUSY` if another file system is already mounted there.
+	pub fn mount<F: FileSystem + 'static>(&mut self, path: &CStr, fs: F) -> Result<(), Error> {
+		self.mount_shared(path, share(fs), false)
+	}
+
+	/// Unmount the file system mounted at `path`.
+	///
+	/// Fails with `EBUSY` if the file system has open files or other file systems are mounted
+	/// under it, unless `detach` is true. Detached file system remains accessible via the open
+	/// files. The root file system can't be unmounted.
+	pub fn unmount(&mut self, path: &CStr, detach: bool) -> Result<(), Error> {
+		let path = normalize_path(path);
+		let Some(mount) = self.mounts.get(&path) else {
+			return Err(Error(EINVAL));
+		};
+		if path.to_bytes() == b"/" ||
+			(!detach && (Rc::strong_count(&mount.fs) > 1 || self.has_children(&path)))
+		{
+			return Err(Error(EBUSY));
+		}
+		self.mounts.remove(&path);
+		Ok(())
+	}
+
+	/// Get the file system mounted at `path`.
+	pub fn get(&self, path: &CStr) -> Option<&SharedFileSystem> {
+		self.mounts.get(&normalize_path(path)).map(|mount| &mount.fs)
+	}
+
+	/// Register the file system type that the guest can mount.
+	///
+	/// `factory` receives the source (the device) of the mount.
+	pub fn add_fs_type<F: FileSystem + 'static>(
+		&mut self,
+		fs_type: &CStr,
+		mut factory: impl FnMut(&CStr) -> Result<F, Error> + 'static,
+	) {
+		let factory = move |source: &CStr| Ok(share(factory(source)?));
+		self.fs_types.insert(fs_type.into(), Box::new(factory));
+	}
+
+	fn mount_shared(
+		&mut self,
+		path: &CStr,
+		fs: SharedFileSystem,
+		read_only: bool,
+	) -> Result<(), Error> {
+		let path = normalize_path(path);
+		if self.mounts.contains_key(&path) {
+			return Err(Error(EBUSY));
+		}
+		self.mounts.insert(path, Mount { fs, read_only });
+		Ok(())
+	}
+
+	/// Check if any file system is mounted under `path`.
+	fn has_children(&self, path: &CStr) -> bool {
+		let path = path.to_bytes();
+		self.mounts.keys().any(|mount_point| {
+			let mount_point = mount_point.to_bytes();
+			mount_point.len() > path.len() &&
+				mount_point.starts_with(path) &&
+				(path == b"/" || mount_point[path.len()] == b'/')
+		})
+	}
+
+	/// Find the file system the path belongs to.
+	///
+	/// Returns the mount point, the mounted file system and the path relative to the mount point.
+	fn lookup(&self, path: &CStr) -> Result<(CString, Mount, CString), Error> {
+		let path = normalize_path(path);
+		let bytes = path.to_bytes();
+		let prefixes = [bytes].into_iter().chain(
+			bytes.iter().enumerate().rev().filter(|(_, byte)| **byte == b'/').map(
+				|(i, _)| match i {
+					0 => &b"/"[..],
+					i => &bytes[..i],
+				},
+			),
+		);
+		for prefix in prefixes {
+			let mount_point = to_cstring(prefix);
+			let Some(mount) = self.mounts.get(&mount_point) else {
+				continue;
+			};
+			let inner = match &bytes[prefix.len()..] {
+				_ if prefix == b"/" => path.clone(),
+				b"" => c"/".into(),
+				rest => to_cstring(rest),
+			};
+			return Ok((mount_point, mount.clone(), inner));
+		}
+		Err(Error(ENOENT))
+	}
+
+	/// Call `f` with the file system the path belongs to and the path relative to the mount point.
+	fn with_fs<T>(
+		&self,
+		path: &CStr,
+		f: impl FnOnce(&mut dyn FileSystem<Fd = AnyFd>, &CStr) -> Result<T, Error>,
+	) -> Result<T, Error> {
+		let (_, mount, path) = self.lookup(path)?;
+		let mut fs = mount.fs.borrow_mut();
+		f(&mut *fs, &path)
+	}
+
+	/// Same as [`with_fs`](Self::with_fs) but fails with `EROFS` if the file system is mounted
+	/// read-only.
+	fn with_writable_fs<T>(
+		&self,
+		path: &CStr,
+		f: impl FnOnce(&mut dyn FileSystem<Fd = AnyFd>, &CStr) -> Result<T, Error>,
+	) -> Result<T, Error> {
+		let (_, mount, path) = self.lookup(path)?;
+		mount.check_writable()?;
+		let mut fs = mount.fs.borrow_mut();
+		f(&mut *fs, &path)
+	}
+
+	/// Fail with `EBUSY` if the path is a mount point.
+	fn check_not_mount_point(&self, path: &CStr) -> Result<(), Error> {
+		if self.mounts.contains_key(&normalize_path(path)) {
+			return Err(Error(EBUSY));
+		}
+		Ok(())
+	}
+
+	/// Find the writable file system both paths belong to.
+	///
+	/// Fails with `EXDEV` if they belong to different file systems.
+	fn lookup_pair(
+		&self,
+		old_path: &CStr,
+		new_path: &CStr,
+	) -> Result<(SharedFileSystem, CString, CString), Error> {
+		let (old_mount_point, mount, old_path) = self.lookup(old_path)?;
+		let (new_mount_point, _, new_path) = self.lookup(new_path)?;
+		if old_mount_point != new_mount_point {
+			return Err(Error(EXDEV));
+		}
+		mount.check_writable()?;
+		Ok((mount.fs, old_path, new_path))
+	}
+}
+
+impl FileSystem for MountTable {
+	type Fd = MountFd;
+
+	fn open(&mut self, path: &CStr, flags: u64, mode: u32) -> Result<Self::Fd, Error> {
+		let (_, mount, path) = self.lookup(path)?;
+		if flags & (O_CREAT | O_TRUNC) != 0 || flags & O_ACCMODE != O_RDONLY {
+			mount.check_writable()?;
+		}
+		let fd = mount.fs.borrow_mut().open(&path, flags, mode)?;
+		Ok(MountFd { fd, fs: mount.fs })
+	}
+
+	fn seek(&mut self, fd: &mut Self::Fd, from: SeekFrom) -> Result<u64, Error> {
+		fd.fs.borrow_mut().seek(&mut fd.fd, from)
+	}
+
+	fn read(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		fd.fs.borrow_mut().read(&mut fd.fd, buf)
+	}
+
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+		fd.fs.borrow_mut().read_at(&mut fd.fd, buf, offset)
+	}
+
+	fn write(&mut self, fd: &mut Self::Fd, buf: &[u8]) -> Result<usize, Error> {
+		fd.fs.borrow_mut().write(&mut fd.fd, buf)
+	}
+
+	fn truncate(&mut self, fd: &mut Self::Fd, length: u64) -> Result<(), Error> {
+		fd.fs.borrow_mut().truncate(&mut fd.fd, length)
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		fd.fs.borrow_mut().read_dir(&mut fd.fd, buf)
+	}
+
+	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		// Symbolic links may point to the other file systems.
+		let path = resolve_links(self, c"/", path, true)?;
+		self.symlink_metadata(&path)
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		self.with_fs(path, |fs, path| fs.symlink_metadata(path))
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		self.with_fs(path, |fs, path| fs.readlink(path))
+	}
+
+	fn symlink(&mut self, target: &CStr, path: &CStr) -> Result<(), Error> {
+		self.with_writable_fs(path, |fs, path| fs.symlink(target, path))
+	}
+
+	fn link(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		let (fs, old_path, new_path) = self.lookup_pair(old_path, new_path)?;
+		let mut fs = fs.borrow_mut();
+		fs.link(&old_path, &new_path)
+	}
+
+	fn unlink(&mut self, path: &CStr) -> Result<(), Error> {
+		self.check_not_mount_point(path)?;
+		self.with_writable_fs(path, |fs, path| fs.unlink(path))
+	}
+
+	fn mkdir(&mut self, path: &CStr, mode: u32) -> Result<(), Error> {
+		self.with_writable_fs(path, |fs, path| fs.mkdir(path, mode))
+	}
+
+	fn rmdir(&mut self, path: &CStr) -> Result<(), Error> {
+		self.check_not_mount_point(path)?;
+		self.with_writable_fs(path, |fs, path| fs.rmdir(path))
+	}
+
+	fn rename(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		self.check_not_mount_point(old_path)?;
+		self.check_not_mount_point(new_path)?;
+		let (fs, old_path, new_path) = self.lookup_pair(old_path, new_path)?;
+		let mut fs = fs.borrow_mut();
+		fs.rename(&old_path, &new_path)
+	}
+
+	fn set_owner(&mut self, path: &CStr, uid: u32, gid: u32) -> Result<(), Error> {
+		self.with_writable_fs(path, |fs, path| fs.set_owner(path, uid, gid))
+	}
+
+	fn set_times(&mut self, path: &CStr, atime: Duration, mtime: Duration) -> Result<(), Error> {
+		self.with_writable_fs(path, |fs, path| fs.set_times(path, atime, mtime))
+	}
+
+	fn guest_mount(
+		&mut self,
+		source: &CStr,
+		target: &CStr,
+		fs_type: &CStr,
+		flags: u64,
+	) -> Result<(), Error> {
+		if flags & (MS_REMOUNT | MS_BIND | MS_MOVE) != 0 {
+			return Err(Error(EINVAL));
+		}
+		if self.metadata(target)?.mode & S_IFMT != S_IFDIR {
+			return Err(Error(ENOTDIR));
+		}
+		let factory = self.fs_types.get_mut(fs_type).ok_or(Error(ENODEV))?;
+		let fs = factory(source)?;
+		self.mount_shared(target, fs, flags & MS_RDONLY != 0)
+	}
+
+	fn guest_unmount(&mut self, target: &CStr, flags: u64) -> Result<(), Error> {
+		self.unmount(target, flags & MNT_DETACH != 0)
+	}
+}
+
+/// The file system mounted in [`MountTable`].
+#[derive(Clone)]
+struct Mount {
+	fs: SharedFileSystem,
+	/// Reject modifications with `EROFS`.
+	read_only: bool,
+}
+
+impl Mount {
+	fn check_writable(&self) -> Result<(), Error> {
+		if self.read_only {
+			return Err(Error(EROFS));
+		}
+		Ok(())
+	}
+}
+
+/// [`MountTable`] file handle.
+pub struct MountFd {
+	/// The handle of the mounted file system.
+	fd: AnyFd,
+	/// The file system the file belongs to.
+	///
+	/// Keeps the file system alive after it is detached.
+	fs: SharedFileSystem,
+}
+
+/// Erase the type of the file system and its handles.
+fn share<F: FileSystem + 'static>(fs: F) -> SharedFileSystem {
+	Rc::new(RefCell::new(Erased(fs)))
+}
+
+/// A wrapper that erases the type of the file system handles.
+struct Erased<F>(F);
+
+impl<F: FileSystem> Erased<F>
+where
+	F::Fd: 'static,
+{
+	fn downcast(fd: &mut AnyFd) -> Result<&mut F::Fd, Error> {
+		fd.downcast_mut().ok_or(Error(EBADF))
+	}
+}
+
+impl<F: FileSystem> FileSystem for Erased<F>
+where
+	F::Fd: 'static,
+{
+	type Fd = AnyFd;
+
+	fn open(&mut self, path: &CStr, flags: u64, mode: u32) -> Result<Self::Fd, Error> {
+		Ok(Box::new(self.0.open(path, flags, mode)?))
+	}
+
+	fn seek(&mut self, fd: &mut Self::Fd, from: SeekFrom) -> Result<u64, Error> {
+		self.0.seek(Self::downcast(fd)?, from)
+	}
+
+	fn read(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		self.0.read(Self::downcast(fd)?, buf)
+	}
+
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+		self.0.read_at(Self::downcast(fd)?, buf, offset)
+	}
+
+	fn write(&mut self, fd: &mut Self::Fd, buf: &[u8]) -> Result<usize, Error> {
+		self.0.write(Self::downcast(fd)?, buf)
+	}
+
+	fn truncate(&mut self, fd: &mut Self::Fd, length: u64) -> Result<(), Error> {
+		self.0.truncate(Self::downcast(fd)?, length)
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		self.0.read_dir(Self::downcast(fd)?, buf)
+	}
+
+	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		self.0.metadata(path)
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		self.0.symlink_metadata(path)
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		self.0.readlink(path)
+	}
+
+	fn symlink(&mut self, target: &CStr, path: &CStr) -> Result<(), Error> {
+		self.0.symlink(target, path)
+	}
+
+	fn link(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		self.0.link(old_path, new_path)
+	}
+
+	fn unlink(&mut self, path: &CStr) -> Result<(), Error> {
+		self.0.unlink(path)
+	}
+
+	fn mkdir(&mut self, path: &CStr, mode: u32) -> Result<(), Error> {
+		self.0.mkdir(path, mode)
+	}
+
+	fn rmdir(&mut self, path: &CStr) -> Result<(), Error> {
+		self.0.rmdir(path)
+	}
+
+	fn rename(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		self.0.rename(old_path, new_path)
+	}
+
+	fn set_owner(&mut self, path: &CStr, uid: u32, gid: u32) -> Result<(), Error> {
+		self.0.set_owner(path, uid, gid)
+	}
+
+	fn set_times(&mut self, path: &CStr, atime: Duration, mtime: Duration) -> Result<(), Error> {
+		self.0.set_times(path, atime, mtime)
+	}
+
+	fn guest_mount(
+		&mut self,
+		source: &CStr,
+		target: &CStr,
+		fs_type: &CStr,
+		flags: u64,
+	) -> Result<(), Error> {
+		self.0.guest_mount(source, target, fs_type, flags)
+	}
+
+	fn guest_unmount(&mut self, target: &CStr, flags: u64) -> Result<(), Error> {
+		self.0.guest_unmount(target, flags)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::{vec, vec::Vec};
+
+	fn table() -> MountTable {
+		let mut root = InMemoryFileSystem::new();
+		root.insert(c"/data/file", &b"root"[..]);
+		root.insert(c"/datafile", &b"sibling"[..]);
+		root.insert_dir(c"/mnt");
+		let mut data = InMemoryFileSystem::new();
+		data.insert(c"/file", &b"data"[..]);
+		data.insert_dir(c"/sub");
+		let mut sub = InMemoryFileSystem::new();
+		sub.insert(c"/file", &b"sub"[..]);
+		let mut table = MountTable::new(root);
+		table.mount(c"/data", data).unwrap();
+		table.mount(c"/data/sub/", sub).unwrap();
+		table
+	}
+
+	fn read_file(fs: &mut MountTable, path: &CStr) -> Result<Vec<u8>, Error> {
+		let mut fd = fs.open(path, O_RDONLY, 0)?;
+		let mut buf = vec![0_u8; 64];
+		let n = fs.read(&mut fd, &mut buf)?;
+		buf.truncate(n);
+		Ok(buf)
+	}
+
+	#[test]
+	fn longest_prefix_wins() {
+		let mut fs = table();
+		assert_eq!(Ok(b"data".to_vec()), read_file(&mut fs, c"/data/file"));
+		assert_eq!(Ok(b"sub".to_vec()), read_file(&mut fs, c"/data/sub/file"));
+		assert_eq!(Ok(b"sub".to_vec()), read_file(&mut fs, c"/data/../data/sub/./file"));
+		assert_eq!(Ok(b"sibling".to_vec()), read_file(&mut fs, c"/datafile"));
+		assert_eq!(Err(Error(EBUSY)), fs.mount(c"/data", InMemoryFileSystem::new()));
+	}
+
+	#[test]
+	fn cross_mount_changes_fail() {
+		let mut fs = table();
+		assert_eq!(Err(Error(EXDEV)), fs.rename(c"/data/file", c"/file"));
+		assert_eq!(Err(Error(EXDEV)), fs.link(c"/data/file", c"/data/sub/file2"));
+		assert_eq!(Err(Error(EBUSY)), fs.rmdir(c"/data/sub"));
+		assert_eq!(Err(Error(EBUSY)), fs.rename(c"/data", c"/data2"));
+		assert_eq!(Ok(()), fs.rename(c"/data/file", c"/data/file2"));
+		assert_eq!(Ok(b"data".to_vec()), read_file(&mut fs, c"/data/file2"));
+	}
+
+	#[test]
+	fn read_only_guest_mount() {
+		let mut fs = table();
+		assert_eq!(Err(Error(ENODEV)), fs.guest_mount(c"none", c"/mnt", c"ext4", 0));
+		assert_eq!(Err(Error(ENOTDIR)), fs.guest_mount(c"none", c"/datafile", c"tmpfs", 0));
+		assert_eq!(Err(Error(EINVAL)), fs.guest_mount(c"none", c"/mnt", c"tmpfs", MS_BIND));
+		assert_eq!(Ok(()), fs.guest_mount(c"none", c"/mnt", c"tmpfs", MS_RDONLY));
+		assert_eq!(Err(Error(EBUSY)), fs.guest_mount(c"none", c"/mnt", c"tmpfs", 0));
+		assert_eq!(Err(Error(EROFS)), fs.mkdir(c"/mnt/dir", 0o755));
+		assert_eq!(Err(Error(EROFS)), fs.open(c"/mnt/file", O_WRONLY | O_CREAT, 0o644).map(|_| ()));
+		assert_eq!(Err(Error(ENOENT)), fs.open(c"/mnt/file", O_RDONLY, 0).map(|_| ()));
+		assert_eq!(S_IFDIR, fs.metadata(c"/mnt").unwrap().mode & S_IFMT);
+		assert_eq!(Ok(()), fs.guest_unmount(c"/mnt", 0));
+		assert_eq!(Ok(()), fs.mkdir(c"/mnt/dir", 0o755));
+	}
+
+	#[test]
+	fn busy_file_systems_are_not_unmounted() {
+		let mut fs = table();
+		assert_eq!(Err(Error(EBUSY)), fs.unmount(c"/", false));
+		assert_eq!(Err(Error(EINVAL)), fs.unmount(c"/mnt", false));
+		assert_eq!(Err(Error(EBUSY)), fs.unmount(c"/data", false));
+		let mut fd = fs.open(c"/data/sub/file", O_RDONLY, 0).unwrap();
+		assert_eq!(Err(Error(EBUSY)), fs.unmount(c"/data/sub", false));
+		assert_eq!(Ok(()), fs.unmount(c"/data/sub", true));
+		assert!(fs.get(c"/data/sub").is_none());
+		let mut buf = [0_u8; 3];
+		assert_eq!(Ok(3), fs.read(&mut fd, &mut buf));
+		assert_eq!(b"sub", &buf);
+		assert_eq!(Err(Error(ENOENT)), fs.metadata(c"/data/sub/file"));
+		assert_eq!(Ok(()), fs.unmount(c"/data", false));
+		assert_eq!(Ok(b"root".to_vec()), read_file(&mut fs, c"/data/file"));
+	}
+}
@@ -0,0 +1,622 @@
+//! Overlay [`FileSystem`].
+
+use alloc::{collections::BTreeSet, ffi::CString, vec, vec::Vec};
+use core::{ffi::CStr, time::Duration};
+
+use crate::{
+	dir_prefix, libc::*, normalize_path, parent_dir, resolve_links, to_cstring, write_dir_entry,
+	Error, FileSystem, FileType, Metadata, SeekFrom, WriteDirEntryErr,
+};
+
+/// An implementation of [`FileSystem`] that combines read-only lower layer and writable upper
+/// layer.
+///
+/// The files are looked up in the upper layer first. Lower layer files are copied to the upper
+/// layer before they are modified, and deleted files are hidden by the whiteouts, hence the lower
+/// layer is never modified. Directories are merged.
+///
+/// Like on Linux, renaming the directory that exists in the lower layer fails with `EXDEV`.
+///
+/// See [overlayfs](https://docs.kernel.org/filesystems/overlayfs.html).
+#[derive(Debug, Clone, Default)]
+pub struct OverlayFileSystem<Lower, Upper> {
+	/// Read-only layer.
+	pub lower: Lower,
+	/// Writable layer.
+	pub upper: Upper,
+	/// Paths that hide the lower layer files including the files under them.
+	///
+	/// These are the whiteouts of the deleted files and the opaque directories that replaced the
+	/// deleted ones.
+	pub hidden: BTreeSet<CString>,
+}
+
+impl<Lower: FileSystem, Upper: FileSystem> OverlayFileSystem<Lower, Upper> {
+	pub fn new(lower: Lower, upper: Upper) -> Self {
+		Self { lower, upper, hidden: BTreeSet::new() }
+	}
+
+	/// Check if the lower layer file is hidden by the path itself or by one of its parents.
+	fn is_hidden(&self, path: &CStr) -> bool {
+		let path = path.to_bytes();
+		path.iter()
+			.enumerate()
+			.filter(|(i, byte)| *i != 0 && **byte == b'/')
+			.map(|(i, _)| &path[..i])
+			.chain([path])
+			.any(|prefix| self.hidden.contains(to_cstring(prefix).as_c_str()))
+	}
+
+	/// Get the metadata of the lower layer file unless it is hidden.
+	fn lower_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		if self.is_hidden(path) {
+			return Err(Error(ENOENT));
+		}
+		self.lower.symlink_metadata(path)
+	}
+
+	/// Find the layer the file belongs to.
+	///
+	/// The inode number is remapped to not collide with the ones of the other layer.
+	fn locate(&mut self, path: &CStr) -> Result<(Layer, Metadata), Error> {
+		let (layer, mut meta) = match self.upper.symlink_metadata(path) {
+			Ok(meta) => (Layer::Upper, meta),
+			Err(Error(ENOENT)) => (Layer::Lower, self.lower_metadata(path)?),
+			Err(e) => return Err(e),
+		};
+		meta.id = layer.inode_id(meta.id);
+		Ok((layer, meta))
+	}
+
+	/// Make sure the directory exists in the upper layer.
+	fn copy_up_dir(&mut self, path: &CStr) -> Result<(), Error> {
+		let bytes = path.to_bytes();
+		let prefixes = bytes
+			.iter()
+			.enumerate()
+			.filter(|(i, byte)| *i != 0 && **byte == b'/')
+			.map(|(i, _)| &bytes[..i])
+			.chain([bytes])
+			.filter(|prefix| *prefix != b"/");
+		for prefix in prefixes {
+			let prefix = to_cstring(prefix);
+			let (layer, meta) = self.locate(&prefix)?;
+			if meta.mode & S_IFMT != S_IFDIR {
+				return Err(Error(ENOTDIR));
+			}
+			if layer == Layer::Lower {
+				self.upper.mkdir(&prefix, meta.mode & 0o7777)?;
+				self.copy_up_attrs(&prefix, &meta);
+			}
+		}
+		Ok(())
+	}
+
+	/// Make sure the parent directory of the path exists in the upper layer.
+	fn copy_up_parent(&mut self, path: &CStr) -> Result<(), Error> {
+		self.copy_up_dir(&to_cstring(parent_dir(path.to_bytes())))
+	}
+
+	/// Copy the file or the symbolic link from the lower layer unless it exists in the upper one.
+	fn copy_up(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		let (layer, meta) = self.locate(path)?;
+		if layer == Layer::Upper {
+			return Ok(meta);
+		}
+		self.copy_up_parent(path)?;
+		match meta.mode & S_IFMT {
+			S_IFDIR => self.upper.mkdir(path, meta.mode & 0o7777)?,
+			S_IFLNK => {
+				let target = self.lower.readlink(path)?;
+				self.upper.symlink(&target, path)?;
+			},
+			S_IFREG => {
+				let mut src = self.lower.open(path, O_RDONLY, 0)?;
+				let mut dst =
+					self.upper.open(path, O_WRONLY | O_CREAT | O_EXCL, meta.mode & 0o7777)?;
+				let mut buf = vec![0_u8; COPY_BUF_SIZE];
+				loop {
+					let n = self.lower.read(&mut src, &mut buf)?;
+					if n == 0 {
+						break;
+					}
+					let mut written = 0;
+					while written != n {
+						written += self.upper.write(&mut dst, &buf[written..n])?;
+					}
+				}
+			},
+			// Device files, sockets and pipes can't be copied.
+			_ => return Err(Error(EPERM)),
+		}
+		self.copy_up_attrs(path, &meta);
+		self.upper.symlink_metadata(path)
+	}
+
+	/// Copy the owner and the timestamps of the lower layer file to its upper layer copy.
+	///
+	/// Failures are ignored, e.g. an unprivileged host file system can't change the owner.
+	fn copy_up_attrs(&mut self, path: &CStr, meta: &Metadata) {
+		if let Err(e) = self.upper.set_owner(path, meta.uid, meta.gid) {
+			log::debug!("Failed to copy the owner of {path:?}: {e}");
+		}
+		if let Err(e) = self.upper.set_times(path, meta.atime, meta.mtime) {
+			log::debug!("Failed to copy the timestamps of {path:?}: {e}");
+		}
+	}
+
+	/// Hide the lower layer file if it exists.
+	fn whiteout(&mut self, path: &CStr) {
+		if self.lower_metadata(path).is_ok() {
+			self.hidden.insert(path.into());
+		}
+	}
+
+	/// List merged directory entries.
+	fn dir_entries(&mut self, path: &CStr) -> Result<Vec<(CString, FileType, u64)>, Error> {
+		let mut entries = Vec::new();
+		let mut names = BTreeSet::new();
+		if let Ok(mut fd) = self.upper.open(path, O_RDONLY | O_DIRECTORY, 0) {
+			read_all_entries(&mut self.upper, &mut fd, |name, file_type, id| {
+				names.insert(CString::from(name));
+				entries.push((name.into(), file_type, Layer::Upper.inode_id(id)));
+			})?;
+		}
+		if self.lower_metadata(path).is_ok_and(|meta| meta.mode & S_IFMT == S_IFDIR) {
+			let mut lower_entries = Vec::new();
+			let mut fd = self.lower.open(path, O_RDONLY | O_DIRECTORY, 0)?;
+			read_all_entries(&mut self.lower, &mut fd, |name, file_type, id| {
+				if !names.contains(name) {
+					lower_entries.push((CString::from(name), file_type, Layer::Lower.inode_id(id)));
+				}
+			})?;
+			let prefix = dir_prefix(path);
+			for (name, file_type, id) in lower_entries {
+				let mut child = prefix.to_bytes().to_vec();
+				child.extend_from_slice(name.to_bytes());
+				if matches!(name.to_bytes(), b"." | b"..") || !self.is_hidden(&to_cstring(child)) {
+					entries.push((name, file_type, id));
+				}
+			}
+		}
+		Ok(entries)
+	}
+}
+
+impl<Lower: FileSystem, Upper: FileSystem> FileSystem for OverlayFileSystem<Lower, Upper> {
+	type Fd = OverlayFd<Lower::Fd, Upper::Fd>;
+
+	fn open(&mut self, path: &CStr, flags: u64, mode: u32) -> Result<Self::Fd, Error> {
+		let path = normalize_path(path);
+		let meta = match self.locate(&path) {
+			Ok((..)) if flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL => {
+				return Err(Error(EEXIST));
+			},
+			Ok((_, meta)) if meta.mode & S_IFMT == S_IFDIR => {
+				if flags & O_ACCMODE != O_RDONLY {
+					return Err(Error(EISDIR));
+				}
+				let entries = self.dir_entries(&path)?;
+				return Ok(OverlayFd::Dir(OverlayDir { position: 0, entries }));
+			},
+			Ok((_, meta)) => Some(meta),
+			Err(Error(ENOENT)) if flags & O_CREAT != 0 => None,
+			Err(e) => return Err(e),
+		};
+		if flags & O_DIRECTORY != 0 {
+			return Err(Error(ENOTDIR));
+		}
+		let writable = flags & O_ACCMODE != O_RDONLY || flags & O_TRUNC != 0;
+		match meta {
+			Some(..) if !writable => match self.locate(&path)?.0 {
+				Layer::Upper => Ok(OverlayFd::Upper(self.upper.open(&path, flags, mode)?)),
+				// The file exists and isn't modified, hence the lower layer isn't asked to create
+				// or truncate it.
+				Layer::Lower => {
+					let flags = flags & !(O_CREAT | O_EXCL | O_TRUNC);
+					Ok(OverlayFd::Lower(self.lower.open(&path, flags, 0)?))
+				},
+			},
+			Some(..) => {
+				self.copy_up(&path)?;
+				Ok(OverlayFd::Upper(self.upper.open(&path, flags, mode)?))
+			},
+			None => {
+				self.copy_up_parent(&path)?;
+				Ok(OverlayFd::Upper(self.upper.open(&path, flags, mode)?))
+			},
+		}
+	}
+
+	fn seek(&mut self, fd: &mut Self::Fd, from: SeekFrom) -> Result<u64, Error> {
+		match fd {
+			OverlayFd::Lower(fd) => self.lower.seek(fd, from),
+			OverlayFd::Upper(fd) => self.upper.seek(fd, from),
+			OverlayFd::Dir(dir) => {
+				dir.position = match from {
+					SeekFrom::Start(offset) => offset,
+					SeekFrom::Current(offset) =>
+						dir.position.checked_add_signed(offset).ok_or(Error(EINVAL))?,
+					SeekFrom::End(..) => return Err(Error(EINVAL)),
+				};
+				Ok(dir.position)
+			},
+		}
+	}
+
+	fn read(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		match fd {
+			OverlayFd::Lower(fd) => self.lower.read(fd, buf),
+			OverlayFd::Upper(fd) => self.upper.read(fd, buf),
+			OverlayFd::Dir(..) => Err(Error(EISDIR)),
+		}
+	}
+
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+		match fd {
+			OverlayFd::Lower(fd) => self.lower.read_at(fd, buf, offset),
+			OverlayFd::Upper(fd) => self.upper.read_at(fd, buf, offset),
+			OverlayFd::Dir(..) => Err(Error(EISDIR)),
+		}
+	}
+
+	fn write(&mut self, fd: &mut Self::Fd, buf: &[u8]) -> Result<usize, Error> {
+		match fd {
+			// Lower layer files are opened read-only.
+			OverlayFd::Lower(..) | OverlayFd::Dir(..) => Err(Error(EBADF)),
+			OverlayFd::Upper(fd) => self.upper.write(fd, buf),
+		}
+	}
+
+	fn truncate(&mut self, fd: &mut Self::Fd, length: u64) -> Result<(), Error> {
+		match fd {
+			OverlayFd::Lower(..) | OverlayFd::Dir(..) => Err(Error(EINVAL)),
+			OverlayFd::Upper(fd) => self.upper.truncate(fd, length),
+		}
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		let OverlayFd::Dir(dir) = fd else {
+			return Err(Error(ENOTDIR));
+		};
+		let mut offset = 0;
+		for (i, (name, file_type, id)) in dir.entries.iter().enumerate().skip(dir.position as usize)
+		{
+			let next_position = i as u64 + 1;
+			match write_dir_entry(*id, next_position, *file_type, name, &mut buf[offset..]) {
+				Ok(n) => {
+					offset += n;
+					dir.position = next_position;
+				},
+				Err(WriteDirEntryErr::BufferTooSmall) if offset != 0 => break,
+				Err(_) => return Err(Error(EINVAL)),
+			}
+		}
+		Ok(offset)
+	}
+
+	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		// Symbolic links may point to the other layer.
+		let path = resolve_links(self, c"/", path, true)?;
+		self.symlink_metadata(&path)
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		Ok(self.locate(&normalize_path(path))?.1)
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		let path = normalize_path(path);
+		match self.locate(&path)?.0 {
+			Layer::Upper => self.upper.readlink(&path),
+			Layer::Lower => self.lower.readlink(&path),
+		}
+	}
+
+	fn symlink(&mut self, target: &CStr, path: &CStr) -> Result<(), Error> {
+		let path = normalize_path(path);
+		if self.locate(&path).is_ok() {
+			return Err(Error(EEXIST));
+		}
+		self.copy_up_parent(&path)?;
+		self.upper.symlink(target, &path)
+	}
+
+	fn link(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		let old_path = normalize_path(old_path);
+		let new_path = normalize_path(new_path);
+		if self.locate(&old_path)?.1.mode & S_IFMT == S_IFDIR {
+			return Err(Error(EPERM));
+		}
+		if self.locate(&new_path).is_ok() {
+			return Err(Error(EEXIST));
+		}
+		self.copy_up(&old_path)?;
+		self.copy_up_parent(&new_path)?;
+		self.upper.link(&old_path, &new_path)
+	}
+
+	fn unlink(&mut self, path: &CStr) -> Result<(), Error> {
+		let path = normalize_path(path);
+		let (layer, meta) = self.locate(&path)?;
+		if meta.mode & S_IFMT == S_IFDIR {
+			return Err(Error(EISDIR));
+		}
+		if layer == Layer::Upper {
+			self.upper.unlink(&path)?;
+		}
+		self.whiteout(&path);
+		Ok(())
+	}
+
+	fn mkdir(&mut self, path: &CStr, mode: u32) -> Result<(), Error> {
+		let path = normalize_path(path);
+		if self.locate(&path).is_ok() {
+			return Err(Error(EEXIST));
+		}
+		self.copy_up_parent(&path)?;
+		self.upper.mkdir(&path, mode)
+	}
+
+	fn rmdir(&mut self, path: &CStr) -> Result<(), Error> {
+		let path = normalize_path(path);
+		if path.to_bytes() == b"/" {
+			return Err(Error(EBUSY));
+		}
+		let (layer, meta) = self.locate(&path)?;
+		if meta.mode & S_IFMT != S_IFDIR {
+			return Err(Error(ENOTDIR));
+		}
+		let entries = self.dir_entries(&path)?;
+		if entries.iter().any(|(name, ..)| !matches!(name.to_bytes(), b"." | b"..")) {
+			return Err(Error(ENOTEMPTY));
+		}
+		if layer == Layer::Upper {
+			self.upper.rmdir(&path)?;
+		}
+		self.whiteout(&path);
+		Ok(())
+	}
+
+	fn rename(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		let old_path = normalize_path(old_path);
+		let new_path = normalize_path(new_path);
+		if old_path == new_path {
+			return Ok(());
+		}
+		let (_, old_meta) = self.locate(&old_path)?;
+		let old_is_dir = old_meta.mode & S_IFMT == S_IFDIR;
+		if old_is_dir && self.lower_metadata(&old_path).is_ok() {
+			return Err(Error(EXDEV));
+		}
+		let lower_replaced = match self.locate(&new_path) {
+			Ok((layer, new_meta)) => {
+				let new_is_dir = new_meta.mode & S_IFMT == S_IFDIR;
+				match (old_is_dir, new_is_dir) {
+					(true, false) => return Err(Error(ENOTDIR)),
+					(false, true) => return Err(Error(EISDIR)),
+					(true, true) => {
+						let entries = self.dir_entries(&new_path)?;
+						if entries.iter().any(|(name, ..)| !matches!(name.to_bytes(), b"." | b".."))
+						{
+							return Err(Error(ENOTEMPTY));
+						}
+					},
+					(false, false) => {},
+				}
+				layer == Layer::Lower
+			},
+			Err(Error(ENOENT)) => false,
+			Err(e) => return Err(e),
+		};
+		self.copy_up(&old_path)?;
+		self.copy_up_parent(&new_path)?;
+		if lower_replaced && old_is_dir {
+			// The directory doesn't exist in the upper layer yet.
+			self.upper.mkdir(&new_path, 0o755)?;
+		}
+		self.upper.rename(&old_path, &new_path)?;
+		self.whiteout(&old_path);
+		self.whiteout(&new_path);
+		Ok(())
+	}
+
+	fn set_owner(&mut self, path: &CStr, uid: u32, gid: u32) -> Result<(), Error> {
+		let path = normalize_path(path);
+		self.copy_up(&path)?;
+		self.upper.set_owner(&path, uid, gid)
+	}
+
+	fn set_times(&mut self, path: &CStr, atime: Duration, mtime: Duration) -> Result<(), Error> {
+		let path = normalize_path(path);
+		self.copy_up(&path)?;
+		self.upper.set_times(&path, atime, mtime)
+	}
+}
+
+/// The layer of [`OverlayFileSystem`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+	Lower = 0,
+	Upper = 1,
+}
+
+impl Layer {
+	/// Map the inode number of the layer to the one of the overlay.
+	///
+	/// The lowest bit is the layer.
+	const fn inode_id(self, id: u64) -> u64 {
+		(id << 1) | self as u64
+	}
+}
+
+pub enum OverlayFd<Lower, Upper> {
+	/// The file that exists only in the lower layer opened for reading.
+	Lower(Lower),
+	/// The file in the upper layer.
+	Upper(Upper),
+	/// Merged directory.
+	Dir(OverlayDir),
+}
+
+pub struct OverlayDir {
+	/// The index of the next directory entry.
+	pub position: u64,
+	entries: Vec<(CString, FileType, u64)>,
+}
+
+/// Read the directory until the end and call `f` for each entry.
+fn read_all_entries<F: FileSystem>(
+	fs: &mut F,
+	fd: &mut F::Fd,
+	mut f: impl FnMut(&CStr, FileType, u64),
+) -> Result<(), Error> {
+	let mut buf = vec![0_u8; COPY_BUF_SIZE];
+	loop {
+		let n = fs.read_dir(fd, &mut buf)?;
+		if n == 0 {
+			return Ok(());
+		}
+		let mut offset = 0;
+		while offset + DIR_ENTRY_NAME_OFFSET < n {
+			let entry = &buf[offset..n];
+			let id = u64::from_le_bytes(entry[0..8].try_into().expect("The slice is 8 bytes long"));
+			let len = u16::from_le_bytes([entry[16], entry[17]]) as usize;
+			let file_type = FileType::from_dir_entry_type(entry[18]);
+			let name = CStr::from_bytes_until_nul(&entry[DIR_ENTRY_NAME_OFFSET..])
+				.map_err(|_| Error(EIO))?;
+			f(name, file_type, id);
+			if len == 0 {
+				return Err(Error(EIO));
+			}
+			offset += len;
+		}
+	}
+}
+
+/// The offset of the name in `struct dirent64`.
+const DIR_ENTRY_NAME_OFFSET: usize = 19;
+
+const COPY_BUF_SIZE: usize = 64 * 1024;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::in_memory::InMemoryFileSystem;
+
+	type Overlay = OverlayFileSystem<InMemoryFileSystem, InMemoryFileSystem>;
+
+	fn overlay() -> Overlay {
+		let mut lower = InMemoryFileSystem::new();
+		lower.insert(c"/etc/hosts", &b"localhost"[..]);
+		lower.insert(c"/dir/a", &b"a"[..]);
+		lower.insert_symlink(c"/link", c"/etc/hosts");
+		OverlayFileSystem::new(lower, InMemoryFileSystem::new())
+	}
+
+	fn read_file<F: FileSystem>(fs: &mut F, path: &CStr) -> Result<Vec<u8>, Error> {
+		let mut fd = fs.open(path, O_RDONLY, 0)?;
+		let mut buf = vec![0_u8; 64];
+		let n = fs.read(&mut fd, &mut buf)?;
+		buf.truncate(n);
+		Ok(buf)
+	}
+
+	fn list(fs: &mut Overlay, path: &CStr) -> Vec<CString> {
+		let mut fd = fs.open(path, O_RDONLY | O_DIRECTORY, 0).unwrap();
+		let mut names = Vec::new();
+		read_all_entries(fs, &mut fd, |name, _, _| {
+			if !matches!(name.to_bytes(), b"." | b"..") {
+				names.push(name.into());
+			}
+		})
+		.unwrap();
+		names.sort();
+		names
+	}
+
+	#[test]
+	fn lower_files_are_visible() {
+		let mut fs = overlay();
+		assert_eq!(Ok(b"localhost".to_vec()), read_file(&mut fs, c"/etc/hosts"));
+		assert_eq!(9, fs.metadata(c"/link").unwrap().size);
+		assert_eq!(S_IFLNK, fs.symlink_metadata(c"/link").unwrap().mode & S_IFMT);
+		let lower_id = fs.lower.metadata(c"/etc/hosts").unwrap().id;
+		assert_eq!(lower_id << 1, fs.metadata(c"/etc/hosts").unwrap().id);
+		assert_eq!(Err(Error(ENOENT)), fs.upper.metadata(c"/etc"));
+	}
+
+	#[test]
+	fn writes_copy_up() {
+		let mut fs = overlay();
+		let mut fd = fs.open(c"/etc/hosts", O_RDWR, 0).unwrap();
+		fs.seek(&mut fd, SeekFrom::End(0)).unwrap();
+		assert_eq!(Ok(6), fs.write(&mut fd, b" local"));
+		assert_eq!(Ok(b"localhost local".to_vec()), read_file(&mut fs, c"/etc/hosts"));
+		assert_eq!(Ok(b"localhost local".to_vec()), read_file(&mut fs.upper, c"/etc/hosts"));
+		assert_eq!(Ok(b"localhost".to_vec()), read_file(&mut fs.lower, c"/etc/hosts"));
+		assert_eq!(1, fs.metadata(c"/etc/hosts").unwrap().id & 1);
+	}
+
+	#[test]
+	fn directories_are_merged() {
+		let mut fs = overlay();
+		fs.upper.insert(c"/etc/passwd", &b"root"[..]);
+		assert_eq!(vec![CString::from(c"hosts"), c"passwd".into()], list(&mut fs, c"/etc"));
+		assert_eq!(vec![CString::from(c"dir"), c"etc".into(), c"link".into()], list(&mut fs, c"/"));
+	}
+
+	#[test]
+	fn unlink_creates_whiteout() {
+		let mut fs = overlay();
+		assert_eq!(Ok(()), fs.unlink(c"/etc/hosts"));
+		assert_eq!(Err(Error(ENOENT)), fs.metadata(c"/etc/hosts"));
+		assert_eq!(Vec::<CString>::new(), list(&mut fs, c"/etc"));
+		assert_eq!(Ok(b"localhost".to_vec()), read_file(&mut fs.lower, c"/etc/hosts"));
+		// The whiteout doesn't hide the new file.
+		fs.open(c"/etc/hosts", O_WRONLY | O_CREAT | O_EXCL, 0o644).unwrap();
+		assert_eq!(Ok(Vec::new()), read_file(&mut fs, c"/etc/hosts"));
+		assert_eq!(Err(Error(EISDIR)), fs.unlink(c"/dir"));
+	}
+
+	#[test]
+	fn recreated_directory_is_opaque() {
+		let mut fs = overlay();
+		assert_eq!(Err(Error(ENOTEMPTY)), fs.rmdir(c"/dir"));
+		fs.unlink(c"/dir/a").unwrap();
+		assert_eq!(Ok(()), fs.rmdir(c"/dir"));
+		assert_eq!(Err(Error(ENOENT)), fs.metadata(c"/dir/a"));
+		assert_eq!(Ok(()), fs.mkdir(c"/dir", 0o755));
+		assert_eq!(Vec::<CString>::new(), list(&mut fs, c"/dir"));
+		assert_eq!(Err(Error(EBUSY)), fs.rmdir(c"/"));
+	}
+
+	#[test]
+	fn rename_copies_up_files() {
+		let mut fs = overlay();
+		assert_eq!(Ok(()), fs.rename(c"/etc/hosts", c"/dir/hosts"));
+		assert_eq!(Err(Error(ENOENT)), fs.metadata(c"/etc/hosts"));
+		assert_eq!(Ok(b"localhost".to_vec()), read_file(&mut fs, c"/dir/hosts"));
+		assert_eq!(Ok(()), fs.rename(c"/dir/hosts", c"/dir/a"));
+		assert_eq!(Ok(b"localhost".to_vec()), read_file(&mut fs, c"/dir/a"));
+		assert_eq!(vec![CString::from(c"a")], list(&mut fs, c"/dir"));
+	}
+
+	#[test]
+	fn rename_of_lower_directory_fails_with_exdev() {
+		let mut fs = overlay();
+		assert_eq!(Err(Error(EXDEV)), fs.rename(c"/dir", c"/dir2"));
+		fs.mkdir(c"/new", 0o755).unwrap();
+		assert_eq!(Ok(()), fs.rename(c"/new", c"/new2"));
+		assert_eq!(Err(Error(ENOTDIR)), fs.rename(c"/new2", c"/link"));
+		assert_eq!(Err(Error(EISDIR)), fs.rename(c"/link", c"/new2"));
+	}
+
+	#[test]
+	fn lower_layer_symlink_is_copied_up() {
+		let mut fs = overlay();
+		assert_eq!(Ok(()), fs.set_times(c"/link", Duration::ZERO, Duration::from_secs(1)));
+		assert_eq!(Ok(CString::from(c"/etc/hosts")), fs.upper.readlink(c"/link"));
+		assert_eq!(Duration::from_secs(1), fs.symlink_metadata(c"/link").unwrap().mtime);
+	}
+}
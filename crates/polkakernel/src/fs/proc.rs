@@ -0,0 +1,801 @@
+//! Synthetic `/proc` [`FileSystem`].
+
+use alloc::{
+	collections::BTreeMap,
+	ffi::CString,
+	format,
+	rc::Rc,
+	string::{String, ToString},
+	vec,
+	vec::Vec,
+};
+use core::{
+	cell::{Cell, Ref, RefCell},
+	ffi::CStr,
+	fmt::Write,
+	time::Duration,
+};
+
+use crate::{
+	libc::*, normalize_path, to_cstring, write_dir_entry, Credentials, Error, FileSystem, FileType,
+	Mapping, MemoryStats, Metadata, SeekFrom, WriteDirEntryErr,
+};
+
+/// The kernel state rendered by [`ProcFileSystem`].
+///
+/// Updated by [`Kernel`](crate::Kernel) when a syscall looks into the outdated file system, see
+/// [`Kernel::with_proc`](crate::Kernel::with_proc).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcInfo {
+	/// User and group IDs, process ID and friends.
+	pub credentials: Credentials,
+	/// Program arguments.
+	pub argv: Vec<CString>,
+	/// Environment variables.
+	pub envp: Vec<CString>,
+	/// The path of the executable.
+	pub exe: CString,
+	/// The targets of `/proc/self/fd` links by file descriptor.
+	pub fds: BTreeMap<u32, CString>,
+	/// Memory mappings by start address.
+	pub mappings: BTreeMap<u64, Mapping>,
+	/// The number of threads.
+	pub threads: u32,
+	/// Signals pending for the running thread.
+	pub thread_pending_signals: u64,
+	/// Signals pending for the process as a whole.
+	pub pending_signals: u64,
+	/// Signals blocked by the running thread.
+	pub blocked_signals: u64,
+	/// Ignored signals.
+	pub ignored_signals: u64,
+	/// Signals that have handlers installed.
+	pub caught_signals: u64,
+	/// Machine memory statistics.
+	pub memory: MemoryStats,
+	/// Time since the program has started as of the last monotonic clock reading.
+	pub uptime: Duration,
+}
+
+/// An implementation of [`FileSystem`] that renders the process information.
+///
+/// The file system is meant to be mounted at `/proc` via
+/// [`MountTable`](crate::mount::MountTable) and provides `self/fd`, `self/maps`, `self/status`,
+/// `self/cmdline`, `self/environ`, `self/exe`, `meminfo`, `cpuinfo` and `uptime`. Like on Linux,
+/// `self` is a symbolic link to the directory named after the process ID. The clones share the
+/// same [`ProcInfo`], hence the same instance can be registered as `proc` file system type for
+/// the guest to mount.
+///
+/// The files and the directory listings are rendered when they are opened. The file system is
+/// read-only, modifications fail with `EACCES`.
+///
+/// See [proc(5)](https://man7.org/linux/man-pages/man5/proc.5.html).
+#[derive(Debug, Clone, Default)]
+pub struct ProcFileSystem {
+	info: Rc<RefCell<ProcInfo>>,
+	freshness: Rc<Cell<Freshness>>,
+}
+
+impl ProcFileSystem {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Get the rendered kernel state.
+	pub fn info(&self) -> ProcInfo {
+		self.info.borrow().clone()
+	}
+
+	/// Replace the rendered kernel state.
+	pub fn set_info(&self, info: ProcInfo) {
+		*self.info.borrow_mut() = info;
+		self.freshness.set(Freshness::UpToDate);
+	}
+
+	/// Get the file descriptor from the metadata of `/proc/self/fd` link.
+	pub(crate) fn fd_link(meta: &Metadata) -> Option<u32> {
+		if meta.device != PROC_DEVICE || meta.mode & S_IFMT != S_IFLNK {
+			return None;
+		}
+		meta.id.checked_sub(FD_ID_BASE)?.try_into().ok()
+	}
+
+	/// Mark the rendered kernel state as outdated.
+	///
+	/// The file system keeps working with the outdated state but records the access, see
+	/// [`take_missed`](Self::take_missed).
+	pub fn invalidate(&self) {
+		self.freshness.set(Freshness::Outdated);
+	}
+
+	/// Check if the outdated state was accessed since the last call.
+	pub fn take_missed(&self) -> bool {
+		if self.freshness.get() != Freshness::Missed {
+			return false;
+		}
+		self.freshness.set(Freshness::Outdated);
+		true
+	}
+
+	/// Access the rendered kernel state recording the access if it is outdated.
+	fn state(&self) -> Ref<'_, ProcInfo> {
+		if self.freshness.get() == Freshness::Outdated {
+			self.freshness.set(Freshness::Missed);
+		}
+		self.info.borrow()
+	}
+
+	/// Find the node the path refers to.
+	fn lookup(&self, path: &CStr) -> Result<ProcNode, Error> {
+		let info = self.state();
+		let path = normalize_path(path);
+		let components: Vec<&[u8]> = path
+			.to_bytes()
+			.split(|byte| *byte == b'/')
+			.filter(|comp| !comp.is_empty())
+			.collect();
+		let is_process =
+			|comp: &[u8]| comp == b"self" || parse_u32(comp) == Some(info.credentials.pid);
+		let node = match components.as_slice() {
+			[] => ProcNode::Root,
+			[b"self"] => ProcNode::SelfLink,
+			[b"meminfo"] => ProcNode::File(ProcFile::Meminfo),
+			[b"cpuinfo"] => ProcNode::File(ProcFile::Cpuinfo),
+			[b"uptime"] => ProcNode::File(ProcFile::Uptime),
+			[process] if is_process(process) => ProcNode::ProcessDir,
+			[process, rest @ ..] if is_process(process) => match rest {
+				[b"fd"] => ProcNode::FdDir,
+				[b"fd", fd] => match parse_u32(fd) {
+					Some(fd) if info.fds.contains_key(&fd) => ProcNode::FdLink(fd),
+					_ => return Err(Error(ENOENT)),
+				},
+				[b"exe"] => ProcNode::ExeLink,
+				[b"maps"] => ProcNode::File(ProcFile::Maps),
+				[b"status"] => ProcNode::File(ProcFile::Status),
+				[b"cmdline"] => ProcNode::File(ProcFile::Cmdline),
+				[b"environ"] => ProcNode::File(ProcFile::Environ),
+				_ => return Err(Error(ENOENT)),
+			},
+			_ => return Err(Error(ENOENT)),
+		};
+		Ok(node)
+	}
+
+	/// Get the entries of the directory.
+	fn dir_entries(&self, dir: ProcNode) -> Vec<(CString, FileType, u64)> {
+		let info = self.state();
+		let mut entries = vec![
+			(c".".into(), FileType::Directory, dir.id()),
+			(c"..".into(), FileType::Directory, dir.parent().id()),
+		];
+		let nodes: Vec<(CString, ProcNode)> = match dir {
+			ProcNode::Root => vec![
+				(to_cstring(info.credentials.pid.to_string()), ProcNode::ProcessDir),
+				(c"self".into(), ProcNode::SelfLink),
+				(c"cpuinfo".into(), ProcNode::File(ProcFile::Cpuinfo)),
+				(c"meminfo".into(), ProcNode::File(ProcFile::Meminfo)),
+				(c"uptime".into(), ProcNode::File(ProcFile::Uptime)),
+			],
+			ProcNode::ProcessDir => vec![
+				(c"cmdline".into(), ProcNode::File(ProcFile::Cmdline)),
+				(c"environ".into(), ProcNode::File(ProcFile::Environ)),
+				(c"exe".into(), ProcNode::ExeLink),
+				(c"fd".into(), ProcNode::FdDir),
+				(c"maps".into(), ProcNode::File(ProcFile::Maps)),
+				(c"status".into(), ProcNode::File(ProcFile::Status)),
+			],
+			ProcNode::FdDir => info
+				.fds
+				.keys()
+				.map(|fd| (to_cstring(fd.to_string()), ProcNode::FdLink(*fd)))
+				.collect(),
+			_ => Vec::new(),
+		};
+		for (name, node) in nodes {
+			entries.push((name, FileType::from_mode(node.mode()), node.id()));
+		}
+		entries
+	}
+
+	/// Build the metadata of the node.
+	fn node_metadata(&self, node: ProcNode) -> Result<Metadata, Error> {
+		let info = self.state();
+		let mode = node.mode();
+		let size = match node {
+			ProcNode::SelfLink => info.credentials.pid.to_string().len() as u64,
+			ProcNode::ExeLink => info.exe.as_bytes().len() as u64,
+			ProcNode::FdLink(fd) =>
+				info.fds.get(&fd).map_or(0, |target| target.as_bytes().len()) as u64,
+			// Like on Linux, the files are empty until they are read.
+			_ => 0,
+		};
+		let nlink = match node {
+			ProcNode::Root | ProcNode::ProcessDir => 3,
+			ProcNode::FdDir => 2,
+			_ => 1,
+		};
+		Ok(Metadata {
+			id: node.id(),
+			size,
+			mode,
+			block_size: PAGE_SIZE,
+			nlink,
+			uid: info.credentials.euid,
+			gid: info.credentials.egid,
+			device: PROC_DEVICE,
+			..Default::default()
+		})
+	}
+
+	/// Render the contents of the file.
+	fn render(&self, file: ProcFile) -> Vec<u8> {
+		let info = self.state();
+		match file {
+			ProcFile::Cmdline => join_nul(&info.argv),
+			ProcFile::Environ => join_nul(&info.envp),
+			ProcFile::Maps => render_maps(&info).into_bytes(),
+			ProcFile::Status => render_status(&info).into_bytes(),
+			ProcFile::Meminfo => render_meminfo(&info).into_bytes(),
+			ProcFile::Cpuinfo => CPUINFO.into(),
+			ProcFile::Uptime => {
+				let uptime = info.uptime;
+				format!("{}.{:02} 0.00\n", uptime.as_secs(), uptime.subsec_millis() / 10)
+					.into_bytes()
+			},
+		}
+	}
+}
+
+/// The state of [`ProcInfo`] relative to the kernel state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Freshness {
+	#[default]
+	UpToDate,
+	/// The kernel state might have changed.
+	Outdated,
+	/// The outdated state was accessed.
+	Missed,
+}
+
+/// [`ProcFileSystem`] node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProcNode {
+	/// `/proc`.
+	Root,
+	/// `/proc/self`.
+	SelfLink,
+	/// `/proc/<pid>`.
+	ProcessDir,
+	/// `/proc/<pid>/fd`.
+	FdDir,
+	/// `/proc/<pid>/fd/<fd>`.
+	FdLink(u32),
+	/// `/proc/<pid>/exe`.
+	ExeLink,
+	/// Regular file.
+	File(ProcFile),
+}
+
+impl ProcNode {
+	/// Get the inode number.
+	const fn id(self) -> u64 {
+		match self {
+			Self::Root => 1,
+			Self::SelfLink => 2,
+			Self::File(ProcFile::Meminfo) => 3,
+			Self::File(ProcFile::Cpuinfo) => 4,
+			Self::File(ProcFile::Uptime) => 5,
+			Self::ProcessDir => 16,
+			Self::FdDir => 17,
+			Self::ExeLink => 18,
+			Self::File(ProcFile::Maps) => 19,
+			Self::File(ProcFile::Status) => 20,
+			Self::File(ProcFile::Cmdline) => 21,
+			Self::File(ProcFile::Environ) => 22,
+			Self::FdLink(fd) => FD_ID_BASE + fd as u64,
+		}
+	}
+
+	/// Get the parent directory.
+	const fn parent(self) -> Self {
+		match self {
+			Self::Root | Self::SelfLink | Self::ProcessDir => Self::Root,
+			Self::File(ProcFile::Meminfo | ProcFile::Cpuinfo | ProcFile::Uptime) => Self::Root,
+			Self::FdLink(..) => Self::FdDir,
+			Self::FdDir | Self::ExeLink | Self::File(..) => Self::ProcessDir,
+		}
+	}
+
+	/// Get the file type and permissions.
+	const fn mode(self) -> u32 {
+		match self {
+			Self::Root | Self::ProcessDir => S_IFDIR | 0o555,
+			Self::FdDir => S_IFDIR | 0o500,
+			Self::SelfLink | Self::FdLink(..) | Self::ExeLink => S_IFLNK | 0o777,
+			Self::File(ProcFile::Environ) => S_IFREG | 0o400,
+			Self::File(..) => S_IFREG | 0o444,
+		}
+	}
+}
+
+/// [`ProcFileSystem`] regular file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProcFile {
+	Maps,
+	Status,
+	Cmdline,
+	Environ,
+	Meminfo,
+	Cpuinfo,
+	Uptime,
+}
+
+impl FileSystem for ProcFileSystem {
+	type Fd = ProcFd;
+
+	fn open(&mut self, path: &CStr, flags: u64, _mode: u32) -> Result<Self::Fd, Error> {
+		let node = match self.lookup(path) {
+			Ok(..) if flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL => return Err(Error(EEXIST)),
+			Ok(node) => node,
+			Err(Error(ENOENT)) if flags & O_CREAT != 0 => return Err(Error(EACCES)),
+			Err(e) => return Err(e),
+		};
+		let writable = flags & O_ACCMODE != O_RDONLY;
+		match node {
+			ProcNode::Root | ProcNode::ProcessDir | ProcNode::FdDir if writable =>
+				Err(Error(EISDIR)),
+			ProcNode::Root | ProcNode::ProcessDir | ProcNode::FdDir =>
+				Ok(ProcFd::Dir(ProcDir { entries: self.dir_entries(node), position: 0 })),
+			_ if flags & O_DIRECTORY != 0 => Err(Error(ENOTDIR)),
+			// The kernel follows symbolic links before opening the file.
+			ProcNode::SelfLink | ProcNode::FdLink(..) | ProcNode::ExeLink => Err(Error(ELOOP)),
+			ProcNode::File(..) if writable => Err(Error(EACCES)),
+			ProcNode::File(file) =>
+				Ok(ProcFd::File(ProcFileHandle { contents: self.render(file), position: 0 })),
+		}
+	}
+
+	fn seek(&mut self, fd: &mut Self::Fd, from: SeekFrom) -> Result<u64, Error> {
+		let (position, size) = match fd {
+			ProcFd::File(file) => (&mut file.position, file.contents.len() as u64),
+			ProcFd::Dir(dir) => {
+				if let SeekFrom::End(..) = from {
+					return Err(Error(EINVAL));
+				}
+				(&mut dir.position, u64::MAX)
+			},
+		};
+		*position = match from {
+			SeekFrom::Start(offset) => offset,
+			SeekFrom::Current(offset) =>
+				position.checked_add_signed(offset).ok_or(Error(EINVAL))?,
+			SeekFrom::End(offset) => size.checked_add_signed(offset).ok_or(Error(EINVAL))?,
+		};
+		Ok(*position)
+	}
+
+	fn read(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		let ProcFd::File(file) = fd else {
+			return Err(Error(EISDIR));
+		};
+		let n = file.read_at(buf, file.position);
+		file.position += n as u64;
+		Ok(n)
+	}
+
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+		let ProcFd::File(file) = fd else {
+			return Err(Error(EISDIR));
+		};
+		Ok(file.read_at(buf, offset))
+	}
+
+	fn write(&mut self, _fd: &mut Self::Fd, _buf: &[u8]) -> Result<usize, Error> {
+		Err(Error(EBADF))
+	}
+
+	fn truncate(&mut self, _fd: &mut Self::Fd, _length: u64) -> Result<(), Error> {
+		Err(Error(EINVAL))
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		let ProcFd::Dir(dir) = fd else {
+			return Err(Error(ENOTDIR));
+		};
+		let mut offset = 0;
+		for (i, (name, file_type, id)) in dir.entries.iter().enumerate().skip(dir.position as usize)
+		{
+			let next_position = i as u64 + 1;
+			match write_dir_entry(*id, next_position, *file_type, name, &mut buf[offset..]) {
+				Ok(n) => {
+					offset += n;
+					dir.position = next_position;
+				},
+				Err(WriteDirEntryErr::BufferTooSmall) if offset != 0 => break,
+				Err(_) => return Err(Error(EINVAL)),
+			}
+		}
+		Ok(offset)
+	}
+
+	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		let node = match self.lookup(path)? {
+			ProcNode::SelfLink => ProcNode::ProcessDir,
+			// The targets of the other links are outside of this file system.
+			node => node,
+		};
+		self.node_metadata(node)
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		let node = self.lookup(path)?;
+		self.node_metadata(node)
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		let node = self.lookup(path)?;
+		let info = self.state();
+		match node {
+			ProcNode::SelfLink => Ok(to_cstring(info.credentials.pid.to_string())),
+			ProcNode::ExeLink => Ok(info.exe.clone()),
+			ProcNode::FdLink(fd) => info.fds.get(&fd).cloned().ok_or(Error(ENOENT)),
+			_ => Err(Error(EINVAL)),
+		}
+	}
+
+	fn symlink(&mut self, _target: &CStr, _path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn link(&mut self, _old_path: &CStr, _new_path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn unlink(&mut self, _path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn mkdir(&mut self, _path: &CStr, _mode: u32) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn rmdir(&mut self, _path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+
+	fn rename(&mut self, _old_path: &CStr, _new_path: &CStr) -> Result<(), Error> {
+		Err(Error(EACCES))
+	}
+}
+
+/// [`ProcFileSystem`] file handle.
+#[derive(Debug)]
+pub enum ProcFd {
+	File(ProcFileHandle),
+	Dir(ProcDir),
+}
+
+/// [`ProcFileSystem`] regular file handle.
+#[derive(Debug)]
+pub struct ProcFileHandle {
+	/// The contents rendered when the file was opened.
+	contents: Vec<u8>,
+	/// The current read offset.
+	position: u64,
+}
+
+impl ProcFileHandle {
+	fn read_at(&self, buf: &mut [u8], offset: u64) -> usize {
+		let size = self.contents.len() as u64;
+		if offset >= size {
+			return 0;
+		}
+		let end = core::cmp::min(offset.saturating_add(buf.len() as u64), size);
+		let slice = &self.contents[offset as usize..end as usize];
+		buf[..slice.len()].copy_from_slice(slice);
+		slice.len()
+	}
+}
+
+/// [`ProcFileSystem`] directory handle.
+#[derive(Debug)]
+pub struct ProcDir {
+	/// The entries rendered when the directory was opened.
+	entries: Vec<(CString, FileType, u64)>,
+	/// The index of the next directory entry.
+	position: u64,
+}
+
+fn render_maps(info: &ProcInfo) -> String {
+	let mut maps = String::new();
+	for (&start, mapping) in info.mappings.iter() {
+		let flag = |prot: u64, c: char| if mapping.prot & prot != 0 { c } else { '-' };
+		let shared = if mapping.flags & MAP_SHARED != 0 { 's' } else { 'p' };
+		let offset = mapping.file.as_ref().map_or(0, |file| file.offset);
+		let mut line = format!(
+			"{:08x}-{:08x} {}{}{}{} {:08x} 00:00 0",
+			start,
+			start + mapping.length,
+			flag(PROT_READ, 'r'),
+			flag(PROT_WRITE, 'w'),
+			flag(PROT_EXEC, 'x'),
+			shared,
+			offset,
+		);
+		if let Some(ref file) = mapping.file {
+			// Linux aligns the path to the 74th column.
+			let _ = write!(
+				line,
+				"{:width$}{}",
+				"",
+				file.path.to_string_lossy(),
+				width = 73usize.saturating_sub(line.len()).max(1)
+			);
+		}
+		maps.push_str(&line);
+		maps.push('\n');
+	}
+	maps
+}
+
+fn render_status(info: &ProcInfo) -> String {
+	let name = info
+		.argv
+		.first()
+		.map(|arg0| {
+			let arg0 = arg0.to_bytes();
+			let name = arg0.rsplit(|byte| *byte == b'/').next().unwrap_or(arg0);
+			// Linux truncates the name to `TASK_COMM_LEN - 1` bytes.
+			String::from_utf8_lossy(&name[..name.len().min(15)]).into_owned()
+		})
+		.unwrap_or_default();
+	let vm_size: u64 = info.mappings.values().map(|mapping| mapping.length).sum();
+	let fd_size = info.fds.keys().next_back().map_or(0, |fd| fd + 1).next_multiple_of(64);
+	let cred = &info.credentials;
+	let groups: String = cred.groups.iter().map(|group| format!("{group} ")).collect();
+	let mut status = String::new();
+	let _ = write!(
+		status,
+		"Name:\t{name}\n\
+		State:\tR (running)\n\
+		Tgid:\t{pid}\n\
+		Pid:\t{pid}\n\
+		PPid:\t{ppid}\n\
+		Uid:\t{uid}\t{euid}\t{suid}\t{euid}\n\
+		Gid:\t{gid}\t{egid}\t{sgid}\t{egid}\n\
+		FDSize:\t{fd_size}\n\
+		Groups:\t{groups}\n\
+		VmSize:\t{vm_size:8} kB\n\
+		Threads:\t{threads}\n\
+		SigPnd:\t{sig_pnd:016x}\n\
+		ShdPnd:\t{shd_pnd:016x}\n\
+		SigBlk:\t{sig_blk:016x}\n\
+		SigIgn:\t{sig_ign:016x}\n\
+		SigCgt:\t{sig_cgt:016x}\n\
+		Cpus_allowed:\t1\n\
+		Cpus_allowed_list:\t0\n",
+		pid = cred.pid,
+		ppid = cred.ppid,
+		uid = cred.uid,
+		euid = cred.euid,
+		suid = cred.suid,
+		gid = cred.gid,
+		egid = cred.egid,
+		sgid = cred.sgid,
+		vm_size = vm_size / 1024,
+		threads = info.threads,
+		sig_pnd = info.thread_pending_signals,
+		shd_pnd = info.pending_signals,
+		sig_blk = info.blocked_signals,
+		sig_ign = info.ignored_signals,
+		sig_cgt = info.caught_signals,
+	);
+	status
+}
+
+fn render_meminfo(info: &ProcInfo) -> String {
+	let mut meminfo = String::new();
+	let fields = [
+		("MemTotal:", info.memory.total),
+		("MemFree:", info.memory.free),
+		("MemAvailable:", info.memory.free),
+		("Buffers:", 0),
+		("Cached:", 0),
+		("SwapCached:", 0),
+		("SwapTotal:", 0),
+		("SwapFree:", 0),
+		("Shmem:", 0),
+	];
+	for (name, bytes) in fields {
+		let _ = writeln!(meminfo, "{name:<16}{:8} kB", bytes / 1024);
+	}
+	meminfo
+}
+
+/// Join the strings with NUL bytes as terminators.
+fn join_nul(strings: &[CString]) -> Vec<u8> {
+	strings.iter().flat_map(|string| string.to_bytes_with_nul()).copied().collect()
+}
+
+fn parse_u32(s: &[u8]) -> Option<u32> {
+	if s.is_empty() || (s.len() > 1 && s[0] == b'0') {
+		return None;
+	}
+	core::str::from_utf8(s).ok()?.parse().ok()
+}
+
+/// The inode number of the first `/proc/self/fd` link.
+const FD_ID_BASE: u64 = 256;
+
+/// The device `/proc` belongs to.
+const PROC_DEVICE: u64 = makedev(0, 22);
+
+/// PolkaVM machine is a single RISC-V hart.
+const CPUINFO: &[u8] = b"processor\t: 0\nhart\t\t: 0\nisa\t\t: rv64emac\nmmu\t\t: none\n\n";
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::MappedFile;
+
+	fn proc() -> ProcFileSystem {
+		let fs = ProcFileSystem::new();
+		let mut mappings = BTreeMap::new();
+		mappings.insert(
+			0x10000,
+			Mapping {
+				length: 0x2000,
+				prot: PROT_READ | PROT_WRITE,
+				flags: MAP_PRIVATE,
+				file: None,
+			},
+		);
+		mappings.insert(
+			0x20000,
+			Mapping {
+				length: 0x1000,
+				prot: PROT_READ | PROT_EXEC,
+				flags: MAP_PRIVATE,
+				file: Some(MappedFile { path: c"/lib/libc.so".into(), offset: 0x1000 }),
+			},
+		);
+		fs.set_info(ProcInfo {
+			credentials: Credentials::new().with_pid(42).with_uid(1000).with_groups([1, 2]),
+			argv: vec![c"/usr/bin/program-with-long-name".into(), c"-v".into()],
+			envp: vec![c"HOME=/".into()],
+			exe: c"/usr/bin/program-with-long-name".into(),
+			fds: [(0, c"/dev/tty".into()), (3, c"pipe:[5]".into())].into(),
+			mappings,
+			threads: 2,
+			blocked_signals: 1 << 9,
+			memory: MemoryStats { total: 8 << 20, free: 4 << 20 },
+			uptime: Duration::from_millis(3256),
+			..Default::default()
+		});
+		fs
+	}
+
+	fn read_file(fs: &mut ProcFileSystem, path: &CStr) -> Result<String, Error> {
+		let mut fd = fs.open(path, O_RDONLY, 0)?;
+		let mut contents = Vec::new();
+		let mut buf = [0_u8; 100];
+		loop {
+			match fs.read(&mut fd, &mut buf)? {
+				0 => return Ok(String::from_utf8(contents).unwrap()),
+				n => contents.extend_from_slice(&buf[..n]),
+			}
+		}
+	}
+
+	fn list(fs: &mut ProcFileSystem, path: &CStr) -> Vec<String> {
+		let mut fd = fs.open(path, O_RDONLY | O_DIRECTORY, 0).unwrap();
+		let mut buf = [0_u8; 1024];
+		let n = fs.read_dir(&mut fd, &mut buf).unwrap();
+		let mut names = Vec::new();
+		let mut offset = 0;
+		while offset < n {
+			let entry = &buf[offset..n];
+			let name = CStr::from_bytes_until_nul(&entry[19..]).unwrap();
+			names.push(name.to_str().unwrap().to_string());
+			offset += u16::from_le_bytes([entry[16], entry[17]]) as usize;
+		}
+		names
+	}
+
+	#[test]
+	fn process_directory_lookup() {
+		let mut fs = proc();
+		assert_eq!(Ok(c"42".into()), fs.readlink(c"/self"));
+		assert_eq!(Ok(c"pipe:[5]".into()), fs.readlink(c"/42/fd/3"));
+		assert_eq!(Ok(c"/usr/bin/program-with-long-name".into()), fs.readlink(c"/self/exe"));
+		assert_eq!(Err(Error(ENOENT)), fs.readlink(c"/self/fd/1"));
+		assert_eq!(Err(Error(ENOENT)), fs.readlink(c"/self/fd/03"));
+		assert_eq!(Err(Error(ENOENT)), fs.metadata(c"/43"));
+		assert_eq!(Err(Error(EINVAL)), fs.readlink(c"/self/status"));
+		assert_eq!(S_IFDIR | 0o555, fs.metadata(c"/self").unwrap().mode);
+		let link = fs.symlink_metadata(c"/self/fd/3").unwrap();
+		assert_eq!((8, 1000), (link.size, link.uid));
+		assert_eq!(Some(3), ProcFileSystem::fd_link(&link));
+		assert_eq!(None, ProcFileSystem::fd_link(&fs.symlink_metadata(c"/self").unwrap()));
+	}
+
+	#[test]
+	fn directory_listings() {
+		let mut fs = proc();
+		assert_eq!(
+			vec![".", "..", "42", "self", "cpuinfo", "meminfo", "uptime"],
+			list(&mut fs, c"/")
+		);
+		assert_eq!(
+			vec![".", "..", "cmdline", "environ", "exe", "fd", "maps", "status"],
+			list(&mut fs, c"/42")
+		);
+		assert_eq!(vec![".", "..", "0", "3"], list(&mut fs, c"/42/fd"));
+	}
+
+	#[test]
+	fn process_files() {
+		let mut fs = proc();
+		assert_eq!(
+			Ok("/usr/bin/program-with-long-name\0-v\0".into()),
+			read_file(&mut fs, c"/self/cmdline")
+		);
+		assert_eq!(Ok("HOME=/\0".into()), read_file(&mut fs, c"/self/environ"));
+		let maps = read_file(&mut fs, c"/self/maps").unwrap();
+		let lines: Vec<&str> = maps.lines().collect();
+		assert_eq!("00010000-00012000 rw-p 00000000 00:00 0", lines[0]);
+		assert!(lines[1].starts_with("00020000-00021000 r-xp 00001000 00:00 0 "));
+		assert_eq!(Some(73), lines[1].find('/'));
+		assert!(lines[1].ends_with("/lib/libc.so"));
+		let status = read_file(&mut fs, c"/self/status").unwrap();
+		for line in [
+			"Name:\tprogram-with-lo\n",
+			"Pid:\t42\n",
+			"Uid:\t1000\t1000\t1000\t1000\n",
+			"Gid:\t0\t0\t0\t0\n",
+			"FDSize:\t64\n",
+			"Groups:\t1 2 \n",
+			"VmSize:\t      12 kB\n",
+			"Threads:\t2\n",
+			"SigBlk:\t0000000000000200\n",
+		] {
+			assert!(status.contains(line), "{line:?} is missing in {status:?}");
+		}
+	}
+
+	#[test]
+	fn system_files() {
+		let mut fs = proc();
+		assert_eq!(Ok("3.25 0.00\n".into()), read_file(&mut fs, c"/uptime"));
+		let meminfo = read_file(&mut fs, c"/meminfo").unwrap();
+		assert!(meminfo.starts_with("MemTotal:           8192 kB\nMemFree:            4096 kB\n"));
+		assert!(read_file(&mut fs, c"/cpuinfo").unwrap().contains("rv64emac"));
+		// The files are empty until they are read.
+		assert_eq!(0, fs.metadata(c"/meminfo").unwrap().size);
+	}
+
+	#[test]
+	fn file_system_is_read_only() {
+		let mut fs = proc();
+		assert_eq!(Err(Error(EACCES)), fs.open(c"/self/status", O_WRONLY, 0).map(|_| ()));
+		assert_eq!(Err(Error(EACCES)), fs.open(c"/new", O_WRONLY | O_CREAT, 0o644).map(|_| ()));
+		assert_eq!(Err(Error(EISDIR)), fs.open(c"/42", O_RDWR, 0).map(|_| ()));
+		assert_eq!(Err(Error(ELOOP)), fs.open(c"/self/exe", O_RDONLY, 0).map(|_| ()));
+		assert_eq!(Err(Error(EACCES)), fs.mkdir(c"/dir", 0o755));
+		assert_eq!(Err(Error(EACCES)), fs.unlink(c"/uptime"));
+	}
+
+	#[test]
+	fn outdated_state_access_is_recorded() {
+		let fs = proc();
+		assert!(!fs.take_missed());
+		fs.invalidate();
+		assert!(!fs.take_missed());
+		fs.clone().metadata(c"/self").unwrap();
+		assert!(fs.take_missed());
+		assert!(!fs.take_missed());
+		fs.set_info(fs.info());
+		fs.clone().metadata(c"/self").unwrap();
+		assert!(!fs.take_missed());
+	}
+}
@@ -1,39 +1,141 @@
 //! A local [`FileSystem`].
 
-use core::ffi::CStr;
+use alloc::ffi::CString;
+use core::{ffi::CStr, time::Duration};
 use std::{
 	ffi::OsStr,
 	os::{
 		fd::RawFd,
-		unix::{ffi::OsStrExt, fs::MetadataExt},
+		unix::{
+			ffi::{OsStrExt, OsStringExt},
+			fs::{DirBuilderExt, MetadataExt},
+		},
 	},
-	path::Path,
+	path::{Path, PathBuf},
 };
 
-use crate::{Error, FileSystem, Metadata, SeekFrom};
+use crate::{
+	libc::{
+		EINVAL, ENOTDIR, EROFS, O_ACCMODE, O_APPEND, O_CREAT, O_DIRECTORY, O_EXCL, O_NOFOLLOW,
+		O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY,
+	},
+	resolve_links_with, write_dir_entry, Error, FileSystem, FileType, Metadata, PathNode, SeekFrom,
+	WriteDirEntryErr,
+};
 
 /// An implementation of [`FileSystem`] that uses local file system.
-pub struct StdFileSystem;
+///
+/// By default the guest has full access to the host file system. [`StdFileSystem::sandboxed`]
+/// confines all operations to the provided host directory like `chroot` does.
+#[derive(Debug, Clone, Default)]
+pub struct StdFileSystem {
+	/// Canonical host path of the root directory.
+	root: Option<PathBuf>,
+	/// Reject modifications with `EROFS`.
+	read_only: bool,
+}
+
+impl StdFileSystem {
+	/// Create file system with unrestricted access to the host file system.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create file system confined to the `root` host directory.
+	///
+	/// Guest paths are resolved relative to `root`: `..` can't escape it and the targets of
+	/// absolute symbolic links are interpreted relative to it. The host file system should not be
+	/// modified concurrently, otherwise the guest may escape the root between the resolution and
+	/// the actual operation.
+	pub fn sandboxed(root: impl AsRef<Path>) -> Result<Self, Error> {
+		let root = std::fs::canonicalize(root)?;
+		if !root.is_dir() {
+			return Err(Error(ENOTDIR));
+		}
+		Ok(Self { root: Some(root), read_only: false })
+	}
+
+	/// Reject all modifications of the file system with `EROFS`.
+	pub fn with_read_only(mut self, read_only: bool) -> Self {
+		self.read_only = read_only;
+		self
+	}
+
+	/// Translate the guest path to the host path.
+	///
+	/// In sandboxed mode the symbolic links are resolved within the root, the last component of
+	/// the path is followed only if `follow` is true.
+	fn host_path(&self, path: &CStr, follow: bool) -> Result<CString, Error> {
+		let Some(root) = self.root.as_ref() else {
+			return Ok(path.into());
+		};
+		let to_host = |path: &CStr| {
+			let mut host = root.clone().into_os_string().into_vec();
+			if path.to_bytes() != b"/" {
+				host.extend_from_slice(path.to_bytes());
+			}
+			// SAFETY: Neither path contains NUL bytes.
+			unsafe { CString::from_vec_unchecked(host) }
+		};
+		let resolved = resolve_links_with(c"/", path, follow, |path| {
+			let host = to_host(path);
+			let meta = std::fs::symlink_metadata(to_path(&host))?;
+			Ok(if meta.is_symlink() {
+				PathNode::Symlink(read_link(&host)?)
+			} else if meta.is_dir() {
+				PathNode::Dir
+			} else {
+				PathNode::Other
+			})
+		})?;
+		Ok(to_host(&resolved))
+	}
+
+	fn check_writable(&self) -> Result<(), Error> {
+		if self.read_only {
+			return Err(Error(EROFS));
+		}
+		Ok(())
+	}
+}
 
 impl FileSystem for StdFileSystem {
 	type Fd = StdFd;
 
-	fn open(&mut self, path: &CStr, flags: u64) -> Result<Self::Fd, Error> {
-		if flags & libc::O_DIRECTORY as u64 != 0 {
-			let dir = unsafe { libc::opendir(path.as_ptr()) };
+	fn open(&mut self, path: &CStr, flags: u64, mode: u32) -> Result<Self::Fd, Error> {
+		if flags & (O_CREAT | O_TRUNC) != 0 || flags & O_ACCMODE != O_RDONLY {
+			self.check_writable()?;
+		}
+		let nofollow = flags & O_NOFOLLOW != 0 || flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL;
+		let path = self.host_path(path, !nofollow)?;
+		let flags = host_open_flags(flags)?;
+		let mode = mode & 0o7777;
+		if flags & libc::O_DIRECTORY != 0 {
+			let raw_fd = check(unsafe { libc::open(path.as_ptr(), flags, mode) })?;
+			let dir = unsafe { libc::fdopendir(raw_fd) };
 			if dir.is_null() {
-				return Err(errno_error());
+				let error = errno_error();
+				unsafe { libc::close(raw_fd) };
+				return Err(error);
 			}
 			Ok(StdFd::Dir(dir))
 		} else {
-			let raw_fd = check(unsafe { libc::open(path.as_ptr(), flags as i32) })?;
+			let raw_fd = check(unsafe { libc::open(path.as_ptr(), flags, mode) })?;
 			Ok(StdFd::File(raw_fd))
 		}
 	}
 
 	fn seek(&mut self, fd: &mut Self::Fd, from: SeekFrom) -> Result<u64, Error> {
-		let StdFd::File(ref fd) = fd else {
-			return Err(Error(crate::libc::EBADF));
+		let fd = match fd {
+			StdFd::File(fd) => fd,
+			StdFd::Dir(dir) => {
+				match from {
+					SeekFrom::Start(x) => unsafe { libc::seekdir(*dir, x as libc::c_long) },
+					SeekFrom::Current(0) => {},
+					_ => return Err(Error(EINVAL)),
+				}
+				return Ok(unsafe { libc::telldir(*dir) } as u64);
+			},
 		};
 		let (whence, offset): (libc::c_int, libc::off64_t) = match from {
 			SeekFrom::Start(x) =>
@@ -55,20 +157,158 @@ impl FileSystem for StdFileSystem {
 		Ok(ret as usize)
 	}
 
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+		let StdFd::File(ref fd) = fd else {
+			return Err(Error(crate::libc::EBADF));
+		};
+		let offset = offset.try_into().map_err(|_| Error(EINVAL))?;
+		let ret = check(unsafe {
+			libc::pread64(*fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), offset)
+		} as i64)?;
+		Ok(ret as usize)
+	}
+
+	fn write(&mut self, fd: &mut Self::Fd, buf: &[u8]) -> Result<usize, Error> {
+		let StdFd::File(ref fd) = fd else {
+			return Err(Error(crate::libc::EBADF));
+		};
+		let ret = check(
+			unsafe { libc::write(*fd, buf.as_ptr() as *const libc::c_void, buf.len()) } as i64,
+		)?;
+		Ok(ret as usize)
+	}
+
+	fn truncate(&mut self, fd: &mut Self::Fd, length: u64) -> Result<(), Error> {
+		let StdFd::File(ref fd) = fd else {
+			return Err(Error(EINVAL));
+		};
+		let length = length.try_into().map_err(|_| Error(crate::libc::EFBIG))?;
+		check(unsafe { libc::ftruncate64(*fd, length) })?;
+		Ok(())
+	}
+
 	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
-		let path = Path::new(OsStr::from_bytes(path.to_bytes()));
-		let meta = std::fs::metadata(path)?;
-		Ok(Metadata {
-			size: meta.size(),
-			mode: meta.mode(),
-			id: meta.ino(),
-			block_size: meta.blksize(),
-		})
+		let path = self.host_path(path, true)?;
+		Ok(to_metadata(&std::fs::metadata(to_path(&path))?))
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		let path = self.host_path(path, false)?;
+		Ok(to_metadata(&std::fs::symlink_metadata(to_path(&path))?))
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		read_link(&self.host_path(path, false)?)
+	}
+
+	fn symlink(&mut self, target: &CStr, path: &CStr) -> Result<(), Error> {
+		self.check_writable()?;
+		let path = self.host_path(path, false)?;
+		std::os::unix::fs::symlink(to_path(target), to_path(&path))?;
+		Ok(())
+	}
+
+	fn link(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		self.check_writable()?;
+		let old_path = self.host_path(old_path, false)?;
+		let new_path = self.host_path(new_path, false)?;
+		std::fs::hard_link(to_path(&old_path), to_path(&new_path))?;
+		Ok(())
 	}
 
-	fn read_dir(&mut self, _fd: &mut Self::Fd, _buf: &mut [u8]) -> Result<usize, Error> {
-		// TODO
-		Ok(0)
+	fn unlink(&mut self, path: &CStr) -> Result<(), Error> {
+		self.check_writable()?;
+		let path = self.host_path(path, false)?;
+		std::fs::remove_file(to_path(&path))?;
+		Ok(())
+	}
+
+	fn mkdir(&mut self, path: &CStr, mode: u32) -> Result<(), Error> {
+		self.check_writable()?;
+		let path = self.host_path(path, false)?;
+		std::fs::DirBuilder::new().mode(mode).create(to_path(&path))?;
+		Ok(())
+	}
+
+	fn rmdir(&mut self, path: &CStr) -> Result<(), Error> {
+		self.check_writable()?;
+		let path = self.host_path(path, false)?;
+		std::fs::remove_dir(to_path(&path))?;
+		Ok(())
+	}
+
+	fn rename(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		self.check_writable()?;
+		let old_path = self.host_path(old_path, false)?;
+		let new_path = self.host_path(new_path, false)?;
+		std::fs::rename(to_path(&old_path), to_path(&new_path))?;
+		Ok(())
+	}
+
+	fn set_owner(&mut self, path: &CStr, uid: u32, gid: u32) -> Result<(), Error> {
+		self.check_writable()?;
+		let path = self.host_path(path, false)?;
+		std::os::unix::fs::lchown(to_path(&path), Some(uid), Some(gid))?;
+		Ok(())
+	}
+
+	fn set_times(&mut self, path: &CStr, atime: Duration, mtime: Duration) -> Result<(), Error> {
+		self.check_writable()?;
+		let path = self.host_path(path, false)?;
+		let to_timespec = |time: Duration| libc::timespec {
+			tv_sec: time.as_secs() as libc::time_t,
+			tv_nsec: time.subsec_nanos().into(),
+		};
+		let times = [to_timespec(atime), to_timespec(mtime)];
+		check(unsafe {
+			libc::utimensat(
+				libc::AT_FDCWD,
+				path.as_ptr(),
+				times.as_ptr(),
+				libc::AT_SYMLINK_NOFOLLOW,
+			)
+		})?;
+		Ok(())
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		let StdFd::Dir(dir) = fd else {
+			return Err(Error(crate::libc::ENOTDIR));
+		};
+		let mut offset = 0;
+		loop {
+			let position = unsafe { libc::telldir(*dir) };
+			// `readdir` returns NULL both on error and at the end of the directory.
+			unsafe { *libc::__errno_location() = 0 };
+			let entry = unsafe { libc::readdir64(*dir) };
+			if entry.is_null() {
+				let error = errno_error();
+				if error.0 != 0 {
+					return Err(error);
+				}
+				break;
+			}
+			let entry = unsafe { &*entry };
+			let name = unsafe { CStr::from_ptr(entry.d_name.as_ptr()) };
+			match write_dir_entry(
+				entry.d_ino,
+				entry.d_off as u64,
+				FileType::from_dir_entry_type(entry.d_type),
+				name,
+				&mut buf[offset..],
+			) {
+				Ok(n) => offset += n,
+				Err(e) => {
+					// Return the entry to the stream.
+					unsafe { libc::seekdir(*dir, position) };
+					if offset == 0 || !matches!(e, WriteDirEntryErr::BufferTooSmall) {
+						return Err(Error(EINVAL));
+					}
+					break;
+				},
+			}
+		}
+		Ok(offset)
 	}
 }
 
@@ -90,6 +330,59 @@ impl Drop for StdFd {
 	}
 }
 
+/// Translate the guest `open` flags to the host ones.
+///
+/// Only the flags that affect the opened file are passed through. The path is already resolved,
+/// hence a symbolic link at the end of it is never followed.
+fn host_open_flags(flags: u64) -> Result<libc::c_int, Error> {
+	let mut host_flags = match flags & O_ACCMODE {
+		O_RDONLY => libc::O_RDONLY,
+		O_WRONLY => libc::O_WRONLY,
+		O_RDWR => libc::O_RDWR,
+		_ => return Err(Error(EINVAL)),
+	};
+	for (flag, host_flag) in [
+		(O_CREAT, libc::O_CREAT),
+		(O_EXCL, libc::O_EXCL),
+		(O_TRUNC, libc::O_TRUNC),
+		(O_APPEND, libc::O_APPEND),
+		(O_DIRECTORY, libc::O_DIRECTORY),
+	] {
+		if flags & flag != 0 {
+			host_flags |= host_flag;
+		}
+	}
+	Ok(host_flags | libc::O_NOFOLLOW | libc::O_CLOEXEC)
+}
+
+fn to_path(path: &CStr) -> &Path {
+	Path::new(OsStr::from_bytes(path.to_bytes()))
+}
+
+fn read_link(path: &CStr) -> Result<CString, Error> {
+	let target = std::fs::read_link(to_path(path))?;
+	CString::new(target.into_os_string().into_vec()).map_err(|_| Error(EINVAL))
+}
+
+fn to_metadata(meta: &std::fs::Metadata) -> Metadata {
+	// Timestamps before the Unix epoch are clamped.
+	let timestamp = |secs: i64, nanos: i64| Duration::new(secs.max(0) as u64, nanos as u32);
+	Metadata {
+		size: meta.size(),
+		mode: meta.mode(),
+		id: meta.ino(),
+		block_size: meta.blksize(),
+		nlink: meta.nlink() as u32,
+		uid: meta.uid(),
+		gid: meta.gid(),
+		device: meta.dev(),
+		rdev: meta.rdev(),
+		atime: timestamp(meta.atime(), meta.atime_nsec()),
+		mtime: timestamp(meta.mtime(), meta.mtime_nsec()),
+		ctime: timestamp(meta.ctime(), meta.ctime_nsec()),
+	}
+}
+
 fn check<T: Into<i64> + Copy>(ret: T) -> Result<T, Error> {
 	if ret.into() < 0_i64 {
 		return Err(errno_error());
@@ -101,3 +394,88 @@ fn errno_error() -> Error {
 	let errno = unsafe { libc::__errno_location() };
 	Error(unsafe { std::ptr::read::<i32>(errno) } as u64)
 }
+
+#[cfg(test)]
+mod tests {
+	use alloc::{format, vec::Vec};
+	use std::os::unix::fs::symlink;
+
+	use super::*;
+	use crate::libc::{ELOOP, ENOENT};
+
+	/// Host directory with the sandbox root inside and a secret file next to it.
+	struct Sandbox {
+		dir: PathBuf,
+		fs: StdFileSystem,
+	}
+
+	impl Sandbox {
+		fn new(name: &str) -> Self {
+			let dir =
+				std::env::temp_dir().join(format!("polkakernel-{name}-{}", std::process::id()));
+			let _ = std::fs::remove_dir_all(&dir);
+			std::fs::create_dir_all(dir.join("root")).unwrap();
+			std::fs::write(dir.join("root/file"), b"inside").unwrap();
+			std::fs::write(dir.join("secret"), b"outside").unwrap();
+			let fs = StdFileSystem::sandboxed(dir.join("root")).unwrap();
+			Self { dir, fs }
+		}
+
+		fn symlink(&self, target: impl AsRef<Path>, name: &str) {
+			symlink(target, self.dir.join("root").join(name)).unwrap();
+		}
+
+		fn read(&mut self, path: &CStr, flags: u64) -> Result<Vec<u8>, Error> {
+			let mut fd = self.fs.open(path, flags, 0)?;
+			let mut buf = [0_u8; 16];
+			let n = self.fs.read(&mut fd, &mut buf)?;
+			Ok(buf[..n].to_vec())
+		}
+	}
+
+	impl Drop for Sandbox {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.dir);
+		}
+	}
+
+	#[test]
+	fn dot_dot_stays_in_root() {
+		let mut sandbox = Sandbox::new("dot-dot");
+		assert_eq!(sandbox.read(c"/../file", O_RDONLY), Ok(b"inside".to_vec()));
+		assert_eq!(sandbox.read(c"/../../secret", O_RDONLY), Err(Error(ENOENT)));
+	}
+
+	#[test]
+	fn absolute_symlinks_are_relative_to_root() {
+		let mut sandbox = Sandbox::new("absolute");
+		sandbox.symlink("/file", "link");
+		sandbox.symlink(sandbox.dir.join("secret"), "host-link");
+		assert_eq!(sandbox.read(c"/link", O_RDONLY), Ok(b"inside".to_vec()));
+		assert_eq!(sandbox.read(c"/host-link", O_RDONLY), Err(Error(ENOENT)));
+	}
+
+	#[test]
+	fn symlinks_do_not_escape_root() {
+		let mut sandbox = Sandbox::new("escape");
+		sandbox.symlink("../secret", "up");
+		sandbox.symlink("../../../..", "dir");
+		assert_eq!(sandbox.read(c"/up", O_RDONLY), Err(Error(ENOENT)));
+		assert_eq!(sandbox.read(c"/dir/file", O_RDONLY), Ok(b"inside".to_vec()));
+		assert_eq!(sandbox.read(c"/dir/secret", O_RDONLY), Err(Error(ENOENT)));
+	}
+
+	#[test]
+	fn last_symlink_is_not_followed_with_nofollow() {
+		let mut sandbox = Sandbox::new("nofollow");
+		sandbox.symlink("/file", "link");
+		assert_eq!(sandbox.read(c"/link", O_RDONLY | O_NOFOLLOW), Err(Error(ELOOP)));
+	}
+
+	#[test]
+	fn unsupported_flags_are_not_passed_to_host() {
+		const O_PATH: u64 = 0o10000000;
+		let mut sandbox = Sandbox::new("flags");
+		assert_eq!(sandbox.read(c"/file", O_RDONLY | O_PATH), Ok(b"inside".to_vec()));
+	}
+}
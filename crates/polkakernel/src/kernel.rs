@@ -1,21 +1,99 @@
-use alloc::{collections::BTreeMap, vec};
-use core::{ffi::CStr, mem::size_of};
+use alloc::{collections::BTreeMap, ffi::CString, format, vec, vec::Vec};
+use core::{
+	ffi::CStr,
+	mem::{offset_of, size_of},
+	time::Duration,
+};
 
 use crate::{
-	debug, libc::*, Environment, Error, FileSystem, IntoSyscallRet, Machine, MachineError, Reg,
-	SeekFrom,
+	debug,
+	dev::{PTS_DEVICE, TTY_DEVICE, URANDOM_DEVICE},
+	greg_index,
+	libc::*,
+	normalize_path, parent_dir,
+	proc::{ProcFileSystem, ProcInfo},
+	resolve_target_with, sig_bit, to_cstring, AuxVec, ChaChaRng, Clock, ClockId, Credentials,
+	DefaultAction, Environment, Error, FdTable, FileObject, FileSystem, IntervalTimer,
+	IntoSyscallRet, Machine, MachineError, MappedFile, MemoryMap, Metadata, OpenFile, PathNode,
+	Reg, Scheduler, SeekFrom, SigAction, SignalStack, Signals, Target, Thread, ThreadState,
+	VirtualTime, MAX_FDS, NUM_SIGNALS, UNBLOCKABLE,
 };
 
 use SyscallOutcome::*;
 
 /// Serializable kernel state.
 pub struct KernelState<Fd> {
-	pub fds: BTreeMap<u32, Fd>,
+	/// Open file descriptors.
+	pub fds: FdTable<Fd>,
+	/// Current working directory.
+	///
+	/// Always an absolute normalized path.
+	pub cwd: CString,
+	/// The total time the program has slept in [`VirtualTime`] mode.
+	pub slept: Duration,
+	/// Memory mappings.
+	pub memory: MemoryMap,
+	/// Threads of the program.
+	pub threads: Scheduler,
+	/// Signal dispositions, process-wide pending signals and timers.
+	pub signals: Signals,
+	/// The deadline of the syscall that has been suspended with [`WaitReason::Timer`].
+	///
+	/// Reused when the syscall is restarted, so that the timeout doesn't start over.
+	pub restart_deadline: Option<Duration>,
+	/// Program arguments passed to [`Kernel::init`].
+	pub argv: Vec<CString>,
+	/// Environment variables passed to [`Kernel::init`].
+	pub envp: Vec<CString>,
+	/// The path of the executable.
+	///
+	/// The first program argument resolved relative to the working directory by [`Kernel::init`].
+	pub exe: CString,
+	/// The source of `getrandom` and `AT_RANDOM` bytes.
+	pub random: ChaChaRng,
+	/// User and group IDs, process ID and friends.
+	pub credentials: Credentials,
+	/// File mode creation mask.
+	///
+	/// See [umask(2)](https://man7.org/linux/man-pages/man2/umask.2.html).
+	pub umask: u32,
 }
 
 impl<Fd> KernelState<Fd> {
 	pub fn new() -> Self {
-		Self { fds: BTreeMap::new() }
+		Self {
+			fds: FdTable::new(),
+			cwd: c"/".into(),
+			slept: Duration::ZERO,
+			memory: MemoryMap::new(),
+			threads: Scheduler::new(),
+			signals: Signals::new(),
+			restart_deadline: None,
+			argv: Vec::new(),
+			envp: Vec::new(),
+			exe: CString::default(),
+			random: ChaChaRng::default(),
+			credentials: Credentials::new(),
+			umask: DEFAULT_UMASK,
+		}
+	}
+
+	/// Seed the random number generator.
+	///
+	/// The seed is all zeroes by default.
+	pub fn with_random_seed(mut self, seed: [u8; 32]) -> Self {
+		self.random = ChaChaRng::new(seed);
+		self
+	}
+
+	/// Set process credentials.
+	///
+	/// The process runs as the superuser with process ID 1 by default. The threads are reset to
+	/// the main thread whose ID equals the process ID.
+	pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+		self.threads = Scheduler::with_main_tid(credentials.pid);
+		self.credentials = credentials;
+		self
 	}
 }
 
@@ -26,19 +104,106 @@ impl<Fd> Default for KernelState<Fd> {
 }
 
 /// Linux kernel engine that implements system calls.
-pub struct Kernel<C: Machine + Environment + FileSystem> {
+pub struct Kernel<C: Machine + Environment + FileSystem + Clock> {
 	/// The execution context of all syscalls.
 	pub context: C,
 	/// Persistent state.
 	pub state: KernelState<C::Fd>,
-	uid: u32,
-	gid: u32,
-	max_fd: Option<u32>,
+	virtual_time: Option<VirtualTime>,
+	/// Set when the current syscall can't complete until the host resumes the program.
+	blocked: Option<WaitReason>,
+	/// Synthetic `/proc` file system that renders the kernel state.
+	proc: Option<ProcFileSystem>,
+	/// The last reading of the monotonic clock.
+	last_monotonic: Duration,
 }
 
-impl<C: Machine + Environment + FileSystem> Kernel<C> {
+impl<C: Machine + Environment + FileSystem + Clock> Kernel<C> {
 	pub fn new(context: C, state: KernelState<C::Fd>) -> Self {
-		Self { context, state, uid: 0, gid: 0, max_fd: None }
+		Self {
+			context,
+			state,
+			virtual_time: None,
+			blocked: None,
+			proc: None,
+			last_monotonic: Duration::ZERO,
+		}
+	}
+
+	/// Keep the provided `/proc` file system up to date with the kernel state.
+	///
+	/// The file system is updated when a syscall looks into it. It is up to the host to mount it,
+	/// e.g. via [`MountTable`](crate::mount::MountTable).
+	pub fn with_proc(mut self, proc: ProcFileSystem) -> Self {
+		self.proc = Some(proc);
+		self
+	}
+
+	/// Get the default aux vector.
+	///
+	/// All the fields [`init`](Self::init) fills are left unset.
+	pub fn auxv(&self) -> AuxVec {
+		AuxVec::new()
+	}
+
+	/// Prepare the machine for calling `main` function.
+	///
+	/// Records the arguments and the environment variables for `/proc` and forwards them to
+	/// [`Machine::init`] along with `auxv`, see [`auxv`](Self::auxv). `AT_EXECFN` defaults to
+	/// the first argument, `AT_RANDOM` bytes default to the ones drawn from
+	/// [`KernelState::random`], the IDs default to the ones from [`KernelState::credentials`] and
+	/// `AT_SECURE` defaults to whether the real and the effective IDs differ.
+	pub fn init<'argv, 'envp, I1, I2>(
+		&mut self,
+		default_sp: u64,
+		default_ra: u64,
+		argv: I1,
+		envp: I2,
+		mut auxv: AuxVec,
+	) -> Result<(), MachineError>
+	where
+		I1: IntoIterator<Item = &'argv CStr>,
+		I2: IntoIterator<Item = &'envp CStr>,
+	{
+		self.state.argv = argv.into_iter().map(CString::from).collect();
+		self.state.envp = envp.into_iter().map(CString::from).collect();
+		self.state.exe = match self.state.argv.first().cloned() {
+			Some(arg0) =>
+				self.resolve_path(AT_FDCWD, &arg0, 0).unwrap_or_else(|_| normalize_path(&arg0)),
+			None => CString::default(),
+		};
+		if auxv.execfn.is_empty() {
+			if let Some(arg0) = self.state.argv.first() {
+				auxv.execfn = arg0.clone();
+			}
+		}
+		let cred = &self.state.credentials;
+		let uid = *auxv.uid.get_or_insert(cred.uid);
+		let euid = *auxv.euid.get_or_insert(cred.euid);
+		let gid = *auxv.gid.get_or_insert(cred.gid);
+		let egid = *auxv.egid.get_or_insert(cred.egid);
+		auxv.secure.get_or_insert(uid != euid || gid != egid);
+		if auxv.random.is_none() {
+			let mut random = [0_u8; 16];
+			self.state.random.fill(&mut random);
+			auxv.random = Some(random);
+		}
+		self.context.init(
+			default_sp,
+			default_ra,
+			self.state.argv.iter().map(CString::as_c_str),
+			self.state.envp.iter().map(CString::as_c_str),
+			&auxv,
+		)
+	}
+
+	/// Derive time from the gas consumed by the program instead of using [`Clock`].
+	///
+	/// This is the only deterministic time model: once enabled, the kernel never reads, sleeps on
+	/// or queries the resolution of the host clock.
+	pub fn with_virtual_time(mut self, virtual_time: VirtualTime) -> Self {
+		self.virtual_time = Some(virtual_time);
+		self
 	}
 
 	pub fn handle_syscall(&mut self) -> Result<SyscallOutcome, MachineError> {
@@ -48,6 +213,9 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 		let a3 = self.context.reg(Reg::A3);
 		let a4 = self.context.reg(Reg::A4);
 		let a5 = self.context.reg(Reg::A5);
+		if let Some(ref proc) = self.proc {
+			proc.invalidate();
+		}
 		match syscall {
 			SYS_READ => {
 				let result = self.handle_read(a1 as i64 as i32, a2, a3);
@@ -71,28 +239,53 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 			},
 			SYS_EXIT => {
 				log::debug!("Syscall exit(status={a1})");
-				return Ok(Exit(a1 as u8));
+				if let Some(outcome) = self.handle_exit(a1 as u8) {
+					return Ok(outcome);
+				}
 			},
 			SYS_EXIT_GROUP => {
 				log::debug!("Syscall exit_group(status={a1})");
 				return Ok(Exit(a1 as u8));
 			},
+			SYS_KILL => {
+				let result = self.handle_kill(a1 as i64 as i32, a2);
+				log::debug!(
+					"Syscall kill(pid={}, signal={}) = {result:?}",
+					a1 as i64 as i32,
+					debug::Signal(a2 as u8)
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_TKILL => {
-				if a1 == 0 && a2 != 0 {
-					// Abort the program.
-					log::debug!(
-						"Syscall tkill(pid={a1}, signal={}) = Ok(Exit({}))",
-						debug::Signal(a2 as u8),
-						a2 as u8
-					);
-					return Ok(Exit(a2 as u8));
-				}
-				let result = self.handle_tkill(a1, a2);
-				log::debug!("Syscall tkill(pid={a1}, signal={})", debug::Signal(a2 as u8));
+				let result = self.handle_tkill(a1 as i64 as i32, a2);
+				log::debug!(
+					"Syscall tkill(tid={}, signal={}) = {result:?}",
+					a1 as i64 as i32,
+					debug::Signal(a2 as u8)
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_TGKILL => {
+				let result = self.handle_tgkill(a1 as i64 as i32, a2 as i64 as i32, a3);
+				log::debug!(
+					"Syscall tgkill(tgid={}, tid={}, signal={}) = {result:?}",
+					a1 as i64 as i32,
+					a2 as i64 as i32,
+					debug::Signal(a3 as u8)
+				);
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
 			SYS_OPENAT => {
-				let result = self.handle_openat(a1, a2, a3);
+				let result = self.handle_openat(a1, a2, a3, a4);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_TRUNCATE => {
+				let result = self.handle_truncate(a1, a2);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_FTRUNCATE => {
+				let result = self.handle_ftruncate(a1 as i64 as i32, a2 as i64);
+				log::debug!("Syscall ftruncate(fd={a1}, length={}) = {result:?}", a2 as i64);
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
 			SYS_LSEEK => {
@@ -105,6 +298,40 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 				log::debug!("Syscall close(fd={a1}) = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_CLONE => {
+				let result = self.handle_clone(a1, a2, a3, a4, a5);
+				log::debug!(
+					"Syscall clone(flags={a1:#x}, stack={a2:#x}, parent_tid={a3:#x}, tls={a4:#x}, child_tid={a5:#x}) = {result:?}"
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_FUTEX => {
+				// The sixth argument is passed via T0 register.
+				let a6 = self.context.reg(Reg::T0);
+				let result = self.handle_futex(a1, a2, a3, a4, a5, a6);
+				log::debug!(
+					"Syscall futex(address={a1:#x}, op={a2}, {a3}, {a4:#x}, {a5:#x}, {a6}) = {result:?}"
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+				self.schedule_if_blocked();
+			},
+			SYS_GETTID => {
+				let result = self.handle_gettid();
+				log::debug!("Syscall gettid() = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_SCHED_YIELD => {
+				let result = Ok(());
+				log::debug!("Syscall sched_yield() = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+				self.poll_alarm();
+				match self.state.threads.next_runnable() {
+					Some(tid) if tid != self.state.threads.current => self.switch_to(tid),
+					_ if self.has_deliverable_signals() => {},
+					// Nobody else wants to run.
+					_ => return Ok(Yield),
+				}
+			},
 			SYS_SET_TID_ADDRESS => {
 				let result = self.handle_set_tid_address(a1);
 				log::debug!("Syscall set_tid_address(tid_ptr={a1:#x}) = {result:?}");
@@ -145,11 +372,70 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 				log::debug!("Syscall setgid({a1}) = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_SETRESUID => {
+				let result = self.handle_setresuid(a1 as u32, a2 as u32, a3 as u32);
+				log::debug!(
+					"Syscall setresuid({}, {}, {}) = {result:?}",
+					a1 as i32,
+					a2 as i32,
+					a3 as i32
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_GETRESUID => {
+				let result = self.handle_getresuid(a1, a2, a3);
+				log::debug!("Syscall getresuid({a1:#x}, {a2:#x}, {a3:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_SETRESGID => {
+				let result = self.handle_setresgid(a1 as u32, a2 as u32, a3 as u32);
+				log::debug!(
+					"Syscall setresgid({}, {}, {}) = {result:?}",
+					a1 as i32,
+					a2 as i32,
+					a3 as i32
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_GETRESGID => {
+				let result = self.handle_getresgid(a1, a2, a3);
+				log::debug!("Syscall getresgid({a1:#x}, {a2:#x}, {a3:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_GETPID => {
+				let result = self.handle_getpid();
+				log::debug!("Syscall getpid() = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_GETPPID => {
+				let result = self.handle_getppid();
+				log::debug!("Syscall getppid() = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_GETPGID => {
+				let result = self.handle_getpgid(a1 as i32);
+				log::debug!("Syscall getpgid({}) = {result:?}", a1 as i32);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_SETSID => {
+				let result = self.handle_setsid();
+				log::debug!("Syscall setsid() = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_UNAME => {
 				let result = self.handle_uname(a1);
 				log::debug!("Syscall uname({a1:#x}) = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_FSTAT => {
+				let result = self.handle_fstat(a1 as i64 as i32, a2);
+				log::debug!("Syscall fstat(fd={a1}, stat={a2:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_STATX => {
+				let result = self.handle_statx(a1, a2, a3, a4, a5);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_NEWFSTATAT => {
 				let result = self.handle_newfstatat(a1, a2, a3, a4);
 				self.context.set_reg(Reg::A0, result.into_ret());
@@ -159,6 +445,45 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 				log::debug!("Syscall clock_gettime({a1}, {a2:#x}) = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_CLOCK_GETRES => {
+				let result = self.handle_clock_getres(a1, a2);
+				log::debug!("Syscall clock_getres({a1}, {a2:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_GETTIMEOFDAY => {
+				let result = self.handle_gettimeofday(a1, a2);
+				log::debug!("Syscall gettimeofday({a1:#x}, {a2:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_NANOSLEEP => {
+				let result = self.handle_nanosleep(a1, a2);
+				log::debug!("Syscall nanosleep({a1:#x}, {a2:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+				self.schedule_if_blocked();
+			},
+			SYS_CLOCK_NANOSLEEP => {
+				let result = self.handle_clock_nanosleep(a1, a2, a3, a4);
+				log::debug!(
+					"Syscall clock_nanosleep({a1}, flags={a2:#x}, {a3:#x}, {a4:#x}) = {result:?}"
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+				self.schedule_if_blocked();
+			},
+			SYS_DUP => {
+				let result = self.handle_dup(a1 as i64 as i32);
+				log::debug!("Syscall dup(old_fd={a1}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_DUP3 => {
+				let result = self.handle_dup3(a1 as i64 as i32, a2 as i64 as i32, a3);
+				log::debug!("Syscall dup3(old_fd={a1}, new_fd={a2}, flags={a3:#o}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_PIPE2 => {
+				let result = self.handle_pipe2(a1, a2);
+				log::debug!("Syscall pipe2(fds={a1:#x}, flags={a2:#o}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_FCNTL => {
 				let result = self.handle_fcntl(a1 as i64 as i32, a2, a3);
 				log::debug!("Syscall fcntl(fd={a1}, op={a2}, {a3}) = {result:?}");
@@ -170,34 +495,162 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
 			SYS_FACCESSAT => {
+				let result = self.handle_faccessat(a1, a2, a3, 0);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_FACCESSAT2 => {
 				let result = self.handle_faccessat(a1, a2, a3, a4);
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_READLINKAT => {
+				let result = self.handle_readlinkat(a1, a2, a3, a4);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_SYMLINKAT => {
+				let result = self.handle_symlinkat(a1, a2, a3);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_LINKAT => {
+				let result = self.handle_linkat(a1, a2, a3, a4, a5);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_MKDIRAT => {
+				let result = self.handle_mkdirat(a1, a2, a3);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_UNLINKAT => {
+				let result = self.handle_unlinkat(a1, a2, a3);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_RENAMEAT2 => {
+				let result = self.handle_renameat2(a1, a2, a3, a4, a5);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_MOUNT => {
+				let result = self.handle_mount(a1, a2, a3, a4);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_UMOUNT2 => {
+				let result = self.handle_umount2(a1, a2);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_GETGROUPS => {
-				let result = self.handle_getgroups(a1, a2);
-				log::debug!("Syscall getgroups(size={a1}, list={a2:#x}) = {result:?}");
+				let result = self.handle_getgroups(a1 as i32, a2);
+				log::debug!("Syscall getgroups(size={}, list={a2:#x}) = {result:?}", a1 as i32);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_SETGROUPS => {
+				let result = self.handle_setgroups(a1, a2);
+				log::debug!("Syscall setgroups(size={a1}, list={a2:#x}) = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_UMASK => {
+				let result = self.handle_umask(a1 as u32);
+				log::debug!("Syscall umask(mask={:#o}) = {result:#o}", a1 as u32);
+				self.context.set_reg(Reg::A0, result.into());
+			},
 			SYS_SYNC => {
 				let result = Ok(());
 				log::debug!("Syscall sync() = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_GETRANDOM => {
+				let result = self.handle_getrandom(a1, a2, a3);
+				log::debug!(
+					"Syscall getrandom(buf={a1:#x}, buflen={a2}, flags={a3:#x}) = {result:?}"
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_GETCWD => {
 				let result = self.handle_getcwd(a1, a2);
 				log::debug!("Syscall getcwd(buf={a1:#x}, size={a2}) = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_CHDIR => {
+				let result = self.handle_chdir(a1);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_FCHDIR => {
+				let result = self.handle_fchdir(a1 as i64 as i32);
+				log::debug!("Syscall fchdir(fd={a1}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_PPOLL => {
 				let result = self.handle_ppoll(a1, a2, a3, a4);
 				log::debug!("Syscall ppoll(fds={a1:#x}, nfds={a2}, timeout={a3:#x}, sigmask={a4:#x}) = {result:?}");
 				self.context.set_reg(Reg::A0, result.into_ret());
+				self.schedule_if_blocked();
+			},
+			SYS_BRK => {
+				let result = self.handle_brk(a1);
+				log::debug!("Syscall brk({a1:#x}) = {result:#x}");
+				self.context.set_reg(Reg::A0, result);
+			},
+			SYS_MMAP => {
+				// The sixth argument is passed via T0 register.
+				let a6 = self.context.reg(Reg::T0);
+				let result = self.handle_mmap(a1, a2, a3, a4, a5 as i64 as i32, a6);
+				log::debug!(
+					"Syscall mmap(address={a1:#x}, length={a2}, prot={a3:#x}, flags={a4:#x}, fd={}, offset={a6}) = {result:x?}",
+					a5 as i64 as i32
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_MUNMAP => {
+				let result = self.handle_munmap(a1, a2);
+				log::debug!("Syscall munmap(address={a1:#x}, length={a2}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_MPROTECT => {
+				let result = self.handle_mprotect(a1, a2, a3);
+				log::debug!(
+					"Syscall mprotect(address={a1:#x}, length={a2}, prot={a3:#x}) = {result:?}"
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_MSYNC => {
+				let result = self.handle_msync(a1, a2, a3);
+				log::debug!(
+					"Syscall msync(address={a1:#x}, length={a2}, flags={a3:#x}) = {result:?}"
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
 			},
 			SYS_RT_SIGACTION => {
 				let result = self.handle_rt_sigaction(a1, a2, a3, a4);
 				log::debug!("Syscall rt_sigaction(signal={}, action={a2:#x}, old_action={a3:#x}, sigset_size={a4}) = {result:?}", debug::Signal(a1 as u8));
 				self.context.set_reg(Reg::A0, result.into_ret());
 			},
+			SYS_RT_SIGPENDING => {
+				let result = self.handle_rt_sigpending(a1, a2);
+				log::debug!("Syscall rt_sigpending(set={a1:#x}, sigset_size={a2}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_RT_SIGRETURN => {
+				let result = self.handle_rt_sigreturn();
+				log::debug!("Syscall rt_sigreturn() = {result:?}");
+				if result.is_err() {
+					// The signal frame is corrupted.
+					return Ok(Killed { signal: SIGSEGV, core_dumped: true });
+				}
+				// All registers including A0 are restored from the signal frame.
+			},
+			SYS_SIGALTSTACK => {
+				let result = self.handle_sigaltstack(a1, a2);
+				log::debug!("Syscall sigaltstack(ss={a1:#x}, old_ss={a2:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_GETITIMER => {
+				let result = self.handle_getitimer(a1, a2);
+				log::debug!("Syscall getitimer(which={a1}, value={a2:#x}) = {result:?}");
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
+			SYS_SETITIMER => {
+				let result = self.handle_setitimer(a1, a2, a3);
+				log::debug!(
+					"Syscall setitimer(which={a1}, value={a2:#x}, old_value={a3:#x}) = {result:?}"
+				);
+				self.context.set_reg(Reg::A0, result.into_ret());
+			},
 			SYS_RT_SIGPROCMASK => {
 				let result = self.handle_rt_sigprocmask(a1, a2, a3, a4);
 				log::debug!(
@@ -214,60 +667,326 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 				self.context.set_reg(Reg::A0, errno(ENOSYS));
 			},
 		}
-		Ok(Continue)
+		if let Some(outcome) = self.schedule_if_waiting(syscall) {
+			return Ok(outcome);
+		}
+		if let Some(reason) = self.blocked.take() {
+			// The syscall is restarted when the host resumes the program.
+			self.context.set_reg(Reg::A0, syscall);
+			log::debug!("Blocked: {reason:?}");
+			return Ok(Blocked(reason));
+		}
+		self.state.restart_deadline = None;
+		if let Ok(thread) = self.state.threads.current_mut() {
+			if core::mem::take(&mut thread.restart_syscall) {
+				// The thread switched to executes the syscall it was blocked in again. This
+				// recurses at most once per thread woken up by the previous syscall.
+				return self.handle_syscall();
+			}
+		}
+		Ok(self.handle_signals().unwrap_or(Continue))
+	}
+
+	/// Open the file.
+	///
+	/// `metadata` is the result of the file lookup if it was made during the path resolution.
+	fn handle_open(
+		&mut self,
+		path: CString,
+		metadata: Option<Result<Metadata, Error>>,
+		flags: u64,
+		mode: u32,
+	) -> Result<u32, Error> {
+		let metadata = match metadata {
+			Some(metadata) => metadata,
+			None => self.with_fresh_proc(|fs| fs.symlink_metadata(&path)),
+		};
+		let exclusive = flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL;
+		let create = flags & O_CREAT != 0 && metadata == Err(Error(ENOENT));
+		if create {
+			self.check_parent_writable(&path, EntryChange::Create)?;
+		}
+		// Existing files are checked against the credentials unless `O_EXCL` makes the call fail.
+		if let Some(metadata) = metadata.as_ref().ok().filter(|_| !exclusive) {
+			self.check_open_access(metadata, flags)?;
+		}
+		let device = metadata
+			.ok()
+			.filter(|metadata| metadata.mode & S_IFMT == S_IFCHR && !exclusive)
+			.map(|metadata| metadata.rdev);
+		let object = match device {
+			Some(TTY_DEVICE | URANDOM_DEVICE) if flags & O_DIRECTORY != 0 =>
+				return Err(Error(ENOTDIR)),
+			// The terminal is served by the environment.
+			Some(TTY_DEVICE) => FileObject::Tty,
+			Some(URANDOM_DEVICE) => FileObject::Urandom,
+			_ => {
+				let mode = mode & !self.state.umask;
+				// The kernel appends by itself so that `F_SETFL` can toggle `O_APPEND`.
+				let fs_flags = flags & !O_APPEND;
+				let fd = self.with_fresh_proc(|fs| fs.open(&path, fs_flags, mode))?;
+				if create {
+					self.set_creator(&path);
+				}
+				FileObject::File { fd, path }
+			},
+		};
+		// Creation flags only affect the call itself.
+		let file = OpenFile {
+			object,
+			flags: flags & !(O_CLOEXEC | O_CREAT | O_EXCL | O_NOCTTY | O_TRUNC),
+		};
+		self.state.fds.open(file, 0, flags & O_CLOEXEC != 0)
+	}
+
+	/// Check that the credentials allow opening the existing file with the specified flags.
+	fn check_open_access(&self, metadata: &Metadata, flags: u64) -> Result<(), Error> {
+		let access = match flags & O_ACCMODE {
+			O_RDONLY => R_OK,
+			O_WRONLY => W_OK,
+			_ => R_OK | W_OK,
+		};
+		let access = if flags & O_TRUNC != 0 { access | W_OK } else { access };
+		self.state.credentials.check_access(metadata, access, false)
+	}
+
+	/// Open the file the magic link refers to by duplicating its file descriptor.
+	fn open_fd_link(&mut self, fd: u32, flags: u64) -> Result<u32, Error> {
+		if flags & O_DIRECTORY != 0 && self.fd_metadata(fd)?.mode & S_IFMT != S_IFDIR {
+			return Err(Error(ENOTDIR));
+		}
+		self.state.fds.dup(fd, 0, flags & O_CLOEXEC != 0)
+	}
+
+	/// Resolve `path` relative to the directory referred to by `dirfd`.
+	///
+	/// Empty path refers to `dirfd` itself if `AT_EMPTY_PATH` flag is set. Symbolic links are
+	/// followed except for the last component if `AT_SYMLINK_NOFOLLOW` flag is set.
+	fn resolve_path(&mut self, dirfd: i32, path: &CStr, flags: u64) -> Result<CString, Error> {
+		match self.resolve_target(dirfd, path, flags)?.0 {
+			Target::Path(path) => Ok(path),
+			Target::Fd(fd) => self.state.fds.path(fd).cloned(),
+		}
+	}
+
+	/// Same as [`resolve_path`](Self::resolve_path) but resolves the magic link at the end of the
+	/// path to the file descriptor, e.g. `/proc/self/fd/0`.
+	///
+	/// Also returns the result of the last component lookup if it was made during the resolution.
+	fn resolve_target(
+		&mut self,
+		dirfd: i32,
+		path: &CStr,
+		flags: u64,
+	) -> Result<(Target, Option<Result<Metadata, Error>>), Error> {
+		if path.is_empty() {
+			if flags & AT_EMPTY_PATH == 0 {
+				return Err(Error(ENOENT));
+			}
+			if dirfd == AT_FDCWD {
+				return Ok((Target::Path(self.state.cwd.clone()), None));
+			}
+			let dirfd = dirfd.try_into().map_err(|_| Error(EBADF))?;
+			return match self.state.fds.path(dirfd) {
+				Ok(path) => Ok((Target::Path(path.clone()), None)),
+				Err(Error(ENOTDIR)) => Err(Error(EBADF)),
+				Err(e) => Err(e),
+			};
+		}
+		let base = if path.to_bytes().first() == Some(&b'/') {
+			c"/".into()
+		} else if dirfd == AT_FDCWD {
+			self.state.cwd.clone()
+		} else {
+			let dirfd = dirfd.try_into().map_err(|_| Error(EBADF))?;
+			let dir = self.state.fds.path(dirfd)?.clone();
+			if self.with_fresh_proc(|fs| fs.metadata(&dir))?.mode & S_IFMT != S_IFDIR {
+				return Err(Error(ENOTDIR));
+			}
+			dir
+		};
+		let follow = flags & AT_SYMLINK_NOFOLLOW == 0;
+		let has_proc = self.proc.is_some();
+		// The superuser can search any directory.
+		let searcher = Some(&self.state.credentials).filter(|cred| !cred.is_privileged()).cloned();
+		let mut last = None;
+		let target = self.with_fresh_proc(|fs| {
+			resolve_target_with(&base, path, follow, |path| {
+				if let Some(cred) = searcher.as_ref() {
+					check_search(fs, cred, path, last.as_ref())?;
+				}
+				let metadata = fs.symlink_metadata(path);
+				last = Some((CString::from(path), metadata.clone()));
+				let metadata = metadata?;
+				Ok(match metadata.mode & S_IFMT {
+					S_IFLNK => match ProcFileSystem::fd_link(&metadata).filter(|_| has_proc) {
+						Some(fd) => PathNode::FdLink { fd, target: fs.readlink(path)? },
+						None => PathNode::Symlink(fs.readlink(path)?),
+					},
+					S_IFDIR => PathNode::Dir,
+					_ => PathNode::Other,
+				})
+			})
+		})?;
+		let metadata = match (&target, last) {
+			(Target::Path(path), Some((last_path, metadata))) if *path == last_path =>
+				Some(metadata),
+			(Target::Path(path), last) => {
+				// The last component hasn't been looked up.
+				if let Some(cred) = searcher.as_ref() {
+					self.with_fresh_proc(|fs| check_search(fs, cred, path, last.as_ref()))?;
+				}
+				None
+			},
+			_ => None,
+		};
+		Ok((target, metadata))
 	}
 
-	fn handle_open(&mut self, path: &CStr, flags: u64) -> Result<u32, Error> {
-		if (flags & (O_WRONLY | O_RDWR)) != 0 {
-			return Err(Error(EACCES));
+	/// Check that the credentials allow changing the entry at the resolved path.
+	///
+	/// The parent directory has to be writable and searchable. Like on Linux, the entry lookup
+	/// errors take precedence over `EACCES`, see [`EntryChange`].
+	fn check_parent_writable(&mut self, path: &CStr, change: EntryChange) -> Result<(), Error> {
+		if self.state.credentials.is_privileged() {
+			return Ok(());
 		}
-		let file = self.context.open(path, flags)?;
-		let max_fd = match self.max_fd {
-			Some(ref mut max_fd) => max_fd,
-			ref mut p @ None => p.insert(self.state.fds.keys().copied().max().unwrap_or(0)),
+		let parent = to_cstring(parent_dir(path.to_bytes()));
+		let metadata = self.with_fresh_proc(|fs| fs.metadata(&parent))?;
+		let Err(e) = self.state.credentials.check_access(&metadata, W_OK | X_OK, false) else {
+			return Ok(());
 		};
-		*max_fd += 1;
-		let fd = MAX_RESERVED_FD + *max_fd;
-		self.state.fds.insert(fd, file);
-		Ok(fd)
+		match (change, self.with_fresh_proc(|fs| fs.symlink_metadata(path))) {
+			(EntryChange::Create, Ok(..)) => Err(Error(EEXIST)),
+			(EntryChange::Remove, Err(lookup_error)) => Err(lookup_error),
+			_ => Err(e),
+		}
+	}
+
+	/// Make the caller own the newly created file.
+	fn set_creator(&mut self, path: &CStr) {
+		let cred = &self.state.credentials;
+		if let Err(e) = self.context.set_owner(path, cred.euid, cred.egid) {
+			log::debug!("Failed to set the owner of {path:?}: {e:?}");
+		}
 	}
 
-	fn handle_openat(&mut self, dirfd: u64, path: u64, flags: u64) -> Result<u32, Error> {
+	fn handle_openat(
+		&mut self,
+		dirfd: u64,
+		path: u64,
+		flags: u64,
+		mode: u64,
+	) -> Result<u32, Error> {
 		let path = self.context.read_cstring(path, PATH_MAX)?;
 		let dirfd = dirfd as i64 as i32;
-		let result = self.do_handle_openat(dirfd, &path, flags);
+		let mode = mode as u32;
+		let result = self.do_handle_openat(dirfd, &path, flags, mode);
 		log::debug!(
-			"Syscall openat(dirfd={}, path={path:?}, flags={flags:#o}) = {result:?}",
+			"Syscall openat(dirfd={}, path={path:?}, flags={flags:#o}, mode={mode:#o}) = {result:?}",
 			debug::DirFd(dirfd)
 		);
 		result
 	}
 
 	#[inline]
-	fn do_handle_openat(&mut self, dirfd: i32, path: &CStr, flags: u64) -> Result<u32, Error> {
-		if dirfd != AT_FDCWD {
-			return Err(Error(ENOSYS));
+	fn do_handle_openat(
+		&mut self,
+		dirfd: i32,
+		path: &CStr,
+		flags: u64,
+		mode: u32,
+	) -> Result<u32, Error> {
+		// Exclusive creation fails on dangling symbolic links instead of creating the target.
+		let nofollow = flags & O_NOFOLLOW != 0 || flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL;
+		let at_flags = if nofollow { AT_SYMLINK_NOFOLLOW } else { 0 };
+		match self.resolve_target(dirfd, path, at_flags)? {
+			(Target::Path(path), metadata) => self.handle_open(path, metadata, flags, mode),
+			(Target::Fd(fd), _) => self.open_fd_link(fd, flags),
 		}
-		self.handle_open(path, flags)
+	}
+
+	fn handle_truncate(&mut self, path_address: u64, length: u64) -> Result<(), Error> {
+		let path = self.context.read_cstring(path_address, PATH_MAX)?;
+		let length = length as i64;
+		let result = self.do_handle_truncate(&path, length);
+		log::debug!("Syscall truncate(path={path:?}, length={length}) = {result:?}");
+		result
+	}
+
+	#[inline]
+	fn do_handle_truncate(&mut self, path: &CStr, length: i64) -> Result<(), Error> {
+		let length = length.try_into().map_err(|_| Error(EINVAL))?;
+		let (path, metadata) = match self.resolve_target(AT_FDCWD, path, 0)? {
+			(Target::Path(path), Some(metadata)) => (path, metadata?),
+			(Target::Path(path), None) => {
+				let metadata = self.with_fresh_proc(|fs| fs.metadata(&path))?;
+				(path, metadata)
+			},
+			(Target::Fd(fd), _) => (self.state.fds.path(fd)?.clone(), self.fd_metadata(fd)?),
+		};
+		self.check_open_access(&metadata, O_WRONLY)?;
+		let mut file = self.with_fresh_proc(|fs| fs.open(&path, O_WRONLY, 0))?;
+		self.context.truncate(&mut file, length)
+	}
+
+	fn handle_ftruncate(&mut self, fd: i32, length: i64) -> Result<(), Error> {
+		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
+		let fd = self.state.fds.file_mut(fd, Error(EINVAL))?;
+		let length = length.try_into().map_err(|_| Error(EINVAL))?;
+		self.context.truncate(fd, length)
 	}
 
 	fn handle_close(&mut self, fd: i32) -> Result<(), Error> {
 		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
-		if self.state.fds.remove(&fd).is_some() {
-			return Ok(());
+		let pipe = self.state.fds.get(fd).and_then(|file| file.object.pipe_id());
+		self.state.fds.close(fd)?;
+		if let Some(pipe) = pipe {
+			// The other end might be waiting for end of file or `EPIPE`.
+			self.state.threads.wake_pipe(pipe);
 		}
-		Err(Error(EBADF))
+		Ok(())
 	}
 
 	fn handle_read(&mut self, fd: i32, address: u64, length: u64) -> Result<u64, Error> {
 		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
-		let fd = self.state.fds.get_mut(&fd).ok_or(Error(EBADF))?;
+		if !self.state.fds.contains(fd) {
+			return Err(Error(EBADF));
+		}
 		if address.checked_add(length).is_none() || u32::try_from(address + length).is_err() {
 			return Err(Error(EFAULT));
 		}
 		let buf_len = length.try_into().map_err(|_| Error(EFAULT))?;
 		let mut buf = vec![0_u8; buf_len];
-		let num_bytes_read = self.context.read(fd, &mut buf)?;
+		let file = self.state.fds.get_mut(fd).ok_or(Error(EBADF))?;
+		if file.flags & O_ACCMODE == O_WRONLY {
+			return Err(Error(EBADF));
+		}
+		let num_bytes_read = match file.object {
+			FileObject::File { ref mut fd, .. } => self.context.read(fd, &mut buf)?,
+			FileObject::Urandom => {
+				self.state.random.fill(&mut buf);
+				buf.len()
+			},
+			FileObject::Stdin | FileObject::Tty => match self.context.read_from_stdin(&mut buf) {
+				Err(Error(EAGAIN)) => {
+					self.blocked = Some(WaitReason::Input);
+					return Err(Error(EAGAIN));
+				},
+				result => result?,
+			},
+			FileObject::PipeReader(id) => {
+				let pipe = self.state.fds.pipes.get_mut(&id).ok_or(Error(EBADF))?;
+				if pipe.buf.is_empty() && pipe.writers != 0 && !buf.is_empty() {
+					return self.wait_for_pipe(fd, id);
+				}
+				let n = pipe.read(&mut buf);
+				self.state.threads.wake_pipe(id);
+				n
+			},
+			FileObject::Stdout | FileObject::Stderr | FileObject::PipeWriter(..) =>
+				return Err(Error(EBADF)),
+		};
 		buf.resize(num_bytes_read, 0_u8);
 		self.context.write_memory(address, &buf[..])?;
 		Ok(num_bytes_read as u64)
@@ -282,8 +1001,19 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 		for n in 0..iovcnt {
 			let address = self.context.read_u64(iov.wrapping_add(n * 16))?;
 			let length = self.context.read_u64(iov.wrapping_add(n * 16).wrapping_add(8))?;
-			self.handle_read(fd, address, length)?;
-			total_length += length;
+			let num_bytes_read = match self.handle_read(fd, address, length) {
+				// Return what has been read so far instead of blocking.
+				Err(_) if total_length != 0 => {
+					self.cancel_wait();
+					break;
+				},
+				result => result?,
+			};
+			total_length += num_bytes_read;
+			if num_bytes_read != length {
+				// Short read.
+				break;
+			}
 		}
 
 		Ok(total_length)
@@ -291,7 +1021,7 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 
 	fn handle_write(&mut self, fd: i32, address: u64, length: u64) -> Result<u64, Error> {
 		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
-		if fd != FILENO_STDOUT && fd != FILENO_STDERR && !self.state.fds.contains_key(&fd) {
+		if !self.state.fds.contains(fd) {
 			return Err(Error(EBADF));
 		}
 
@@ -301,10 +1031,38 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 
 		let data = self.context.read_memory(address, length)?;
 
-		match fd {
-			FILENO_STDOUT => self.context.write_to_stdout(&data[..]),
-			FILENO_STDERR => self.context.write_to_stderr(&data[..]),
-			_ => Err(Error(ENOSYS)),
+		let file = self.state.fds.get_mut(fd).ok_or(Error(EBADF))?;
+		if file.flags & O_ACCMODE == O_RDONLY {
+			return Err(Error(EBADF));
+		}
+		let append = file.flags & O_APPEND != 0;
+		match file.object {
+			FileObject::File { ref mut fd, .. } => {
+				if append {
+					self.context.seek(fd, SeekFrom::End(0))?;
+				}
+				let num_bytes_written = self.context.write(fd, &data[..])?;
+				Ok(num_bytes_written as u64)
+			},
+			// The writes are discarded like on Linux, the entropy pool is not affected.
+			FileObject::Urandom => Ok(data.len() as u64),
+			FileObject::Stdout | FileObject::Tty => self.context.write_to_stdout(&data[..]),
+			FileObject::Stderr => self.context.write_to_stderr(&data[..]),
+			FileObject::PipeWriter(id) => {
+				let pipe = self.state.fds.pipes.get_mut(&id).ok_or(Error(EBADF))?;
+				if pipe.readers == 0 {
+					self.send_signal(Some(self.state.threads.current), SIGPIPE);
+					return Err(Error(EPIPE));
+				}
+				match pipe.write(&data[..]) {
+					0 if !data.is_empty() => self.wait_for_pipe(fd, id),
+					n => {
+						self.state.threads.wake_pipe(id);
+						Ok(n as u64)
+					},
+				}
+			},
+			FileObject::Stdin | FileObject::PipeReader(..) => Err(Error(EBADF)),
 		}
 	}
 
@@ -317,8 +1075,19 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 		for n in 0..iovcnt {
 			let address = self.context.read_u64(iov.wrapping_add(n * 16))?;
 			let length = self.context.read_u64(iov.wrapping_add(n * 16).wrapping_add(8))?;
-			self.handle_write(fd, address, length)?;
-			total_length += length;
+			let num_bytes_written = match self.handle_write(fd, address, length) {
+				// Return what has been written so far instead of blocking.
+				Err(_) if total_length != 0 => {
+					self.cancel_wait();
+					break;
+				},
+				result => result?,
+			};
+			total_length += num_bytes_written;
+			if num_bytes_written != length {
+				// Short write.
+				break;
+			}
 		}
 
 		Ok(total_length)
@@ -326,7 +1095,7 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 
 	fn handle_lseek(&mut self, fd: i32, offset: i64, whence: u64) -> Result<u64, Error> {
 		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
-		let fd = self.state.fds.get_mut(&fd).ok_or(Error(EBADF))?;
+		let fd = self.state.fds.file_mut(fd, Error(ESPIPE))?;
 		let from = match whence {
 			SEEK_SET => SeekFrom::Start(offset as u64),
 			SEEK_CUR => SeekFrom::Current(offset),
@@ -338,73 +1107,545 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 		self.context.seek(fd, from)
 	}
 
-	fn handle_set_tid_address(&mut self, thread_id_address: u64) -> Result<u64, Error> {
-		if thread_id_address != 0 {
-			self.context.write_u32(thread_id_address, THREAD_ID)?;
-		}
-		Ok(THREAD_ID.into())
+	fn handle_set_tid_address(&mut self, thread_id_address: u64) -> Result<u32, Error> {
+		self.state.threads.current_mut()?.clear_child_tid = thread_id_address;
+		Ok(self.state.threads.current)
 	}
 
-	fn handle_ioctl(&mut self, _fd: u64, op: u64, arg0: u64) -> Result<(), Error> {
-		if op == TIOCGWINSZ {
-			// NOTE This is a stub to make Musl's `__stdout_write` use line buffering.
-			let address = arg0;
-			let vt100_size = WinSize { col: 80, row: 25, xpixel: 0, ypixel: 0 };
-			self.context.write_memory(address, as_u8_slice(&vt100_size))?;
-			return Ok(());
-		}
-		Err(Error(ENOSYS))
+	fn handle_gettid(&mut self) -> Result<u32, Error> {
+		Ok(self.state.threads.current)
 	}
 
-	fn handle_fcntl(&mut self, fd: i32, op: u64, arg0: u64) -> Result<(), Error> {
-		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
-		if !self.state.fds.contains_key(&fd) {
-			return Err(Error(EBADF));
+	fn handle_clone(
+		&mut self,
+		flags: u64,
+		stack: u64,
+		parent_tid_address: u64,
+		tls: u64,
+		child_tid_address: u64,
+	) -> Result<u32, Error> {
+		if flags & CLONE_THREAD != 0 && flags & CLONE_SIGHAND == 0 {
+			// Threads share signal handlers.
+			return Err(Error(EINVAL));
 		}
-		match (op, arg0) {
-			(F_SETFD, FD_CLOEXEC) => Ok(()),
+		if flags & (CLONE_VM | CLONE_THREAD) != CLONE_VM | CLONE_THREAD {
+			// Only threads are supported.
+			return Err(Error(ENOSYS));
+		}
+		let pc = self.context.next_program_counter().ok_or(Error(ENOSYS))?;
+		let mut thread = Thread::new();
+		for reg in Reg::ALL {
+			thread.regs[reg as usize] = self.context.reg(reg);
+		}
+		// The child returns zero from the syscall.
+		thread.regs[Reg::A0 as usize] = 0;
+		if stack != 0 {
+			thread.regs[Reg::SP as usize] = stack;
+		}
+		thread.pc = pc;
+		thread.blocked_signals = self.state.threads.current()?.blocked_signals;
+		let tp = self.context.thread_pointer();
+		thread.tls = if flags & CLONE_SETTLS != 0 {
+			// The thread pointer can't be applied.
+			tp.ok_or(Error(EINVAL))?;
+			tls
+		} else {
+			tp.unwrap_or(0)
+		};
+		if flags & CLONE_CHILD_CLEARTID != 0 {
+			thread.clear_child_tid = child_tid_address;
+		}
+		let tid = self.state.threads.next_tid;
+		if flags & CLONE_PARENT_SETTID != 0 {
+			self.context.write_u32(parent_tid_address, tid)?;
+		}
+		if flags & CLONE_CHILD_SETTID != 0 {
+			self.context.write_u32(child_tid_address, tid)?;
+		}
+		Ok(self.state.threads.spawn(thread))
+	}
+
+	/// Terminate the calling thread.
+	///
+	/// Returns the outcome if there is no other thread to switch to.
+	fn handle_exit(&mut self, status: u8) -> Option<SyscallOutcome> {
+		let threads = &mut self.state.threads;
+		let tid = threads.current;
+		if threads.threads.keys().all(|other| *other == tid) {
+			return Some(Exit(status));
+		}
+		// The thread is reaped right away. It is already gone if the syscall is restarted.
+		if let Some(thread) = threads.threads.remove(&tid) {
+			let address = thread.clear_child_tid;
+			// Errors are ignored like Linux does.
+			if address != 0 && self.context.write_u32(address, 0).is_ok() {
+				self.state.threads.wake(address, FUTEX_BITSET_MATCH_ANY, 1);
+			}
+		}
+		let reason = match self.schedule() {
+			Ok(true) => return None,
+			Ok(false) => {
+				log::debug!("Deadlock: all remaining threads are blocked forever");
+				return Some(Deadlock);
+			},
+			Err(_) => self.blocked.take().unwrap_or(WaitReason::Futex),
+		};
+		// The syscall is restarted when the host resumes the program.
+		self.context.set_reg(Reg::A0, SYS_EXIT);
+		log::debug!("Blocked: {reason:?}");
+		Some(Blocked(reason))
+	}
+
+	fn handle_futex(
+		&mut self,
+		address: u64,
+		op: u64,
+		val: u64,
+		timeout_address: u64,
+		address2: u64,
+		val3: u64,
+	) -> Result<u64, Error> {
+		if !address.is_multiple_of(4) {
+			return Err(Error(EINVAL));
+		}
+		let val = val as u32;
+		let val3 = val3 as u32;
+		let clock =
+			if op & FUTEX_CLOCK_REALTIME != 0 { ClockId::Realtime } else { ClockId::Monotonic };
+		match op & !(FUTEX_PRIVATE | FUTEX_CLOCK_REALTIME) {
+			FUTEX_WAIT => {
+				let deadline = if timeout_address != 0 {
+					let timeout = self.read_timespec(timeout_address)?;
+					Some(self.deadline_after(timeout)?)
+				} else {
+					None
+				};
+				self.futex_wait(address, val, FUTEX_BITSET_MATCH_ANY, deadline)
+			},
+			FUTEX_WAIT_BITSET => {
+				if val3 == 0 {
+					return Err(Error(EINVAL));
+				}
+				let deadline = if timeout_address != 0 {
+					// The timeout is absolute. Deadlines are tracked in monotonic time.
+					let timeout = self.read_timespec(timeout_address)?;
+					let remaining = timeout.saturating_sub(self.now(clock)?);
+					Some(self.deadline_after(remaining)?)
+				} else {
+					None
+				};
+				self.futex_wait(address, val, val3, deadline)
+			},
+			FUTEX_WAKE => {
+				let woken = self.state.threads.wake(address, FUTEX_BITSET_MATCH_ANY, val as usize);
+				Ok(woken.len() as u64)
+			},
+			FUTEX_WAKE_BITSET => {
+				if val3 == 0 {
+					return Err(Error(EINVAL));
+				}
+				Ok(self.state.threads.wake(address, val3, val as usize).len() as u64)
+			},
+			cmd @ (FUTEX_REQUEUE | FUTEX_CMP_REQUEUE) => {
+				if !address2.is_multiple_of(4) {
+					return Err(Error(EINVAL));
+				}
+				if cmd == FUTEX_CMP_REQUEUE && self.context.read_u32(address)? != val3 {
+					return Err(Error(EAGAIN));
+				}
+				// The fourth argument is the number of threads to requeue.
+				let requeue_count = timeout_address as u32 as usize;
+				let woken = self.state.threads.wake(address, FUTEX_BITSET_MATCH_ANY, val as usize);
+				let requeued = self.state.threads.requeue(address, address2, requeue_count);
+				Ok((woken.len() + requeued) as u64)
+			},
+			_ => Err(Error(ENOSYS)),
+		}
+	}
+
+	fn futex_wait(
+		&mut self,
+		address: u64,
+		val: u32,
+		bitset: u32,
+		deadline: Option<Duration>,
+	) -> Result<u64, Error> {
+		if self.context.read_u32(address)? != val {
+			return Err(Error(EAGAIN));
+		}
+		// The thread returns zero when woken up, otherwise the return value is overwritten.
+		self.state.threads.wait(address, bitset, deadline)?;
+		Ok(0)
+	}
+
+	/// Switch to another thread if the running one is blocked.
+	///
+	/// If all threads are blocked, the syscall is restarted when the host resumes the program.
+	fn schedule_if_blocked(&mut self) {
+		if self
+			.state
+			.threads
+			.current()
+			.is_ok_and(|thread| thread.state == ThreadState::Runnable)
+		{
+			return;
+		}
+		match self.schedule() {
+			Ok(true) => return,
+			Ok(false) => self.blocked = Some(WaitReason::Futex),
+			Err(_) if self.blocked.is_some() => {},
+			Err(e) => self.context.set_reg(Reg::A0, e.code()),
+		}
+		if let Ok(thread) = self.state.threads.current_mut() {
+			thread.state = ThreadState::Runnable;
+		}
+	}
+
+	/// Block the running thread until the pipe is ready unless the file is non-blocking.
+	///
+	/// Always fails with `EAGAIN`. The error isn't returned to the blocked thread, see
+	/// [`schedule_if_waiting`](Self::schedule_if_waiting).
+	fn wait_for_pipe<T>(&mut self, fd: u32, pipe: u64) -> Result<T, Error> {
+		if self.state.fds.get(fd).is_some_and(|file| file.flags & O_NONBLOCK == 0) {
+			self.state.threads.current_mut()?.state = ThreadState::PipeWait { pipe };
+		}
+		Err(Error(EAGAIN))
+	}
+
+	/// Undo [`wait_for_pipe`](Self::wait_for_pipe) and any other blocking of the running thread.
+	fn cancel_wait(&mut self) {
+		self.blocked = None;
+		if let Ok(thread) = self.state.threads.current_mut() {
+			if matches!(thread.state, ThreadState::PipeWait { .. }) {
+				thread.state = ThreadState::Runnable;
+			}
+		}
+	}
+
+	/// Switch to another thread if the running one waits for a pipe.
+	///
+	/// The waiting thread executes the syscall again once woken up. Returns the outcome if no
+	/// thread can run.
+	fn schedule_if_waiting(&mut self, syscall: u64) -> Option<SyscallOutcome> {
+		let thread = self.state.threads.current_mut().ok()?;
+		if !matches!(thread.state, ThreadState::PipeWait { .. }) {
+			return None;
+		}
+		thread.restart_syscall = true;
+		self.context.set_reg(Reg::A0, syscall);
+		match self.schedule() {
+			Ok(true) => return None,
+			Ok(false) => {
+				log::debug!("Deadlock: all threads are blocked forever");
+				return Some(Deadlock);
+			},
+			Err(_) if self.blocked.is_some() => {},
+			Err(e) => self.context.set_reg(Reg::A0, e.code()),
+		}
+		// The thread keeps running: either the host restarts the syscall or it fails.
+		if let Ok(thread) = self.state.threads.current_mut() {
+			thread.state = ThreadState::Runnable;
+			thread.restart_syscall = false;
+		}
+		None
+	}
+
+	/// Switch to the next runnable thread.
+	///
+	/// The threads which deadlines have passed become runnable. If no thread is runnable, sleeps
+	/// until the earliest deadline. Returns `false` if all threads are blocked forever.
+	fn schedule(&mut self) -> Result<bool, Error> {
+		loop {
+			if self.state.threads.next_deadline().is_some() || self.state.signals.alarm.is_some() {
+				let now = self.now(ClockId::Monotonic)?;
+				while let Some((tid, deadline)) = self.state.threads.next_deadline() {
+					if deadline > now {
+						break;
+					}
+					self.wake_at_deadline(tid);
+				}
+				self.check_alarm(now);
+			}
+			if let Some(tid) = self.state.threads.next_runnable() {
+				self.switch_to(tid);
+				return Ok(true);
+			}
+			// The alarm can't wake up anybody if the signal is already pending or ignored.
+			let alarm = self.state.signals.alarm.filter(|_| {
+				self.state.signals.pending & sig_bit(SIGALRM) == 0 &&
+					!self.state.signals.is_ignored(SIGALRM)
+			});
+			let deadline = self
+				.state
+				.threads
+				.next_deadline()
+				.map(|(_, deadline)| deadline)
+				.into_iter()
+				.chain(alarm.map(|timer| timer.deadline))
+				.min();
+			let Some(deadline) = deadline else {
+				return Ok(false);
+			};
+			let now = self.now(ClockId::Monotonic)?;
+			self.wait_until(now, deadline)?;
+		}
+	}
+
+	/// Make the thread which deadline has passed runnable.
+	///
+	/// The sleep succeeds while the futex wait times out.
+	fn wake_at_deadline(&mut self, tid: u32) {
+		let Some(thread) = self.state.threads.threads.get_mut(&tid) else {
+			return;
+		};
+		match thread.state {
+			// The return value has been set when the thread started sleeping.
+			ThreadState::Sleep { .. } => thread.state = ThreadState::Runnable,
+			_ => self.wake_with_error(tid, ETIMEDOUT),
+		}
+	}
+
+	/// Make the blocked thread runnable and return the specified error from the syscall.
+	fn wake_with_error(&mut self, tid: u32, error: u64) {
+		let threads = &mut self.state.threads;
+		let Some(thread) = threads.threads.get_mut(&tid) else {
+			return;
+		};
+		thread.state = ThreadState::Runnable;
+		thread.restart_syscall = false;
+		if tid == threads.current {
+			self.context.set_reg(Reg::A0, errno(error));
+		} else {
+			thread.regs[Reg::A0 as usize] = errno(error);
+		}
+	}
+
+	/// Save the registers of the running thread and restore the registers of the specified one.
+	fn switch_to(&mut self, tid: u32) {
+		let threads = &mut self.state.threads;
+		if tid == threads.current && threads.threads.contains_key(&tid) {
+			return;
+		}
+		// The running thread might have exited already.
+		if let Some(thread) = threads.threads.get_mut(&threads.current) {
+			for reg in Reg::ALL {
+				thread.regs[reg as usize] = self.context.reg(reg);
+			}
+			thread.pc = self.context.next_program_counter().unwrap_or(0);
+			thread.tls = self.context.thread_pointer().unwrap_or(0);
+		}
+		let thread = &threads.threads[&tid];
+		for reg in Reg::ALL {
+			self.context.set_reg(reg, thread.regs[reg as usize]);
+		}
+		self.context.set_next_program_counter(thread.pc);
+		if self.context.thread_pointer().is_some() {
+			self.context.set_thread_pointer(thread.tls);
+		}
+		threads.current = tid;
+		log::debug!("Switched to thread {tid}");
+	}
+
+	fn handle_ioctl(&mut self, fd: u64, op: u64, arg0: u64) -> Result<(), Error> {
+		let fd = u32::try_from(fd).map_err(|_| Error(EBADF))?;
+		match self.state.fds.get(fd).map(|file| &file.object) {
+			Some(object) if object.is_terminal() => {},
+			Some(..) => return Err(Error(ENOTTY)),
+			None => return Err(Error(EBADF)),
+		}
+		if op == TIOCGWINSZ {
+			// NOTE This is a stub to make Musl's `__stdout_write` use line buffering.
+			let address = arg0;
+			let vt100_size = WinSize { col: 80, row: 25, xpixel: 0, ypixel: 0 };
+			self.context.write_memory(address, as_u8_slice(&vt100_size))?;
+			return Ok(());
+		}
+		Err(Error(ENOSYS))
+	}
+
+	fn handle_fcntl(&mut self, fd: i32, op: u64, arg0: u64) -> Result<u64, Error> {
+		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
+		let entry = self.state.fds.fds.get_mut(&fd).ok_or(Error(EBADF))?;
+		match op {
+			F_DUPFD | F_DUPFD_CLOEXEC => {
+				let min_fd = u32::try_from(arg0).map_err(|_| Error(EINVAL))?;
+				if min_fd >= MAX_FDS {
+					return Err(Error(EINVAL));
+				}
+				let cloexec = op == F_DUPFD_CLOEXEC;
+				self.state.fds.dup(fd, min_fd, cloexec).map(u64::from)
+			},
+			F_GETFD => Ok(if entry.cloexec { FD_CLOEXEC } else { 0 }),
+			F_SETFD => {
+				entry.cloexec = arg0 & FD_CLOEXEC != 0;
+				Ok(0)
+			},
+			F_GETFL => Ok(self.state.fds.get(fd).map_or(0, |file| file.flags)),
+			F_SETFL => {
+				let file = self.state.fds.get_mut(fd).ok_or(Error(EBADF))?;
+				// The access mode and the creation flags are ignored.
+				file.flags = (file.flags & !SETFL_FLAGS) | (arg0 & SETFL_FLAGS);
+				Ok(0)
+			},
 			_ => Err(Error(ENOSYS)),
 		}
 	}
 
+	fn handle_dup(&mut self, old_fd: i32) -> Result<u32, Error> {
+		let old_fd = old_fd.try_into().map_err(|_| Error(EBADF))?;
+		self.state.fds.dup(old_fd, 0, false)
+	}
+
+	fn handle_dup3(&mut self, old_fd: i32, new_fd: i32, flags: u64) -> Result<u32, Error> {
+		let old_fd = old_fd.try_into().map_err(|_| Error(EBADF))?;
+		let new_fd = new_fd.try_into().map_err(|_| Error(EBADF))?;
+		if flags & !O_CLOEXEC != 0 || old_fd == new_fd {
+			return Err(Error(EINVAL));
+		}
+		let pipe = self.state.fds.get(new_fd).and_then(|file| file.object.pipe_id());
+		self.state.fds.dup_to(old_fd, new_fd, flags & O_CLOEXEC != 0)?;
+		if let Some(pipe) = pipe {
+			self.state.threads.wake_pipe(pipe);
+		}
+		Ok(new_fd)
+	}
+
+	fn handle_pipe2(&mut self, fds_address: u64, flags: u64) -> Result<(), Error> {
+		if flags & !(O_CLOEXEC | O_NONBLOCK) != 0 {
+			return Err(Error(EINVAL));
+		}
+		let (fd0, fd1) = self.state.fds.pipe(flags & O_NONBLOCK, flags & O_CLOEXEC != 0)?;
+		let fds = [fd0 as i32, fd1 as i32];
+		if let Err(e) = self.context.write_memory(fds_address, as_u8_slice(&fds)) {
+			self.state.fds.close(fd0)?;
+			self.state.fds.close(fd1)?;
+			return Err(e.into());
+		}
+		Ok(())
+	}
+
 	fn handle_getuid(&mut self) -> Result<u32, Error> {
-		Ok(self.uid)
+		Ok(self.state.credentials.uid)
 	}
 
 	fn handle_geteuid(&mut self) -> Result<u32, Error> {
-		Ok(self.uid)
+		Ok(self.state.credentials.euid)
 	}
 
 	fn handle_getgid(&mut self) -> Result<u32, Error> {
-		Ok(self.gid)
+		Ok(self.state.credentials.gid)
 	}
 
 	fn handle_getegid(&mut self) -> Result<u32, Error> {
-		Ok(self.gid)
+		Ok(self.state.credentials.egid)
 	}
 
 	fn handle_setuid(&mut self, uid: u64) -> Result<(), Error> {
-		if uid == u64::from(self.uid) {
-			Ok(())
-		} else {
-			Err(Error(ENOSYS))
-		}
+		self.state.credentials.set_uid(uid as u32)
 	}
 
 	fn handle_setgid(&mut self, gid: u64) -> Result<(), Error> {
-		if gid == u64::from(self.gid) {
-			Ok(())
-		} else {
-			Err(Error(ENOSYS))
+		self.state.credentials.set_gid(gid as u32)
+	}
+
+	fn handle_setresuid(&mut self, uid: u32, euid: u32, suid: u32) -> Result<(), Error> {
+		self.state.credentials.set_res_uid(uid, euid, suid)
+	}
+
+	fn handle_getresuid(&mut self, uid: u64, euid: u64, suid: u64) -> Result<(), Error> {
+		let cred = &self.state.credentials;
+		let ids = [cred.uid, cred.euid, cred.suid];
+		for (address, id) in [uid, euid, suid].into_iter().zip(ids) {
+			self.context.write_u32(address, id)?;
+		}
+		Ok(())
+	}
+
+	fn handle_setresgid(&mut self, gid: u32, egid: u32, sgid: u32) -> Result<(), Error> {
+		self.state.credentials.set_res_gid(gid, egid, sgid)
+	}
+
+	fn handle_getresgid(&mut self, gid: u64, egid: u64, sgid: u64) -> Result<(), Error> {
+		let cred = &self.state.credentials;
+		let ids = [cred.gid, cred.egid, cred.sgid];
+		for (address, id) in [gid, egid, sgid].into_iter().zip(ids) {
+			self.context.write_u32(address, id)?;
 		}
+		Ok(())
 	}
 
-	fn handle_getgroups(&mut self, size: u64, address: u64) -> Result<u64, Error> {
+	fn handle_getgroups(&mut self, size: i32, address: u64) -> Result<u64, Error> {
+		let groups = &self.state.credentials.groups;
+		let n = groups.len() as u64;
+		let size = u64::try_from(size).map_err(|_| Error(EINVAL))?;
 		if size == 0 {
-			return Ok(1);
+			return Ok(n);
+		}
+		if size < n {
+			return Err(Error(EINVAL));
+		}
+		for (i, group) in groups.iter().enumerate() {
+			self.context.write_u32(address.wrapping_add(i as u64 * 4), *group)?;
+		}
+		Ok(n)
+	}
+
+	fn handle_setgroups(&mut self, size: u64, address: u64) -> Result<(), Error> {
+		if size > NGROUPS_MAX {
+			return Err(Error(EINVAL));
+		}
+		let mut groups = Vec::with_capacity(size as usize);
+		for i in 0..size {
+			groups.push(self.context.read_u32(address.wrapping_add(i * 4))?);
+		}
+		self.state.credentials.set_groups(groups)
+	}
+
+	fn handle_umask(&mut self, mask: u32) -> u32 {
+		core::mem::replace(&mut self.state.umask, mask & 0o777)
+	}
+
+	fn handle_getpid(&mut self) -> Result<u32, Error> {
+		Ok(self.state.credentials.pid)
+	}
+
+	fn handle_getppid(&mut self) -> Result<u32, Error> {
+		Ok(self.state.credentials.ppid)
+	}
+
+	fn handle_getpgid(&mut self, pid: i32) -> Result<u32, Error> {
+		let cred = &self.state.credentials;
+		if pid != 0 && pid as u32 != cred.pid {
+			return Err(Error(ESRCH));
+		}
+		Ok(cred.pgid)
+	}
+
+	fn handle_setsid(&mut self) -> Result<u32, Error> {
+		self.state.credentials.set_sid()
+	}
+
+	fn handle_getrandom(&mut self, address: u64, length: u64, flags: u64) -> Result<u64, Error> {
+		if flags & !(GRND_NONBLOCK | GRND_RANDOM | GRND_INSECURE) != 0 ||
+			flags & (GRND_RANDOM | GRND_INSECURE) == GRND_RANDOM | GRND_INSECURE
+		{
+			return Err(Error(EINVAL));
+		}
+		// The generator is always seeded, hence the flags don't matter.
+		let length = length.min(GETRANDOM_MAX);
+		if address.checked_add(length).is_none() {
+			return Err(Error(EFAULT));
+		}
+		let mut buf = [0_u8; PAGE_SIZE as usize];
+		let mut offset = 0;
+		while offset < length {
+			let n = (length - offset).min(PAGE_SIZE);
+			let chunk = &mut buf[..n as usize];
+			self.state.random.fill(chunk);
+			self.context.write_memory(address + offset, chunk)?;
+			offset += n;
 		}
-		self.context.write_u32(address, self.gid)?;
-		Ok(1)
+		Ok(length)
 	}
 
 	fn handle_uname(&mut self, address: u64) -> Result<(), Error> {
@@ -454,34 +1695,250 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 		dirfd: i32,
 		path: &CStr,
 		stat_address: u64,
-		_flags: u64,
+		flags: u64,
 	) -> Result<(), Error> {
-		if dirfd != AT_FDCWD {
-			return Err(Error(ENOSYS));
+		if flags & !(AT_SYMLINK_NOFOLLOW | AT_NO_AUTOMOUNT | AT_EMPTY_PATH) != 0 {
+			return Err(Error(EINVAL));
 		}
-		let meta = self.context.metadata(path).map_err(|_| Error(ENOENT))?;
+		let meta = self.stat_metadata(dirfd, path, flags)?;
 		let stat = Stat {
-			st_dev: 0,
+			st_dev: meta.device,
 			st_ino: meta.id,
-			st_rdev: 0,
-			st_uid: 0,
-			st_gid: 0,
-			st_nlink: 1,
+			st_rdev: meta.rdev,
+			st_uid: meta.uid,
+			st_gid: meta.gid,
+			st_nlink: meta.nlink,
 			st_mode: meta.mode,
 			st_blksize: meta.block_size as BlksizeT,
 			st_blocks: meta.size.div_ceil(512) as BlkcntT,
 			st_size: meta.size as OffT,
+			st_atim: to_timespec(meta.atime),
+			st_mtim: to_timespec(meta.mtime),
+			st_ctim: to_timespec(meta.ctime),
 			..Default::default()
 		};
 		self.context.write_memory(stat_address, as_u8_slice(&stat))?;
 		Ok(())
 	}
 
-	fn handle_clock_gettime(&mut self, _clock_id: u64, address: u64) -> Result<(), Error> {
+	fn handle_fstat(&mut self, fd: i32, stat_address: u64) -> Result<(), Error> {
+		self.do_handle_newfstatat(fd, c"", stat_address, AT_EMPTY_PATH)
+	}
+
+	fn handle_statx(
+		&mut self,
+		dirfd: u64,
+		path_address: u64,
+		flags: u64,
+		mask: u64,
+		statx_address: u64,
+	) -> Result<(), Error> {
+		let dirfd = dirfd as i64 as i32;
+		let path = self.context.read_cstring(path_address, PATH_MAX)?;
+		let result = self.do_handle_statx(dirfd, &path, flags, statx_address);
+		log::debug!(
+			"Syscall statx(dirfd={}, path={path:?}, flags={flags:#x}, mask={mask:#x}, statx={statx_address:#x}) = {result:?}",
+			debug::DirFd(dirfd)
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_statx(
+		&mut self,
+		dirfd: i32,
+		path: &CStr,
+		flags: u64,
+		statx_address: u64,
+	) -> Result<(), Error> {
+		if flags & !(AT_SYMLINK_NOFOLLOW | AT_NO_AUTOMOUNT | AT_EMPTY_PATH | AT_STATX_SYNC_TYPE) !=
+			0
+		{
+			return Err(Error(EINVAL));
+		}
+		let meta = self.stat_metadata(dirfd, path, flags)?;
+		let to_statx_timestamp = |time: Duration| StatxTimestamp {
+			tv_sec: time.as_secs() as i64,
+			tv_nsec: time.subsec_nanos(),
+			__pad: 0,
+		};
+		// All basic fields are filled regardless of the requested mask, and birth time is unknown.
+		let statx = Statx {
+			stx_mask: STATX_BASIC_STATS,
+			stx_blksize: meta.block_size as u32,
+			stx_nlink: meta.nlink,
+			stx_uid: meta.uid,
+			stx_gid: meta.gid,
+			stx_mode: meta.mode as u16,
+			stx_ino: meta.id,
+			stx_size: meta.size,
+			stx_blocks: meta.size.div_ceil(512),
+			stx_atime: to_statx_timestamp(meta.atime),
+			stx_ctime: to_statx_timestamp(meta.ctime),
+			stx_mtime: to_statx_timestamp(meta.mtime),
+			stx_rdev_major: major(meta.rdev),
+			stx_rdev_minor: minor(meta.rdev),
+			stx_dev_major: major(meta.device),
+			stx_dev_minor: minor(meta.device),
+			..Default::default()
+		};
+		self.context.write_memory(statx_address, as_u8_slice(&statx))?;
+		Ok(())
+	}
+
+	/// Get the metadata of the file referred to by `stat`-family syscall arguments.
+	///
+	/// Empty path refers to `dirfd` itself if `AT_EMPTY_PATH` flag is set. The symbolic link is
+	/// not followed if `AT_SYMLINK_NOFOLLOW` flag is set.
+	fn stat_metadata(&mut self, dirfd: i32, path: &CStr, flags: u64) -> Result<Metadata, Error> {
+		if path.is_empty() && flags & AT_EMPTY_PATH != 0 && dirfd != AT_FDCWD {
+			let fd = dirfd.try_into().map_err(|_| Error(EBADF))?;
+			return self.fd_metadata(fd);
+		}
+		match self.resolve_target(dirfd, path, flags)? {
+			(Target::Fd(fd), _) => self.fd_metadata(fd),
+			(_, Some(metadata)) => metadata,
+			(Target::Path(path), None) if flags & AT_SYMLINK_NOFOLLOW != 0 =>
+				self.with_fresh_proc(|fs| fs.symlink_metadata(&path)),
+			(Target::Path(path), None) => self.with_fresh_proc(|fs| fs.metadata(&path)),
+		}
+	}
+
+	fn handle_clock_gettime(&mut self, clock_id: u64, address: u64) -> Result<(), Error> {
+		let clock = ClockId::from_raw(clock_id).ok_or(Error(EINVAL))?;
 		if address == 0 {
 			return Err(Error(EFAULT));
 		}
-		let ts = Timespec { tv_sec: 0, tv_nsec: 0 };
+		let now = self.now(clock)?;
+		self.write_timespec(address, now)
+	}
+
+	fn handle_clock_getres(&mut self, clock_id: u64, address: u64) -> Result<(), Error> {
+		let clock = ClockId::from_raw(clock_id).ok_or(Error(EINVAL))?;
+		let resolution = self.resolution(clock)?;
+		if address == 0 {
+			return Ok(());
+		}
+		self.write_timespec(address, resolution)
+	}
+
+	fn handle_gettimeofday(&mut self, tv_address: u64, tz_address: u64) -> Result<(), Error> {
+		if tv_address != 0 {
+			let now = self.now(ClockId::Realtime)?;
+			self.context.write_memory(tv_address, as_u8_slice(&to_timeval(now)))?;
+		}
+		if tz_address != 0 {
+			// Always UTC.
+			self.context.write_memory(tz_address, as_u8_slice(&Timezone::default()))?;
+		}
+		Ok(())
+	}
+
+	fn handle_nanosleep(&mut self, req_address: u64, rem_address: u64) -> Result<(), Error> {
+		let duration = self.read_timespec(req_address)?;
+		let deadline = self.deadline_after(duration)?;
+		self.sleep_until(deadline, rem_address)
+	}
+
+	fn handle_clock_nanosleep(
+		&mut self,
+		clock_id: u64,
+		flags: u64,
+		req_address: u64,
+		rem_address: u64,
+	) -> Result<(), Error> {
+		let clock = match ClockId::from_raw(clock_id) {
+			Some(ClockId::ThreadCpuTime) | None => return Err(Error(EINVAL)),
+			Some(clock) => clock,
+		};
+		let mut duration = self.read_timespec(req_address)?;
+		let absolute = flags & TIMER_ABSTIME != 0;
+		if absolute {
+			let now = self.now(clock)?;
+			duration = duration.saturating_sub(now);
+		}
+		let deadline = self.deadline_after(duration)?;
+		// The remaining time is not reported for the absolute deadline.
+		self.sleep_until(deadline, if absolute { 0 } else { rem_address })
+	}
+
+	fn now(&mut self, clock: ClockId) -> Result<Duration, Error> {
+		let now = match self.virtual_time {
+			Some(ref virtual_time) =>
+				virtual_time.now(clock, self.context.gas_consumed(), self.state.slept),
+			None => self.context.now(clock)?,
+		};
+		if clock == ClockId::Monotonic {
+			self.last_monotonic = now;
+		}
+		Ok(now)
+	}
+
+	fn resolution(&mut self, clock: ClockId) -> Result<Duration, Error> {
+		match self.virtual_time {
+			Some(ref virtual_time) => Ok(virtual_time.resolution()),
+			None => self.context.resolution(clock),
+		}
+	}
+
+	/// Get the monotonic clock deadline of the timeout that starts now.
+	///
+	/// The restarted syscall gets the same deadline it had before being suspended.
+	fn deadline_after(&mut self, timeout: Duration) -> Result<Duration, Error> {
+		let deadline = match self.state.restart_deadline {
+			Some(deadline) => deadline,
+			None => self.now(ClockId::Monotonic)?.saturating_add(timeout),
+		};
+		self.state.restart_deadline = Some(deadline);
+		Ok(deadline)
+	}
+
+	/// Block until the monotonic clock reaches the deadline.
+	///
+	/// If the clock refuses to block, the program is suspended with [`WaitReason::Timer`].
+	fn wait_until(&mut self, now: Duration, deadline: Duration) -> Result<(), Error> {
+		let duration = deadline.saturating_sub(now);
+		match self.virtual_time {
+			Some(..) => {
+				self.state.slept = self.state.slept.saturating_add(duration);
+				Ok(())
+			},
+			None => match self.context.sleep(duration) {
+				Err(Error(EAGAIN)) => {
+					self.blocked = Some(WaitReason::Timer { deadline });
+					Err(Error(EAGAIN))
+				},
+				result => result,
+			},
+		}
+	}
+
+	/// Block the running thread until the monotonic clock reaches the deadline.
+	///
+	/// The other threads run in the meantime, see
+	/// [`schedule_if_blocked`](Self::schedule_if_blocked). The thread returns zero when woken up
+	/// at the deadline. If a signal interrupts the sleep, the thread fails with `EINTR` and the
+	/// remaining time is written to `remaining_address` unless it is zero.
+	fn sleep_until(&mut self, deadline: Duration, remaining_address: u64) -> Result<(), Error> {
+		if self.now(ClockId::Monotonic)? >= deadline {
+			return Ok(());
+		}
+		self.state.threads.sleep(deadline, remaining_address)
+	}
+
+	fn read_timespec(&mut self, address: u64) -> Result<Duration, Error> {
+		let tv_sec = self.context.read_u64(address)? as i64;
+		let tv_nsec = self.context.read_u64(address.wrapping_add(8))? as i64;
+		let (Ok(secs), Ok(nanos @ 0..NANOS_PER_SEC)) =
+			(u64::try_from(tv_sec), u32::try_from(tv_nsec))
+		else {
+			return Err(Error(EINVAL));
+		};
+		Ok(Duration::new(secs, nanos))
+	}
+
+	fn write_timespec(&mut self, address: u64, duration: Duration) -> Result<(), Error> {
+		let ts = to_timespec(duration);
 		self.context.write_memory(address, as_u8_slice(&ts))?;
 		Ok(())
 	}
@@ -493,19 +1950,12 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 		buf_size: u64,
 	) -> Result<u64, Error> {
 		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
-		let fd = self.state.fds.get_mut(&fd).ok_or(Error(EBADF))?;
-		let buf_size = buf_size as usize;
+		let fd = self.state.fds.file_mut(fd, Error(ENOTDIR))?;
+		let buf_size = buf_size.try_into().map_err(|_| Error(EINVAL))?;
 		let mut buf = vec![0_u8; buf_size];
-		let mut offset = 0;
-		while offset != buf_size {
-			let n = self.context.read_dir(fd, &mut buf[offset..])?;
-			if n == 0 {
-				break;
-			}
-			offset += n;
-		}
-		self.context.write_memory(buf_address, &buf[..offset])?;
-		Ok(offset as u64)
+		let n = self.context.read_dir(fd, &mut buf[..])?;
+		self.context.write_memory(buf_address, &buf[..n])?;
+		Ok(n as u64)
 	}
 
 	fn handle_faccessat(
@@ -532,63 +1982,1089 @@ impl<C: Machine + Environment + FileSystem> Kernel<C> {
 		dirfd: i32,
 		path: &CStr,
 		mode: u32,
-		_flags: u64,
+		flags: u64,
 	) -> Result<(), Error> {
-		if dirfd != AT_FDCWD {
-			return Err(Error(ENOSYS));
-		}
-		let meta = self.context.metadata(path)?;
-		if meta.mode & mode == mode {
-			return Ok(())
-		}
-		Err(Error(EACCES))
-	}
-
-	fn handle_getcwd(&mut self, buf_address: u64, buf_size: u64) -> Result<u64, Error> {
-		let cwd = c"/".to_bytes_with_nul();
-		if buf_size < cwd.len() as u64 {
-			return Err(Error(ERANGE));
+		if flags & !(AT_EACCESS | AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+			return Err(Error(EINVAL));
 		}
-		self.context.write_memory(buf_address, cwd)?;
-		Ok(cwd.len() as u64)
-	}
-
-	fn handle_tkill(&mut self, pid: u64, _sig: u64) -> Result<(), Error> {
-		if pid != 0 {
-			return Err(Error(ENOSYS));
+		if mode & !(R_OK | W_OK | X_OK) != 0 {
+			return Err(Error(EINVAL));
 		}
-		Ok(())
+		let meta = match self.resolve_target(dirfd, path, flags)? {
+			(Target::Fd(fd), _) => self.fd_metadata(fd)?,
+			(_, Some(metadata)) => metadata?,
+			(Target::Path(path), None) => self.with_fresh_proc(|fs| fs.metadata(&path))?,
+		};
+		self.state.credentials.check_access(&meta, mode, flags & AT_EACCESS == 0)
+	}
+
+	fn handle_readlinkat(
+		&mut self,
+		dirfd: u64,
+		path_address: u64,
+		buf_address: u64,
+		buf_size: u64,
+	) -> Result<u64, Error> {
+		let path = self.context.read_cstring(path_address, PATH_MAX)?;
+		let dirfd = dirfd as i64 as i32;
+		let result = self.do_handle_readlinkat(dirfd, &path, buf_address, buf_size);
+		log::debug!(
+			"Syscall readlinkat(dirfd={}, path={path:?}, buf={buf_address:#x}, size={buf_size}) = {result:?}",
+			debug::DirFd(dirfd)
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_readlinkat(
+		&mut self,
+		dirfd: i32,
+		path: &CStr,
+		buf_address: u64,
+		buf_size: u64,
+	) -> Result<u64, Error> {
+		if buf_size as i64 <= 0 {
+			return Err(Error(EINVAL));
+		}
+		let path = self.resolve_path(dirfd, path, AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH)?;
+		let target = self.with_fresh_proc(|fs| fs.readlink(&path))?;
+		// The target is silently truncated and is not NUL-terminated.
+		let target = target.to_bytes();
+		let n = target.len().min(buf_size as usize);
+		self.context.write_memory(buf_address, &target[..n])?;
+		Ok(n as u64)
+	}
+
+	fn handle_symlinkat(
+		&mut self,
+		target_address: u64,
+		dirfd: u64,
+		path_address: u64,
+	) -> Result<(), Error> {
+		let target = self.context.read_cstring(target_address, PATH_MAX)?;
+		let path = self.context.read_cstring(path_address, PATH_MAX)?;
+		let dirfd = dirfd as i64 as i32;
+		let result = self.do_handle_symlinkat(&target, dirfd, &path);
+		log::debug!(
+			"Syscall symlinkat(target={target:?}, dirfd={}, path={path:?}) = {result:?}",
+			debug::DirFd(dirfd)
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_symlinkat(&mut self, target: &CStr, dirfd: i32, path: &CStr) -> Result<(), Error> {
+		if target.is_empty() {
+			return Err(Error(ENOENT));
+		}
+		let path = self.resolve_path(dirfd, path, AT_SYMLINK_NOFOLLOW)?;
+		self.check_parent_writable(&path, EntryChange::Create)?;
+		self.context.symlink(target, &path)?;
+		self.set_creator(&path);
+		Ok(())
+	}
+
+	fn handle_linkat(
+		&mut self,
+		old_dirfd: u64,
+		old_path_address: u64,
+		new_dirfd: u64,
+		new_path_address: u64,
+		flags: u64,
+	) -> Result<(), Error> {
+		let old_path = self.context.read_cstring(old_path_address, PATH_MAX)?;
+		let new_path = self.context.read_cstring(new_path_address, PATH_MAX)?;
+		let old_dirfd = old_dirfd as i64 as i32;
+		let new_dirfd = new_dirfd as i64 as i32;
+		let result = self.do_handle_linkat(old_dirfd, &old_path, new_dirfd, &new_path, flags);
+		log::debug!(
+			"Syscall linkat(olddirfd={}, oldpath={old_path:?}, newdirfd={}, newpath={new_path:?}, flags={flags:#x}) = {result:?}",
+			debug::DirFd(old_dirfd),
+			debug::DirFd(new_dirfd)
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_linkat(
+		&mut self,
+		old_dirfd: i32,
+		old_path: &CStr,
+		new_dirfd: i32,
+		new_path: &CStr,
+		flags: u64,
+	) -> Result<(), Error> {
+		if flags & !(AT_SYMLINK_FOLLOW | AT_EMPTY_PATH) != 0 {
+			return Err(Error(EINVAL));
+		}
+		// Unlike the other syscalls, `linkat` doesn't follow symbolic links by default.
+		let old_flags = match flags & AT_SYMLINK_FOLLOW {
+			0 => flags | AT_SYMLINK_NOFOLLOW,
+			_ => flags,
+		};
+		let old_path = self.resolve_path(old_dirfd, old_path, old_flags)?;
+		let new_path = self.resolve_path(new_dirfd, new_path, AT_SYMLINK_NOFOLLOW)?;
+		self.check_parent_writable(&new_path, EntryChange::Create)?;
+		self.context.link(&old_path, &new_path)
+	}
+
+	fn handle_mkdirat(&mut self, dirfd: u64, path_address: u64, mode: u64) -> Result<(), Error> {
+		let path = self.context.read_cstring(path_address, PATH_MAX)?;
+		let dirfd = dirfd as i64 as i32;
+		let mode = mode as u32;
+		let result = self.do_handle_mkdirat(dirfd, &path, mode);
+		log::debug!(
+			"Syscall mkdirat(dirfd={}, path={path:?}, mode={mode:#o}) = {result:?}",
+			debug::DirFd(dirfd)
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_mkdirat(&mut self, dirfd: i32, path: &CStr, mode: u32) -> Result<(), Error> {
+		// The existing symbolic link is not followed even if it is dangling.
+		let path = self.resolve_path(dirfd, path, AT_SYMLINK_NOFOLLOW)?;
+		self.check_parent_writable(&path, EntryChange::Create)?;
+		self.context.mkdir(&path, mode & 0o7777 & !self.state.umask)?;
+		self.set_creator(&path);
+		Ok(())
+	}
+
+	fn handle_unlinkat(&mut self, dirfd: u64, path_address: u64, flags: u64) -> Result<(), Error> {
+		let path = self.context.read_cstring(path_address, PATH_MAX)?;
+		let dirfd = dirfd as i64 as i32;
+		let result = self.do_handle_unlinkat(dirfd, &path, flags);
+		log::debug!(
+			"Syscall unlinkat(dirfd={}, path={path:?}, flags={flags:#x}) = {result:?}",
+			debug::DirFd(dirfd)
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_unlinkat(&mut self, dirfd: i32, path: &CStr, flags: u64) -> Result<(), Error> {
+		if flags & !AT_REMOVEDIR != 0 {
+			return Err(Error(EINVAL));
+		}
+		let path = self.resolve_path(dirfd, path, AT_SYMLINK_NOFOLLOW)?;
+		self.check_parent_writable(&path, EntryChange::Remove)?;
+		if flags & AT_REMOVEDIR != 0 {
+			return self.context.rmdir(&path);
+		}
+		self.context.unlink(&path)
+	}
+
+	fn handle_renameat2(
+		&mut self,
+		old_dirfd: u64,
+		old_path_address: u64,
+		new_dirfd: u64,
+		new_path_address: u64,
+		flags: u64,
+	) -> Result<(), Error> {
+		let old_path = self.context.read_cstring(old_path_address, PATH_MAX)?;
+		let new_path = self.context.read_cstring(new_path_address, PATH_MAX)?;
+		let old_dirfd = old_dirfd as i64 as i32;
+		let new_dirfd = new_dirfd as i64 as i32;
+		let result = self.do_handle_renameat2(old_dirfd, &old_path, new_dirfd, &new_path, flags);
+		log::debug!(
+			"Syscall renameat2(olddirfd={}, oldpath={old_path:?}, newdirfd={}, newpath={new_path:?}, flags={flags:#x}) = {result:?}",
+			debug::DirFd(old_dirfd),
+			debug::DirFd(new_dirfd)
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_renameat2(
+		&mut self,
+		old_dirfd: i32,
+		old_path: &CStr,
+		new_dirfd: i32,
+		new_path: &CStr,
+		flags: u64,
+	) -> Result<(), Error> {
+		if flags & !(RENAME_NOREPLACE | RENAME_EXCHANGE | RENAME_WHITEOUT) != 0 {
+			return Err(Error(EINVAL));
+		}
+		// Atomic exchange and whiteouts are not supported.
+		if flags & (RENAME_EXCHANGE | RENAME_WHITEOUT) != 0 {
+			return Err(Error(EINVAL));
+		}
+		let old_path = self.resolve_path(old_dirfd, old_path, AT_SYMLINK_NOFOLLOW)?;
+		let new_path = self.resolve_path(new_dirfd, new_path, AT_SYMLINK_NOFOLLOW)?;
+		self.check_parent_writable(&old_path, EntryChange::Remove)?;
+		self.check_parent_writable(&new_path, EntryChange::Replace)?;
+		if flags & RENAME_NOREPLACE != 0 {
+			match self.context.symlink_metadata(&new_path) {
+				Ok(..) => return Err(Error(EEXIST)),
+				Err(Error(ENOENT)) => {},
+				Err(e) => return Err(e),
+			}
+		}
+		self.context.rename(&old_path, &new_path)
+	}
+
+	fn handle_mount(
+		&mut self,
+		source_address: u64,
+		target_address: u64,
+		fs_type_address: u64,
+		flags: u64,
+	) -> Result<(), Error> {
+		// Source and file system type are ignored by some operations and may be NULL.
+		let source = match source_address {
+			0 => CString::default(),
+			address => self.context.read_cstring(address, PATH_MAX)?,
+		};
+		let target = self.context.read_cstring(target_address, PATH_MAX)?;
+		let fs_type = match fs_type_address {
+			0 => CString::default(),
+			address => self.context.read_cstring(address, PATH_MAX)?,
+		};
+		let result = self.do_handle_mount(&source, &target, &fs_type, flags);
+		log::debug!(
+			"Syscall mount(source={source:?}, target={target:?}, fstype={fs_type:?}, flags={flags:#x}) = {result:?}"
+		);
+		result
+	}
+
+	#[inline]
+	fn do_handle_mount(
+		&mut self,
+		source: &CStr,
+		target: &CStr,
+		fs_type: &CStr,
+		flags: u64,
+	) -> Result<(), Error> {
+		if !self.state.credentials.is_privileged() {
+			return Err(Error(EPERM));
+		}
+		let target = self.resolve_path(AT_FDCWD, target, 0)?;
+		self.context.guest_mount(source, &target, fs_type, flags)
+	}
+
+	fn handle_umount2(&mut self, target_address: u64, flags: u64) -> Result<(), Error> {
+		let target = self.context.read_cstring(target_address, PATH_MAX)?;
+		let result = self.do_handle_umount2(&target, flags);
+		log::debug!("Syscall umount2(target={target:?}, flags={flags:#x}) = {result:?}");
+		result
+	}
+
+	#[inline]
+	fn do_handle_umount2(&mut self, target: &CStr, flags: u64) -> Result<(), Error> {
+		if flags & !(MNT_FORCE | MNT_DETACH | MNT_EXPIRE | UMOUNT_NOFOLLOW) != 0 {
+			return Err(Error(EINVAL));
+		}
+		if !self.state.credentials.is_privileged() {
+			return Err(Error(EPERM));
+		}
+		let at_flags = if flags & UMOUNT_NOFOLLOW != 0 { AT_SYMLINK_NOFOLLOW } else { 0 };
+		let target = self.resolve_path(AT_FDCWD, target, at_flags)?;
+		self.context.guest_unmount(&target, flags)
+	}
+
+	/// Run the file system operation with up-to-date `/proc`.
+	///
+	/// The kernel state is rendered only if the operation looked into outdated `/proc`, then the
+	/// operation is repeated. This is safe because `/proc` is read-only.
+	fn with_fresh_proc<T>(
+		&mut self,
+		mut f: impl FnMut(&mut C) -> Result<T, Error>,
+	) -> Result<T, Error> {
+		let result = f(&mut self.context);
+		if !self.proc.as_ref().is_some_and(ProcFileSystem::take_missed) {
+			return result;
+		}
+		self.update_proc();
+		f(&mut self.context)
+	}
+
+	/// Render the kernel state into `/proc` file system.
+	fn update_proc(&mut self) {
+		if self.proc.is_none() {
+			return;
+		}
+		let fds: BTreeMap<u32, CString> = self
+			.state
+			.fds
+			.fds
+			.keys()
+			.filter_map(|fd| {
+				let target = match self.state.fds.get(*fd)?.object {
+					FileObject::File { ref path, .. } => path.clone(),
+					FileObject::Stdin | FileObject::Stdout | FileObject::Stderr =>
+						c"/dev/pts/0".into(),
+					FileObject::Tty => c"/dev/tty".into(),
+					FileObject::Urandom => c"/dev/urandom".into(),
+					FileObject::PipeReader(id) | FileObject::PipeWriter(id) =>
+						CString::new(format!("pipe:[{id}]")).ok()?,
+				};
+				Some((*fd, target))
+			})
+			.collect();
+		let (thread_pending_signals, blocked_signals) = match self.state.threads.current() {
+			Ok(thread) => (thread.pending_signals, thread.blocked_signals),
+			Err(_) => (0, 0),
+		};
+		let mut ignored_signals = 0;
+		let mut caught_signals = 0;
+		for (i, action) in self.state.signals.actions.iter().enumerate() {
+			match action.handler {
+				SIG_DFL => {},
+				SIG_IGN => ignored_signals |= 1 << i,
+				_ => caught_signals |= 1 << i,
+			}
+		}
+		let info = ProcInfo {
+			credentials: self.state.credentials.clone(),
+			argv: self.state.argv.clone(),
+			envp: self.state.envp.clone(),
+			exe: self.state.exe.clone(),
+			fds,
+			mappings: self.state.memory.mappings.clone(),
+			threads: self.state.threads.threads.len() as u32,
+			thread_pending_signals,
+			pending_signals: self.state.signals.pending,
+			blocked_signals,
+			ignored_signals,
+			caught_signals,
+			memory: self.context.memory_stats(),
+			// Path lookups never read the clock.
+			uptime: self.last_monotonic,
+		};
+		if let Some(ref proc) = self.proc {
+			proc.set_info(info);
+		}
+	}
+
+	/// Get the metadata of the object the file descriptor refers to.
+	fn fd_metadata(&mut self, fd: u32) -> Result<Metadata, Error> {
+		let file = self.state.fds.get(fd).ok_or(Error(EBADF))?;
+		match file.object {
+			FileObject::File { ref path, .. } => {
+				let path = path.clone();
+				self.with_fresh_proc(|fs| fs.metadata(&path))
+			},
+			// The streams look like the same terminal device owned by the user the program runs as,
+			// see `DevFileSystem::with_tty_owner`.
+			FileObject::Stdin | FileObject::Stdout | FileObject::Stderr => Ok(Metadata {
+				id: TTY_ID,
+				mode: S_IFCHR | 0o620,
+				block_size: TTY_BLOCK_SIZE,
+				nlink: 1,
+				uid: self.state.credentials.uid,
+				gid: self.state.credentials.gid,
+				rdev: PTS_DEVICE,
+				..Default::default()
+			}),
+			FileObject::Tty => Ok(Metadata {
+				id: CONTROLLING_TTY_ID,
+				mode: S_IFCHR | 0o666,
+				block_size: TTY_BLOCK_SIZE,
+				nlink: 1,
+				rdev: TTY_DEVICE,
+				..Default::default()
+			}),
+			FileObject::Urandom => Ok(Metadata {
+				id: URANDOM_ID,
+				mode: S_IFCHR | 0o666,
+				block_size: PAGE_SIZE,
+				nlink: 1,
+				rdev: URANDOM_DEVICE,
+				..Default::default()
+			}),
+			FileObject::PipeReader(id) | FileObject::PipeWriter(id) => Ok(Metadata {
+				id,
+				mode: S_IFIFO | 0o600,
+				block_size: PAGE_SIZE,
+				nlink: 1,
+				uid: self.state.credentials.euid,
+				gid: self.state.credentials.egid,
+				..Default::default()
+			}),
+		}
+	}
+
+	fn handle_getcwd(&mut self, buf_address: u64, buf_size: u64) -> Result<u64, Error> {
+		let cwd = self.state.cwd.to_bytes_with_nul();
+		if buf_size < cwd.len() as u64 {
+			return Err(Error(ERANGE));
+		}
+		self.context.write_memory(buf_address, cwd)?;
+		Ok(cwd.len() as u64)
+	}
+
+	fn handle_chdir(&mut self, path_address: u64) -> Result<(), Error> {
+		let path = self.context.read_cstring(path_address, PATH_MAX)?;
+		let result = self.do_handle_chdir(&path);
+		log::debug!("Syscall chdir(path={path:?}) = {result:?}");
+		result
+	}
+
+	#[inline]
+	fn do_handle_chdir(&mut self, path: &CStr) -> Result<(), Error> {
+		let path = self.resolve_path(AT_FDCWD, path, 0)?;
+		self.set_cwd(path)
+	}
+
+	fn handle_fchdir(&mut self, fd: i32) -> Result<(), Error> {
+		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
+		let path = self.state.fds.path(fd)?.clone();
+		self.set_cwd(path)
+	}
+
+	fn set_cwd(&mut self, path: CString) -> Result<(), Error> {
+		let meta = self.with_fresh_proc(|fs| fs.metadata(&path))?;
+		if meta.mode & S_IFMT != S_IFDIR {
+			return Err(Error(ENOTDIR));
+		}
+		self.state.credentials.check_access(&meta, X_OK, false)?;
+		self.state.cwd = path;
+		Ok(())
 	}
 
 	fn handle_ppoll(
 		&mut self,
 		_fds: u64,
 		_nfds: u64,
-		_timeout: u64,
+		timeout_address: u64,
 		_sigmask: u64,
 	) -> Result<u64, Error> {
+		// NOTE None of the file descriptors ever become ready, hence we wait until timeout.
+		if timeout_address != 0 {
+			let timeout = self.read_timespec(timeout_address)?;
+			let deadline = self.deadline_after(timeout)?;
+			self.sleep_until(deadline, 0)?;
+		}
+		// The thread returns zero when the sleep ends, otherwise the return value is overwritten.
 		Ok(0)
 	}
 
+	fn handle_brk(&mut self, address: u64) -> u64 {
+		self.state.memory.set_brk(&mut self.context, address)
+	}
+
+	fn handle_mmap(
+		&mut self,
+		address: u64,
+		length: u64,
+		prot: u64,
+		flags: u64,
+		fd: i32,
+		offset: u64,
+	) -> Result<u64, Error> {
+		if !matches!(flags & MAP_TYPE, MAP_SHARED | MAP_PRIVATE | MAP_SHARED_VALIDATE) {
+			return Err(Error(EINVAL));
+		}
+		if flags & MAP_ANONYMOUS != 0 {
+			return self.state.memory.map(&mut self.context, address, length, prot, flags, None);
+		}
+		if !offset.is_multiple_of(PAGE_SIZE) {
+			return Err(Error(EINVAL));
+		}
+		if flags & MAP_TYPE != MAP_PRIVATE && prot & PROT_WRITE != 0 {
+			// Shared writable file mappings are not supported.
+			return Err(Error(ENODEV));
+		}
+		let fd = fd.try_into().map_err(|_| Error(EBADF))?;
+		let path = match self.state.fds.get(fd) {
+			// The file is read into the memory even if the mapping isn't readable.
+			Some(OpenFile { flags, .. }) if flags & O_ACCMODE == O_WRONLY =>
+				return Err(Error(EACCES)),
+			Some(OpenFile { object: FileObject::File { path, .. }, .. }) => path.clone(),
+			Some(..) => return Err(Error(ENODEV)),
+			None => return Err(Error(EBADF)),
+		};
+		if offset.checked_add(length).is_none() {
+			return Err(Error(EOVERFLOW));
+		}
+		let file = MappedFile { path, offset };
+		let address =
+			self.state
+				.memory
+				.map(&mut self.context, address, length, prot, flags, Some(file))?;
+		if let Err(e) = self.copy_file_to_memory(fd, offset, address, length) {
+			self.state.memory.unmap(&mut self.context, address, length)?;
+			return Err(e);
+		}
+		Ok(address)
+	}
+
+	/// Copy up to `length` bytes of the file starting at `offset` to the memory page by page.
+	///
+	/// The rest of the memory past the end of the file is left intact.
+	fn copy_file_to_memory(
+		&mut self,
+		fd: u32,
+		offset: u64,
+		address: u64,
+		length: u64,
+	) -> Result<(), Error> {
+		let Some(OpenFile { object: FileObject::File { fd: file, .. }, .. }) =
+			self.state.fds.get_mut(fd)
+		else {
+			return Err(Error(EBADF));
+		};
+		let mut buf = [0_u8; PAGE_SIZE as usize];
+		let mut copied = 0;
+		while copied != length {
+			let chunk_len = (length - copied).min(PAGE_SIZE) as usize;
+			let n = self.context.read_at(file, &mut buf[..chunk_len], offset + copied)?;
+			if n == 0 {
+				break;
+			}
+			self.context.write_memory(address + copied, &buf[..n])?;
+			copied += n as u64;
+		}
+		Ok(())
+	}
+
+	fn handle_munmap(&mut self, address: u64, length: u64) -> Result<(), Error> {
+		self.state.memory.unmap(&mut self.context, address, length)
+	}
+
+	fn handle_mprotect(&mut self, address: u64, length: u64, prot: u64) -> Result<(), Error> {
+		self.state.memory.protect(address, length, prot)
+	}
+
+	fn handle_msync(&mut self, address: u64, length: u64, flags: u64) -> Result<(), Error> {
+		self.state.memory.sync(address, length, flags)
+	}
+
 	fn handle_rt_sigaction(
 		&mut self,
-		_signum: u64,
-		_action: u64,
-		_old_action: u64,
-		_sigset_size: u64,
+		signal: u64,
+		action_address: u64,
+		old_action_address: u64,
+		sigset_size: u64,
 	) -> Result<(), Error> {
+		if sigset_size != size_of::<u64>() as u64 {
+			return Err(Error(EINVAL));
+		}
+		let signal = match parse_signal(signal)? {
+			0 => return Err(Error(EINVAL)),
+			signal => signal,
+		};
+		let old_action = *self.state.signals.action(signal);
+		if action_address != 0 {
+			if signal == SIGKILL || signal == SIGSTOP {
+				return Err(Error(EINVAL));
+			}
+			let handler = self.context.read_u64(action_address)?;
+			let flags =
+				self.context.read_u64(action_address + offset_of!(KSigaction, flags) as u64)?;
+			let mask =
+				self.context.read_u64(action_address + offset_of!(KSigaction, mask) as u64)?;
+			*self.state.signals.action_mut(signal) =
+				SigAction { handler, flags, mask: mask & !UNBLOCKABLE };
+			if self.state.signals.is_ignored(signal) {
+				// Pending signals are discarded.
+				let bit = sig_bit(signal);
+				self.state.signals.pending &= !bit;
+				for thread in self.state.threads.threads.values_mut() {
+					thread.pending_signals &= !bit;
+				}
+			}
+		}
+		if old_action_address != 0 {
+			let old_action = KSigaction {
+				handler: old_action.handler,
+				flags: old_action.flags,
+				mask: old_action.mask,
+				__unused: 0,
+			};
+			self.context.write_memory(old_action_address, as_u8_slice(&old_action))?;
+		}
 		Ok(())
 	}
 
 	fn handle_rt_sigprocmask(
 		&mut self,
-		_signum: u64,
-		_action: u64,
-		_old_action: u64,
-		_sigset_size: u64,
+		how: u64,
+		set_address: u64,
+		old_set_address: u64,
+		sigset_size: u64,
+	) -> Result<(), Error> {
+		if sigset_size != size_of::<u64>() as u64 {
+			return Err(Error(EINVAL));
+		}
+		let old_set = self.state.threads.current()?.blocked_signals;
+		if set_address != 0 {
+			let set = self.context.read_u64(set_address)?;
+			let blocked = match u8::try_from(how) {
+				Ok(SIG_BLOCK) => old_set | set,
+				Ok(SIG_UNBLOCK) => old_set & !set,
+				Ok(SIG_SETMASK) => set,
+				_ => return Err(Error(EINVAL)),
+			};
+			self.state.threads.current_mut()?.blocked_signals = blocked & !UNBLOCKABLE;
+		}
+		if old_set_address != 0 {
+			self.context.write_u64(old_set_address, old_set)?;
+		}
+		Ok(())
+	}
+
+	fn handle_rt_sigpending(&mut self, set_address: u64, sigset_size: u64) -> Result<(), Error> {
+		if sigset_size > size_of::<u64>() as u64 {
+			return Err(Error(EINVAL));
+		}
+		let thread = self.state.threads.current()?;
+		let pending =
+			(thread.pending_signals | self.state.signals.pending) & thread.blocked_signals;
+		self.context
+			.write_memory(set_address, &pending.to_le_bytes()[..sigset_size as usize])?;
+		Ok(())
+	}
+
+	fn handle_rt_sigreturn(&mut self) -> Result<(), Error> {
+		let frame = self.context.reg(Reg::SP);
+		let ucontext = frame + size_of::<Siginfo>() as u64;
+		let stack = ucontext + offset_of!(Ucontext, uc_stack) as u64;
+		let gregs = ucontext + offset_of!(Ucontext, uc_mcontext) as u64;
+		let blocked = self.context.read_u64(ucontext + offset_of!(Ucontext, uc_sigmask) as u64)?;
+		let signal_stack = SignalStack {
+			sp: self.context.read_u64(stack)?,
+			flags: self.context.read_u32(stack + offset_of!(StackT, ss_flags) as u64)?,
+			size: self.context.read_u64(stack + offset_of!(StackT, ss_size) as u64)?,
+		};
+		let pc = self.context.read_u64(gregs)?;
+		let mut regs = [0_u64; Reg::ALL.len()];
+		for reg in Reg::ALL {
+			regs[reg as usize] = self.context.read_u64(gregs + 8 * greg_index(reg) as u64)?;
+		}
+		for reg in Reg::ALL {
+			self.context.set_reg(reg, regs[reg as usize]);
+		}
+		self.context.set_next_program_counter(pc);
+		let thread = self.state.threads.current_mut()?;
+		thread.blocked_signals = blocked & !UNBLOCKABLE;
+		// The stack can't be changed while it is in use.
+		if !thread.signal_stack.contains(frame) {
+			thread.signal_stack = if signal_stack.flags & SS_DISABLE != 0 {
+				SignalStack::DISABLED
+			} else {
+				SignalStack { flags: signal_stack.flags & SS_AUTODISARM, ..signal_stack }
+			};
+		}
+		Ok(())
+	}
+
+	fn handle_sigaltstack(&mut self, ss_address: u64, old_ss_address: u64) -> Result<(), Error> {
+		let sp = self.context.reg(Reg::SP);
+		let old_stack = self.state.threads.current()?.signal_stack;
+		let on_stack = old_stack.contains(sp);
+		if ss_address != 0 {
+			if on_stack {
+				return Err(Error(EPERM));
+			}
+			let ss_sp = self.context.read_u64(ss_address)?;
+			let flags = self.context.read_u32(ss_address + offset_of!(StackT, ss_flags) as u64)?;
+			let size = self.context.read_u64(ss_address + offset_of!(StackT, ss_size) as u64)?;
+			let stack = match flags & !SS_AUTODISARM {
+				SS_DISABLE => SignalStack::DISABLED,
+				0 | SS_ONSTACK => {
+					if size < MINSIGSTKSZ {
+						return Err(Error(ENOMEM));
+					}
+					SignalStack { sp: ss_sp, flags: flags & SS_AUTODISARM, size }
+				},
+				_ => return Err(Error(EINVAL)),
+			};
+			self.state.threads.current_mut()?.signal_stack = stack;
+		}
+		if old_ss_address != 0 {
+			let flags = if on_stack { SS_ONSTACK | old_stack.flags } else { old_stack.flags };
+			let old_ss = StackT {
+				ss_sp: old_stack.sp,
+				ss_flags: flags as i32,
+				__pad: 0,
+				ss_size: old_stack.size,
+			};
+			self.context.write_memory(old_ss_address, as_u8_slice(&old_ss))?;
+		}
+		Ok(())
+	}
+
+	fn handle_kill(&mut self, pid: i32, signal: u64) -> Result<(), Error> {
+		let signal = parse_signal(signal)?;
+		// The process is the only one in its process group.
+		let cred = &self.state.credentials;
+		let found = match pid {
+			0 | -1 => true,
+			1.. => pid.unsigned_abs() == cred.pid,
+			_ => pid.unsigned_abs() == cred.pgid,
+		};
+		if !found {
+			return Err(Error(ESRCH));
+		}
+		if signal != 0 {
+			self.send_signal(None, signal);
+		}
+		Ok(())
+	}
+
+	fn handle_tkill(&mut self, tid: i32, signal: u64) -> Result<(), Error> {
+		let signal = parse_signal(signal)?;
+		let tid = u32::try_from(tid).ok().filter(|tid| *tid != 0).ok_or(Error(EINVAL))?;
+		if !self.state.threads.threads.contains_key(&tid) {
+			return Err(Error(ESRCH));
+		}
+		if signal != 0 {
+			self.send_signal(Some(tid), signal);
+		}
+		Ok(())
+	}
+
+	fn handle_tgkill(&mut self, tgid: i32, tid: i32, signal: u64) -> Result<(), Error> {
+		if tgid <= 0 || tid <= 0 {
+			return Err(Error(EINVAL));
+		}
+		if tgid.unsigned_abs() != self.state.credentials.pid {
+			return Err(Error(ESRCH));
+		}
+		self.handle_tkill(tid, signal)
+	}
+
+	fn handle_getitimer(&mut self, which: u64, value_address: u64) -> Result<(), Error> {
+		let value = self.itimer_value(which)?;
+		self.context.write_memory(value_address, as_u8_slice(&value))?;
+		Ok(())
+	}
+
+	fn handle_setitimer(
+		&mut self,
+		which: u64,
+		value_address: u64,
+		old_value_address: u64,
 	) -> Result<(), Error> {
+		let old_value = self.itimer_value(which)?;
+		let (interval, value) = if value_address != 0 {
+			let interval =
+				self.read_timeval(value_address + offset_of!(Itimerval, it_interval) as u64)?;
+			let value =
+				self.read_timeval(value_address + offset_of!(Itimerval, it_value) as u64)?;
+			(interval, value)
+		} else {
+			(Duration::ZERO, Duration::ZERO)
+		};
+		self.state.signals.alarm = if value.is_zero() {
+			None
+		} else {
+			let now = self.now(ClockId::Monotonic)?;
+			Some(IntervalTimer { deadline: now.saturating_add(value), interval })
+		};
+		if old_value_address != 0 {
+			self.context.write_memory(old_value_address, as_u8_slice(&old_value))?;
+		}
 		Ok(())
 	}
+
+	fn itimer_value(&mut self, which: u64) -> Result<Itimerval, Error> {
+		match which {
+			ITIMER_REAL => {},
+			// NOTE Only the real-time timer is supported.
+			ITIMER_VIRTUAL | ITIMER_PROF => return Err(Error(ENOSYS)),
+			_ => return Err(Error(EINVAL)),
+		}
+		let Some(timer) = self.state.signals.alarm else {
+			return Ok(Itimerval::default());
+		};
+		let now = self.now(ClockId::Monotonic)?;
+		Ok(Itimerval {
+			it_interval: to_timeval(timer.interval),
+			it_value: to_timeval(timer.deadline.saturating_sub(now)),
+		})
+	}
+
+	fn read_timeval(&mut self, address: u64) -> Result<Duration, Error> {
+		let tv_sec = self.context.read_u64(address)? as i64;
+		let tv_usec = self.context.read_u64(address.wrapping_add(8))? as i64;
+		let (Ok(secs), Ok(micros @ 0..MICROS_PER_SEC)) =
+			(u64::try_from(tv_sec), u32::try_from(tv_usec))
+		else {
+			return Err(Error(EINVAL));
+		};
+		Ok(Duration::new(secs, micros * 1000))
+	}
+
+	/// Send `SIGALRM` if the alarm has expired.
+	fn check_alarm(&mut self, now: Duration) {
+		let Some(timer) = self.state.signals.alarm else {
+			return;
+		};
+		if now < timer.deadline {
+			return;
+		}
+		self.state.signals.alarm = if timer.interval.is_zero() {
+			None
+		} else {
+			// Missed expirations are coalesced.
+			let mut deadline = timer.deadline.saturating_add(timer.interval);
+			if deadline <= now {
+				deadline = now.saturating_add(timer.interval);
+			}
+			Some(IntervalTimer { deadline, ..timer })
+		};
+		self.send_signal(None, SIGALRM);
+	}
+
+	/// Send `SIGALRM` if the alarm is armed and has expired.
+	///
+	/// The clock is read only here and at the points where the program blocks, so that the syscalls
+	/// that don't wait don't pay for it.
+	fn poll_alarm(&mut self) {
+		if self.state.signals.alarm.is_some() {
+			if let Ok(now) = self.now(ClockId::Monotonic) {
+				self.check_alarm(now);
+			}
+		}
+	}
+
+	/// Make the signal pending for the specified thread or for the process as a whole.
+	///
+	/// The thread blocked in a syscall is interrupted with `EINTR` error if it is the one to handle
+	/// the signal.
+	fn send_signal(&mut self, tid: Option<u32>, signal: u8) {
+		if self.state.signals.is_ignored(signal) {
+			return;
+		}
+		let bit = sig_bit(signal);
+		let threads = &mut self.state.threads;
+		let tid = match tid {
+			Some(tid) => {
+				let Some(thread) = threads.threads.get_mut(&tid) else {
+					return;
+				};
+				thread.pending_signals |= bit;
+				tid
+			},
+			None => {
+				self.state.signals.pending |= bit;
+				let can_handle = |thread: &Thread| thread.blocked_signals & bit == 0;
+				if threads.threads.get(&threads.current).is_some_and(can_handle) {
+					// The running thread handles the signal. It might be blocked if the signal is
+					// sent while waiting for the next runnable thread.
+					threads.current
+				} else {
+					match threads.threads.iter().find(|(_, thread)| can_handle(thread)) {
+						Some((tid, _)) => *tid,
+						None => return,
+					}
+				}
+			},
+		};
+		let thread = &self.state.threads.threads[&tid];
+		if thread.blocked_signals & bit != 0 {
+			return;
+		}
+		match thread.state {
+			ThreadState::Runnable => {},
+			ThreadState::Sleep { deadline, remaining_address } => {
+				if remaining_address != 0 {
+					// The clock has been read when the process started waiting or later.
+					let remaining = deadline.saturating_sub(self.last_monotonic);
+					// Errors are ignored like Linux does.
+					let _ = self.write_timespec(remaining_address, remaining);
+				}
+				self.wake_with_error(tid, EINTR);
+			},
+			ThreadState::FutexWait { .. } | ThreadState::PipeWait { .. } =>
+				self.wake_with_error(tid, EINTR),
+		}
+	}
+
+	/// Check if the running thread has pending signals that are not blocked.
+	fn has_deliverable_signals(&self) -> bool {
+		self.state.threads.current().is_ok_and(|thread| {
+			(thread.pending_signals | self.state.signals.pending) & !thread.blocked_signals != 0
+		})
+	}
+
+	/// Deliver pending signals to the running thread.
+	///
+	/// Returns the outcome if the process is terminated by a signal.
+	fn handle_signals(&mut self) -> Option<SyscallOutcome> {
+		loop {
+			let thread = self.state.threads.current().ok()?;
+			let thread_pending = thread.pending_signals & !thread.blocked_signals;
+			let pending = thread_pending | (self.state.signals.pending & !thread.blocked_signals);
+			if pending == 0 {
+				return None;
+			}
+			// Lower signal numbers are delivered first.
+			let signal = pending.trailing_zeros() as u8 + 1;
+			let bit = sig_bit(signal);
+			let code = if thread_pending & bit != 0 {
+				self.state.threads.current_mut().ok()?.pending_signals &= !bit;
+				SI_TKILL
+			} else {
+				self.state.signals.pending &= !bit;
+				SI_USER
+			};
+			let action = *self.state.signals.action(signal);
+			match action.handler {
+				SIG_IGN => continue,
+				SIG_DFL => {},
+				_ =>
+					match (self.context.next_program_counter(), self.context.signal_trampoline()) {
+						(Some(pc), Some(trampoline)) => {
+							if let Err(e) =
+								self.run_signal_handler(signal, code, action, pc, trampoline)
+							{
+								log::debug!(
+									"Failed to deliver signal {}: {e}",
+									debug::Signal(signal)
+								);
+								return Some(Killed { signal: SIGSEGV, core_dumped: true });
+							}
+							return None;
+						},
+						_ => {
+							log::debug!("The machine doesn't support signal handlers");
+						},
+					},
+			}
+			match DefaultAction::of(signal) {
+				action @ (DefaultAction::Terminate | DefaultAction::CoreDump) => {
+					log::debug!("Terminated by signal {}", debug::Signal(signal));
+					let core_dumped = action == DefaultAction::CoreDump;
+					return Some(Killed { signal, core_dumped });
+				},
+				DefaultAction::Ignore | DefaultAction::Stop | DefaultAction::Continue => continue,
+			}
+		}
+	}
+
+	/// Build the signal frame on the stack and redirect execution to the signal handler.
+	///
+	/// The frame consists of `siginfo_t` followed by `ucontext_t` like on RISC-V Linux. The handler
+	/// returns to `trampoline` that invokes `rt_sigreturn` which restores the context.
+	fn run_signal_handler(
+		&mut self,
+		signal: u8,
+		code: i32,
+		action: SigAction,
+		pc: u64,
+		trampoline: u64,
+	) -> Result<(), Error> {
+		let sp = self.context.reg(Reg::SP);
+		let thread = self.state.threads.current()?;
+		let stack = thread.signal_stack;
+		let top = if action.flags & SA_ONSTACK != 0 &&
+			stack.flags & SS_DISABLE == 0 &&
+			!stack.contains(sp)
+		{
+			stack.sp.wrapping_add(stack.size)
+		} else {
+			sp
+		};
+		let frame_size = (size_of::<Siginfo>() + size_of::<Ucontext>()) as u64;
+		let frame = top.checked_sub(frame_size).ok_or(Error(EFAULT))? & !15;
+		let info = Siginfo {
+			si_signo: signal.into(),
+			si_code: code,
+			si_pid: self.state.credentials.pid as i32,
+			si_uid: self.state.credentials.uid,
+			..Default::default()
+		};
+		let mut ucontext = Ucontext {
+			uc_flags: 0,
+			uc_link: 0,
+			uc_stack: StackT {
+				ss_sp: stack.sp,
+				ss_flags: stack.flags as i32,
+				__pad: 0,
+				ss_size: stack.size,
+			},
+			uc_sigmask: [0; 16],
+			__pad: 0,
+			uc_mcontext: Mcontext { gregs: [0; 32], fpregs: [0; 66] },
+		};
+		ucontext.uc_sigmask[0] = thread.blocked_signals;
+		ucontext.uc_mcontext.gregs[0] = pc;
+		for reg in Reg::ALL {
+			ucontext.uc_mcontext.gregs[greg_index(reg)] = self.context.reg(reg);
+		}
+		self.context.write_memory(frame, as_u8_slice(&info))?;
+		self.context
+			.write_memory(frame + size_of::<Siginfo>() as u64, as_u8_slice(&ucontext))?;
+		let thread = self.state.threads.current_mut()?;
+		thread.blocked_signals |= action.mask;
+		if action.flags & SA_NODEFER == 0 {
+			thread.blocked_signals |= sig_bit(signal);
+		}
+		thread.blocked_signals &= !UNBLOCKABLE;
+		if stack.flags & SS_AUTODISARM != 0 {
+			thread.signal_stack = SignalStack::DISABLED;
+		}
+		if action.flags & SA_RESETHAND != 0 {
+			*self.state.signals.action_mut(signal) = SigAction::default();
+		}
+		self.context.set_reg(Reg::SP, frame);
+		self.context.set_reg(Reg::RA, trampoline);
+		self.context.set_reg(Reg::A0, signal.into());
+		self.context.set_reg(Reg::A1, frame);
+		self.context.set_reg(Reg::A2, frame + size_of::<Siginfo>() as u64);
+		self.context.set_next_program_counter(action.handler);
+		log::debug!(
+			"Delivered signal {} to thread {}",
+			debug::Signal(signal),
+			self.state.threads.current
+		);
+		Ok(())
+	}
+}
+
+/// The change of the directory entry checked by [`Kernel::check_parent_writable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryChange {
+	/// New entry is added, fails with `EEXIST` if the entry exists.
+	Create,
+	/// The entry is removed, fails with the lookup error if the entry can't be looked up.
+	Remove,
+	/// The entry is added or replaced.
+	Replace,
+}
+
+/// Check that the credentials allow searching the parent directory of the path.
+///
+/// `last` is the previous lookup made during the path resolution, it is reused if it refers to
+/// the parent.
+fn check_search<C: FileSystem>(
+	fs: &mut C,
+	cred: &Credentials,
+	path: &CStr,
+	last: Option<&(CString, Result<Metadata, Error>)>,
+) -> Result<(), Error> {
+	if path.to_bytes() == b"/" {
+		return Ok(());
+	}
+	let parent = parent_dir(path.to_bytes());
+	let metadata = match last {
+		Some((last_path, Ok(metadata))) if last_path.to_bytes() == parent => metadata.clone(),
+		_ => fs.metadata(&to_cstring(parent))?,
+	};
+	cred.check_access(&metadata, X_OK, false)
+}
+
+/// Parse signal number.
+///
+/// Zero is valid and means that only the permissions are checked.
+fn parse_signal(signal: u64) -> Result<u8, Error> {
+	match u8::try_from(signal) {
+		Ok(signal) if usize::from(signal) <= NUM_SIGNALS => Ok(signal),
+		_ => Err(Error(EINVAL)),
+	}
+}
+
+fn to_timespec(duration: Duration) -> Timespec {
+	Timespec { tv_sec: duration.as_secs() as i64, tv_nsec: duration.subsec_nanos().into() }
+}
+
+fn to_timeval(duration: Duration) -> Timeval {
+	Timeval { tv_sec: duration.as_secs() as i64, tv_usec: duration.subsec_micros().into() }
 }
 
 fn as_u8_slice<T>(value: &T) -> &[u8] {
@@ -596,15 +3072,282 @@ fn as_u8_slice<T>(value: &T) -> &[u8] {
 }
 
 /// System call result.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SyscallOutcome {
 	/// Continue process execution.
 	Continue,
 	/// Exit the process with the provided code.
 	Exit(u8),
+	/// The process was terminated by the provided signal.
+	Killed {
+		/// Signal number.
+		signal: u8,
+		/// Whether the default action of the signal is to dump core.
+		core_dumped: bool,
+	},
+	/// The process can't make progress until the provided condition is met.
+	///
+	/// The syscall hasn't been completed and the registers are left intact. The host is expected
+	/// to suspend the program and call [`Kernel::handle_syscall`] again to restart the syscall
+	/// once the condition is met.
+	Blocked(WaitReason),
+	/// All threads are blocked forever.
+	///
+	/// The running thread has exited or waits for a pipe while the other threads wait on futexes
+	/// without timeout or on pipes, hence no thread can make progress. The host is expected to
+	/// terminate the program.
+	Deadlock,
+	/// The process has yielded the processor and no other thread wants it.
+	///
+	/// The syscall has been completed. The host may run other tasks before continuing process
+	/// execution.
+	Yield,
+}
+
+/// The condition the blocked process is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitReason {
+	/// Standard input has no data available.
+	///
+	/// See [`Environment::read_from_stdin`].
+	Input,
+	/// All threads wait on futexes without timeout.
+	///
+	/// Only the host can change that, e.g. by modifying the memory or sending a signal.
+	Futex,
+	/// Monotonic clock reaches the deadline.
+	///
+	/// See [`Clock::sleep`].
+	Timer {
+		/// Monotonic clock value.
+		deadline: Duration,
+	},
 }
 
-const THREAD_ID: u32 = 1;
+const NANOS_PER_SEC: u32 = 1_000_000_000;
 
-/// 0, 1, 2 are reserved.
-const MAX_RESERVED_FD: u32 = 2;
+const MICROS_PER_SEC: u32 = 1_000_000;
+
+/// The maximum number of bytes `getrandom` returns at once.
+const GETRANDOM_MAX: u64 = (1 << 25) - 1;
+
+/// The inode number of the terminal the standard streams are connected to.
+const TTY_ID: u64 = 1;
+
+/// The block size of the terminal the standard streams are connected to.
+const TTY_BLOCK_SIZE: u64 = 1024;
+
+/// The inode number of `/dev/tty`.
+const CONTROLLING_TTY_ID: u64 = 2;
+
+/// The inode number of `/dev/urandom`.
+const URANDOM_ID: u64 = 3;
+
+/// The file status flags that `F_SETFL` changes.
+const SETFL_FLAGS: u64 = O_APPEND | O_NONBLOCK;
+
+/// The default file mode creation mask.
+const DEFAULT_UMASK: u32 = 0o022;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{in_memory::InMemoryFileSystem, testing::*, MAIN_THREAD_ID};
+
+	const SET: u64 = MEMORY_START;
+	const OLD_SET: u64 = MEMORY_START + 8;
+	const TIMER: u64 = MEMORY_START + 16;
+
+	fn sigprocmask(kernel: &mut Kernel<TestContext>, how: u8, set: u64) -> u64 {
+		kernel.context.write_u64(SET, set).unwrap();
+		let args = [how.into(), SET, OLD_SET, 8];
+		assert_eq!((Continue, 0), syscall(kernel, SYS_RT_SIGPROCMASK, &args));
+		kernel.context.read_u64(OLD_SET).unwrap()
+	}
+
+	fn blocked_signals(kernel: &mut Kernel<TestContext>) -> u64 {
+		assert_eq!((Continue, 0), syscall(kernel, SYS_RT_SIGPROCMASK, &[0, 0, OLD_SET, 8]));
+		kernel.context.read_u64(OLD_SET).unwrap()
+	}
+
+	fn pending_signals(kernel: &mut Kernel<TestContext>) -> u64 {
+		assert_eq!((Continue, 0), syscall(kernel, SYS_RT_SIGPENDING, &[SET, 8]));
+		kernel.context.read_u64(SET).unwrap()
+	}
+
+	fn setitimer(kernel: &mut Kernel<TestContext>, interval: u64, value: u64) {
+		let timer = [interval, 0, value, 0];
+		for (i, word) in timer.into_iter().enumerate() {
+			kernel.context.write_u64(TIMER + 8 * i as u64, word).unwrap();
+		}
+		assert_eq!((Continue, 0), syscall(kernel, SYS_SETITIMER, &[ITIMER_REAL, TIMER, 0]));
+	}
+
+	fn getitimer(kernel: &mut Kernel<TestContext>) -> [u64; 4] {
+		assert_eq!((Continue, 0), syscall(kernel, SYS_GETITIMER, &[ITIMER_REAL, TIMER]));
+		core::array::from_fn(|i| kernel.context.read_u64(TIMER + 8 * i as u64).unwrap())
+	}
+
+	#[test]
+	fn signal_mask_changes() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		let usr = sig_bit(SIGUSR1) | sig_bit(SIGUSR2);
+		assert_eq!(0, sigprocmask(&mut kernel, SIG_BLOCK, usr | UNBLOCKABLE));
+		assert_eq!(usr, blocked_signals(&mut kernel));
+		assert_eq!(usr, sigprocmask(&mut kernel, SIG_UNBLOCK, sig_bit(SIGUSR2)));
+		assert_eq!(sig_bit(SIGUSR1), sigprocmask(&mut kernel, SIG_SETMASK, sig_bit(SIGHUP)));
+		assert_eq!(sig_bit(SIGHUP), blocked_signals(&mut kernel));
+		let (_, ret) = syscall(&mut kernel, SYS_RT_SIGPROCMASK, &[3, SET, 0, 8]);
+		assert_eq!(errno(EINVAL) as i64, ret);
+		let (_, ret) = syscall(&mut kernel, SYS_RT_SIGPROCMASK, &[0, 0, OLD_SET, 4]);
+		assert_eq!(errno(EINVAL) as i64, ret);
+	}
+
+	#[test]
+	fn blocked_signal_is_delivered_when_unblocked() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		sigprocmask(&mut kernel, SIG_BLOCK, sig_bit(SIGUSR1));
+		assert_eq!((Continue, 0), syscall(&mut kernel, SYS_KILL, &[0, SIGUSR1.into()]));
+		assert_eq!(sig_bit(SIGUSR1), pending_signals(&mut kernel));
+		kernel.context.write_u64(SET, sig_bit(SIGUSR1)).unwrap();
+		let args = [SIG_UNBLOCK.into(), SET, 0, 8];
+		let (outcome, _) = syscall(&mut kernel, SYS_RT_SIGPROCMASK, &args);
+		assert_eq!(Killed { signal: SIGUSR1, core_dumped: false }, outcome);
+	}
+
+	#[test]
+	fn ignored_signal_is_discarded() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		sigprocmask(&mut kernel, SIG_BLOCK, sig_bit(SIGCHLD) | sig_bit(SIGUSR1));
+		kernel.state.signals.action_mut(SIGUSR1).handler = SIG_IGN;
+		syscall(&mut kernel, SYS_KILL, &[0, SIGCHLD.into()]);
+		syscall(&mut kernel, SYS_KILL, &[0, SIGUSR1.into()]);
+		assert_eq!(0, pending_signals(&mut kernel));
+	}
+
+	#[test]
+	fn alarm_terminates_process() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		setitimer(&mut kernel, 0, 2);
+		kernel.context.clock += Duration::from_millis(500);
+		assert_eq!([0, 0, 1, 500_000], getitimer(&mut kernel));
+		assert_eq!((Yield, 0), syscall(&mut kernel, SYS_SCHED_YIELD, &[]));
+		kernel.context.clock += Duration::from_secs(2);
+		let (outcome, _) = syscall(&mut kernel, SYS_SCHED_YIELD, &[]);
+		assert_eq!(Killed { signal: SIGALRM, core_dumped: false }, outcome);
+	}
+
+	#[test]
+	fn periodic_alarm_coalesces_missed_expirations() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		sigprocmask(&mut kernel, SIG_BLOCK, sig_bit(SIGALRM));
+		setitimer(&mut kernel, 1, 1);
+		kernel.context.clock += Duration::from_millis(5500);
+		assert_eq!((Yield, 0), syscall(&mut kernel, SYS_SCHED_YIELD, &[]));
+		assert_eq!(sig_bit(SIGALRM), pending_signals(&mut kernel));
+		assert_eq!([1, 0, 1, 0], getitimer(&mut kernel));
+		setitimer(&mut kernel, 0, 0);
+		assert_eq!(None, kernel.state.signals.alarm);
+	}
+
+	fn pipe(kernel: &mut Kernel<TestContext>, flags: u64) -> (u64, u64) {
+		assert_eq!((Continue, 0), syscall(kernel, SYS_PIPE2, &[SET, flags]));
+		let fds = kernel.context.read_u64(SET).unwrap();
+		(fds & 0xffff_ffff, fds >> 32)
+	}
+
+	#[test]
+	fn pipe_transfers_data_until_eof() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		let (reader, writer) = pipe(&mut kernel, 0);
+		assert_eq!((3, 4), (reader, writer));
+		kernel.context.write_memory(TIMER, b"hello").unwrap();
+		assert_eq!((Continue, 5), syscall(&mut kernel, SYS_WRITE, &[writer, TIMER, 5]));
+		let (_, dup) = syscall(&mut kernel, SYS_DUP, &[writer]);
+		assert_eq!((Continue, 0), syscall(&mut kernel, SYS_CLOSE, &[writer]));
+		assert_eq!((Continue, 3), syscall(&mut kernel, SYS_READ, &[reader, SET, 3]));
+		assert_eq!(b"hel", &kernel.context.read_memory(SET, 3).unwrap()[..]);
+		assert_eq!((Continue, 0), syscall(&mut kernel, SYS_CLOSE, &[dup as u64]));
+		assert_eq!((Continue, 2), syscall(&mut kernel, SYS_READ, &[reader, SET, 8]));
+		assert_eq!((Continue, 0), syscall(&mut kernel, SYS_READ, &[reader, SET, 8]));
+	}
+
+	#[test]
+	fn empty_nonblocking_pipe_fails_with_eagain() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		let (reader, _) = pipe(&mut kernel, O_NONBLOCK);
+		let (outcome, ret) = syscall(&mut kernel, SYS_READ, &[reader, SET, 8]);
+		assert_eq!((Continue, errno(EAGAIN) as i64), (outcome, ret));
+	}
+
+	#[test]
+	fn write_to_closed_pipe_raises_sigpipe() {
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		let (reader, writer) = pipe(&mut kernel, 0);
+		syscall(&mut kernel, SYS_CLOSE, &[reader]);
+		let (outcome, _) = syscall(&mut kernel, SYS_WRITE, &[writer, SET, 1]);
+		assert_eq!(Killed { signal: SIGPIPE, core_dumped: false }, outcome);
+		kernel.state.signals.action_mut(SIGPIPE).handler = SIG_IGN;
+		let (outcome, ret) = syscall(&mut kernel, SYS_WRITE, &[writer, SET, 1]);
+		assert_eq!((Continue, errno(EPIPE) as i64), (outcome, ret));
+	}
+
+	#[test]
+	fn init_fills_aux_vector_from_state() {
+		let seed = [3; 32];
+		let credentials =
+			Credentials { euid: 0, ..Credentials::new().with_uid(1000).with_gid(100) };
+		let state = KernelState::new().with_random_seed(seed).with_credentials(credentials);
+		let mut kernel = Kernel::new(TestContext::new(InMemoryFileSystem::new()), state);
+		let auxv = kernel.auxv();
+		kernel.init(MEMORY_END, 0, [c"/bin/program"], [], auxv).unwrap();
+		let (_, _, entries) = kernel.context.read_initial_stack();
+		assert_eq!(
+			[1000, 0, 100, 100, 1],
+			[AT_UID, AT_EUID, AT_GID, AT_EGID, AT_SECURE].map(|key| entries[&key])
+		);
+		let mut random = [0_u8; 16];
+		ChaChaRng::new(seed).fill(&mut random);
+		assert_eq!(Ok(random.to_vec()), kernel.context.read_memory(entries[&AT_RANDOM], 16));
+		assert_eq!(
+			Ok(c"/bin/program".into()),
+			kernel.context.read_cstring(entries[&AT_EXECFN], 64)
+		);
+		assert_eq!(c"/bin/program", kernel.state.exe.as_c_str());
+	}
+
+	#[test]
+	fn init_keeps_explicit_aux_vector_fields() {
+		let state = KernelState::new().with_credentials(Credentials::new().with_uid(1000));
+		let mut kernel = Kernel::new(TestContext::new(InMemoryFileSystem::new()), state);
+		let auxv = kernel.auxv().with_uid(1, 2).with_secure(false).with_random([9; 16]);
+		kernel.init(MEMORY_END, 0, [c"program"], [], auxv.with_execfn(c"/exe")).unwrap();
+		let (_, _, entries) = kernel.context.read_initial_stack();
+		assert_eq!([1, 2, 0], [AT_UID, AT_EUID, AT_SECURE].map(|key| entries[&key]));
+		assert_eq!(Ok(vec![9; 16]), kernel.context.read_memory(entries[&AT_RANDOM], 16));
+		assert_eq!(Ok(c"/exe".into()), kernel.context.read_cstring(entries[&AT_EXECFN], 64));
+	}
+
+	#[test]
+	fn futex_wait_and_wake() {
+		const FUTEX: u64 = MEMORY_START + 32;
+		let mut kernel = kernel(InMemoryFileSystem::new());
+		kernel.context.write_u32(FUTEX, 1).unwrap();
+		let (_, ret) = syscall(&mut kernel, SYS_FUTEX, &[FUTEX, FUTEX_WAIT, 0]);
+		assert_eq!(errno(EAGAIN) as i64, ret);
+		let (_, ret) = syscall(&mut kernel, SYS_FUTEX, &[FUTEX + 2, FUTEX_WAIT, 1]);
+		assert_eq!(errno(EINVAL) as i64, ret);
+		let tid = kernel.state.threads.spawn(Thread::new());
+		// The waiting thread yields to the other one.
+		let (outcome, _) = syscall(&mut kernel, SYS_FUTEX, &[FUTEX, FUTEX_WAIT | FUTEX_PRIVATE, 1]);
+		assert_eq!(Continue, outcome);
+		assert_eq!(tid, kernel.state.threads.current);
+		assert!(matches!(
+			kernel.state.threads.threads[&MAIN_THREAD_ID].state,
+			ThreadState::FutexWait { address: FUTEX, .. }
+		));
+		assert_eq!((Continue, 0), syscall(&mut kernel, SYS_FUTEX, &[FUTEX + 4, FUTEX_WAKE, 1]));
+		assert_eq!((Continue, 1), syscall(&mut kernel, SYS_FUTEX, &[FUTEX, FUTEX_WAKE, 1]));
+		assert_eq!(ThreadState::Runnable, kernel.state.threads.threads[&MAIN_THREAD_ID].state);
+	}
+}
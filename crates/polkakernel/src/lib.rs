@@ -6,12 +6,25 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+mod cred;
 pub(crate) mod debug;
 mod env;
 mod error;
+mod fd;
 mod fs;
 mod kernel;
 pub mod libc;
 mod machine;
+mod memory;
+mod random;
+mod signal;
+mod thread;
+mod time;
 
-pub use self::{env::*, error::*, fs::*, kernel::*, machine::*};
+#[cfg(test)]
+mod testing;
+
+pub use self::{
+	cred::*, env::*, error::*, fd::*, fs::*, kernel::*, machine::*, memory::*, random::*,
+	signal::*, thread::*, time::*,
+};
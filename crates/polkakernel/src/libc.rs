@@ -5,11 +5,33 @@
 #![allow(unused)]
 
 pub const AT_FDCWD: i32 = -100_i32;
+pub const AT_SYMLINK_NOFOLLOW: u64 = 0x100;
+pub const AT_EACCESS: u64 = 0x200;
+pub const AT_REMOVEDIR: u64 = 0x200;
+pub const AT_SYMLINK_FOLLOW: u64 = 0x400;
+pub const AT_NO_AUTOMOUNT: u64 = 0x800;
+pub const AT_EMPTY_PATH: u64 = 0x1000;
+pub const AT_STATX_SYNC_TYPE: u64 = 0x6000;
+pub const AT_NULL: u64 = 0;
 pub const AT_PAGESZ: u64 = 6;
+pub const AT_UID: u64 = 11;
+pub const AT_EUID: u64 = 12;
+pub const AT_GID: u64 = 13;
+pub const AT_EGID: u64 = 14;
+pub const AT_PLATFORM: u64 = 15;
+pub const AT_HWCAP: u64 = 16;
+pub const AT_CLKTCK: u64 = 17;
+pub const AT_SECURE: u64 = 23;
+pub const AT_RANDOM: u64 = 25;
+pub const AT_EXECFN: u64 = 31;
 
 pub const EACCES: u64 = 13;
+pub const EEXIST: u64 = 17;
+pub const EFBIG: u64 = 27;
 pub const EBADF: u64 = 9;
 pub const EFAULT: u64 = 14;
+pub const ENOMEM: u64 = 12;
+pub const ENODEV: u64 = 19;
 pub const ENOTDIR: u64 = 20;
 pub const EINVAL: u64 = 22;
 pub const EIO: u64 = 5;
@@ -17,27 +39,155 @@ pub const ENOENT: u64 = 2;
 pub const ENOSYS: u64 = 38;
 pub const EISDIR: u64 = 21;
 pub const ERANGE: u64 = 34;
+pub const EAGAIN: u64 = 11;
+pub const EDEADLK: u64 = 35;
+pub const ETIMEDOUT: u64 = 110;
+pub const EPERM: u64 = 1;
+pub const ESRCH: u64 = 3;
+pub const EINTR: u64 = 4;
+pub const EMFILE: u64 = 24;
+pub const ENOTTY: u64 = 25;
+pub const ESPIPE: u64 = 29;
+pub const EPIPE: u64 = 32;
+pub const ENOTEMPTY: u64 = 39;
+pub const ELOOP: u64 = 40;
+pub const EBUSY: u64 = 16;
+pub const EROFS: u64 = 30;
+pub const ENOSPC: u64 = 28;
+pub const EXDEV: u64 = 18;
+pub const EOVERFLOW: u64 = 75;
+pub const EMLINK: u64 = 31;
+pub const ENAMETOOLONG: u64 = 36;
 
 pub const FILENO_STDERR: u32 = 2;
 pub const FILENO_STDOUT: u32 = 1;
+pub const FILENO_STDIN: u32 = 0;
 
 pub const IOV_MAX: u64 = 1024;
 
+pub const O_ACCMODE: u64 = 3;
+pub const O_RDONLY: u64 = 0;
 pub const O_RDWR: u64 = 2;
 pub const O_WRONLY: u64 = 1;
+pub const O_CREAT: u64 = 0o100;
+pub const O_EXCL: u64 = 0o200;
+pub const O_NOCTTY: u64 = 0o400;
+pub const O_TRUNC: u64 = 0o1000;
+pub const O_APPEND: u64 = 0o2000;
 pub const O_CLOEXEC: u64 = 0o2000000;
-pub const O_DIRECTORY: u64 = 0o40000;
+pub const O_DIRECTORY: u64 = 0o200000;
+pub const O_NONBLOCK: u64 = 0o4000;
+pub const O_NOFOLLOW: u64 = 0o400000;
+
+pub const MS_RDONLY: u64 = 1;
+pub const MS_REMOUNT: u64 = 32;
+pub const MS_BIND: u64 = 4096;
+pub const MS_MOVE: u64 = 8192;
+
+pub const MNT_FORCE: u64 = 1;
+pub const MNT_DETACH: u64 = 2;
+pub const MNT_EXPIRE: u64 = 4;
+pub const UMOUNT_NOFOLLOW: u64 = 8;
+
+// Musl doesn't define these, see `include/uapi/linux/fcntl.h` in Linux.
+pub const RENAME_NOREPLACE: u64 = 1;
+pub const RENAME_EXCHANGE: u64 = 2;
+pub const RENAME_WHITEOUT: u64 = 4;
 
 pub const PATH_MAX: u64 = 4096;
 
+pub const PAGE_SIZE: u64 = 4096;
+
+pub const PROT_NONE: u64 = 0;
+pub const PROT_READ: u64 = 1;
+pub const PROT_WRITE: u64 = 2;
+pub const PROT_EXEC: u64 = 4;
+
+pub const MAP_SHARED: u64 = 0x01;
+pub const MAP_PRIVATE: u64 = 0x02;
+pub const MAP_SHARED_VALIDATE: u64 = 0x03;
+pub const MAP_TYPE: u64 = 0x0f;
+pub const MAP_FIXED: u64 = 0x10;
+pub const MAP_ANONYMOUS: u64 = 0x20;
+pub const MAP_FIXED_NOREPLACE: u64 = 0x100000;
+
+pub const MS_ASYNC: u64 = 1;
+pub const MS_INVALIDATE: u64 = 2;
+pub const MS_SYNC: u64 = 4;
+
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFSOCK: u32 = 0o140000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFBLK: u32 = 0o60000;
+pub const S_IFDIR: u32 = 0o40000;
+pub const S_IFCHR: u32 = 0o20000;
+pub const S_IFIFO: u32 = 0o10000;
+
+pub const F_OK: u32 = 0;
+pub const X_OK: u32 = 1;
+pub const W_OK: u32 = 2;
+pub const R_OK: u32 = 4;
+
+pub const NGROUPS_MAX: u64 = 65536;
+
+pub const CLOCK_REALTIME: u64 = 0;
+pub const CLOCK_MONOTONIC: u64 = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: u64 = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: u64 = 3;
+pub const CLOCK_MONOTONIC_RAW: u64 = 4;
+pub const CLOCK_REALTIME_COARSE: u64 = 5;
+pub const CLOCK_MONOTONIC_COARSE: u64 = 6;
+pub const CLOCK_BOOTTIME: u64 = 7;
+
+pub const TIMER_ABSTIME: u64 = 1;
+
+pub const CLONE_VM: u64 = 0x100;
+pub const CLONE_FS: u64 = 0x200;
+pub const CLONE_FILES: u64 = 0x400;
+pub const CLONE_SIGHAND: u64 = 0x800;
+pub const CLONE_THREAD: u64 = 0x10000;
+pub const CLONE_SYSVSEM: u64 = 0x40000;
+pub const CLONE_SETTLS: u64 = 0x80000;
+pub const CLONE_PARENT_SETTID: u64 = 0x100000;
+pub const CLONE_CHILD_CLEARTID: u64 = 0x200000;
+pub const CLONE_DETACHED: u64 = 0x400000;
+pub const CLONE_CHILD_SETTID: u64 = 0x1000000;
+
+pub const FUTEX_WAIT: u64 = 0;
+pub const FUTEX_WAKE: u64 = 1;
+pub const FUTEX_REQUEUE: u64 = 3;
+pub const FUTEX_CMP_REQUEUE: u64 = 4;
+pub const FUTEX_WAIT_BITSET: u64 = 9;
+pub const FUTEX_WAKE_BITSET: u64 = 10;
+pub const FUTEX_PRIVATE: u64 = 128;
+pub const FUTEX_CLOCK_REALTIME: u64 = 256;
+pub const FUTEX_BITSET_MATCH_ANY: u32 = 0xffffffff;
+
+pub const DT_UNKNOWN: u8 = 0;
+pub const DT_FIFO: u8 = 1;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_BLK: u8 = 6;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+pub const DT_SOCK: u8 = 12;
+
 pub const SEEK_CUR: u64 = 1;
 pub const SEEK_END: u64 = 2;
 pub const SEEK_SET: u64 = 0;
 
+pub const F_DUPFD: u64 = 0;
+pub const F_GETFD: u64 = 1;
 pub const F_SETFD: u64 = 2;
+pub const F_GETFL: u64 = 3;
+pub const F_SETFL: u64 = 4;
+pub const F_DUPFD_CLOEXEC: u64 = 1030;
 
 pub const FD_CLOEXEC: u64 = 1;
 
+pub const STATX_BASIC_STATS: u32 = 0x7ff;
+
 // Signals.
 pub const SIGHUP: u8 = 1;
 pub const SIGINT: u8 = 2;
@@ -71,12 +221,39 @@ pub const SIGIO: u8 = 29;
 pub const SIGPWR: u8 = 30;
 pub const SIGSYS: u8 = 31;
 
-pub const SIG_BLOCK: u8 = 1;
-pub const SIG_UNBLOCK: u8 = 2;
-pub const SIG_SETMASK: u8 = 3;
+pub const SIG_BLOCK: u8 = 0;
+pub const SIG_UNBLOCK: u8 = 1;
+pub const SIG_SETMASK: u8 = 2;
+
+pub const SIG_DFL: u64 = 0;
+pub const SIG_IGN: u64 = 1;
+
+pub const SA_NOCLDSTOP: u64 = 1;
+pub const SA_NOCLDWAIT: u64 = 2;
+pub const SA_SIGINFO: u64 = 4;
+pub const SA_ONSTACK: u64 = 0x08000000;
+pub const SA_RESTART: u64 = 0x10000000;
+pub const SA_NODEFER: u64 = 0x40000000;
+pub const SA_RESETHAND: u64 = 0x80000000;
+
+pub const SS_ONSTACK: u32 = 1;
+pub const SS_DISABLE: u32 = 2;
+pub const SS_AUTODISARM: u32 = 1 << 31;
+
+pub const MINSIGSTKSZ: u64 = 2048;
+
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 128;
+pub const SI_TKILL: i32 = -6;
+
+pub const ITIMER_REAL: u64 = 0;
+pub const ITIMER_VIRTUAL: u64 = 1;
+pub const ITIMER_PROF: u64 = 2;
 
 // See `arch/riscv64/bits/syscall.h.in` for the actual values.
 pub const SYS_FCNTL: u64 = 25;
+pub const SYS_TRUNCATE: u64 = 45;
+pub const SYS_FTRUNCATE: u64 = 46;
 pub const SYS_CLOSE: u64 = 57;
 pub const SYS_EXIT: u64 = 93;
 pub const SYS_EXIT_GROUP: u64 = 94;
@@ -94,23 +271,72 @@ pub const SYS_GETGID: u64 = 176;
 pub const SYS_GETEGID: u64 = 177;
 pub const SYS_SETUID: u64 = 146;
 pub const SYS_SETGID: u64 = 144;
+pub const SYS_SETRESUID: u64 = 147;
+pub const SYS_GETRESUID: u64 = 148;
+pub const SYS_SETRESGID: u64 = 149;
+pub const SYS_GETRESGID: u64 = 150;
+pub const SYS_GETPID: u64 = 172;
+pub const SYS_GETPPID: u64 = 173;
+pub const SYS_GETPGID: u64 = 155;
+pub const SYS_SETSID: u64 = 157;
 pub const SYS_UNAME: u64 = 160;
 pub const SYS_NEWFSTATAT: u64 = 79;
+pub const SYS_FSTAT: u64 = 80;
+pub const SYS_STATX: u64 = 291;
+pub const SYS_NANOSLEEP: u64 = 101;
 pub const SYS_CLOCK_GETTIME: u64 = 113;
+pub const SYS_CLOCK_GETRES: u64 = 114;
+pub const SYS_CLOCK_NANOSLEEP: u64 = 115;
+pub const SYS_GETTIMEOFDAY: u64 = 169;
 pub const SYS_GETDENTS64: u64 = 61;
 pub const SYS_FACCESSAT: u64 = 48;
+pub const SYS_FACCESSAT2: u64 = 439;
 pub const SYS_GETGROUPS: u64 = 158;
+pub const SYS_SETGROUPS: u64 = 159;
+pub const SYS_UMASK: u64 = 166;
 pub const SYS_SYNC: u64 = 81;
+pub const SYS_SYMLINKAT: u64 = 36;
+pub const SYS_LINKAT: u64 = 37;
+pub const SYS_READLINKAT: u64 = 78;
+pub const SYS_MKDIRAT: u64 = 34;
+pub const SYS_UNLINKAT: u64 = 35;
+pub const SYS_RENAMEAT2: u64 = 276;
+pub const SYS_MOUNT: u64 = 40;
+pub const SYS_UMOUNT2: u64 = 39;
+pub const SYS_DUP: u64 = 23;
 pub const SYS_DUP3: u64 = 24;
+pub const SYS_PIPE2: u64 = 59;
 pub const SYS_GETCWD: u64 = 17;
+pub const SYS_CHDIR: u64 = 49;
+pub const SYS_FCHDIR: u64 = 50;
+pub const SYS_KILL: u64 = 129;
 pub const SYS_TKILL: u64 = 130;
+pub const SYS_TGKILL: u64 = 131;
+pub const SYS_SIGALTSTACK: u64 = 132;
+pub const SYS_RT_SIGPENDING: u64 = 136;
+pub const SYS_RT_SIGRETURN: u64 = 139;
+pub const SYS_GETITIMER: u64 = 102;
+pub const SYS_SETITIMER: u64 = 103;
 pub const SYS_PPOLL: u64 = 73;
 pub const SYS_RT_SIGACTION: u64 = 134;
 pub const SYS_RT_SIGPROCMASK: u64 = 135;
 pub const SYS_FUTEX: u64 = 98;
+pub const SYS_CLONE: u64 = 220;
+pub const SYS_GETTID: u64 = 178;
+pub const SYS_SCHED_YIELD: u64 = 124;
+pub const SYS_BRK: u64 = 214;
+pub const SYS_MUNMAP: u64 = 215;
+pub const SYS_MMAP: u64 = 222;
+pub const SYS_MPROTECT: u64 = 226;
+pub const SYS_MSYNC: u64 = 227;
+pub const SYS_GETRANDOM: u64 = 278;
 
 pub const TIOCGWINSZ: u64 = 0x5413;
 
+pub const GRND_NONBLOCK: u64 = 1;
+pub const GRND_RANDOM: u64 = 2;
+pub const GRND_INSECURE: u64 = 4;
+
 pub type DevT = u64;
 pub type InoT = u64;
 pub type ModeT = u32;
@@ -129,6 +355,27 @@ pub struct Timespec {
 	pub tv_nsec: i64,
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Timeval {
+	pub tv_sec: i64,
+	pub tv_usec: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Itimerval {
+	pub it_interval: Timeval,
+	pub it_value: Timeval,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Timezone {
+	pub tz_minuteswest: i32,
+	pub tz_dsttime: i32,
+}
+
 #[repr(C)]
 #[derive(Debug, Default)]
 pub struct Stat {
@@ -150,6 +397,90 @@ pub struct Stat {
 	pub __unused: [u32; 2],
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct StatxTimestamp {
+	pub tv_sec: i64,
+	pub tv_nsec: u32,
+	pub __pad: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Statx {
+	pub stx_mask: u32,
+	pub stx_blksize: u32,
+	pub stx_attributes: u64,
+	pub stx_nlink: u32,
+	pub stx_uid: u32,
+	pub stx_gid: u32,
+	pub stx_mode: u16,
+	pub __pad1: u16,
+	pub stx_ino: u64,
+	pub stx_size: u64,
+	pub stx_blocks: u64,
+	pub stx_attributes_mask: u64,
+	pub stx_atime: StatxTimestamp,
+	pub stx_btime: StatxTimestamp,
+	pub stx_ctime: StatxTimestamp,
+	pub stx_mtime: StatxTimestamp,
+	pub stx_rdev_major: u32,
+	pub stx_rdev_minor: u32,
+	pub stx_dev_major: u32,
+	pub stx_dev_minor: u32,
+	pub __spare: [u64; 14],
+}
+
+/// `struct k_sigaction` that is used by `rt_sigaction` syscall.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct KSigaction {
+	pub handler: u64,
+	pub flags: u64,
+	pub mask: u64,
+	pub __unused: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct StackT {
+	pub ss_sp: u64,
+	pub ss_flags: i32,
+	pub __pad: i32,
+	pub ss_size: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Siginfo {
+	pub si_signo: i32,
+	pub si_errno: i32,
+	pub si_code: i32,
+	pub __pad: i32,
+	pub si_pid: i32,
+	pub si_uid: u32,
+	pub __rest: [u64; 13],
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct Mcontext {
+	pub gregs: [u64; 32],
+	pub fpregs: [u64; 66],
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct Ucontext {
+	pub uc_flags: u64,
+	pub uc_link: u64,
+	pub uc_stack: StackT,
+	pub uc_sigmask: [u64; 16],
+	/// `mcontext_t` is 16-byte aligned.
+	pub __pad: u64,
+	pub uc_mcontext: Mcontext,
+}
+
 #[repr(C)]
 #[derive(Debug, Default)]
 pub struct WinSize {
@@ -184,3 +515,25 @@ pub(crate) use utsname_field;
 pub const fn errno(error: u64) -> u64 {
 	(-(error as i64)) as u64
 }
+
+/// Combine major and minor device numbers into `dev_t`.
+///
+/// See [makedev(3)](https://man7.org/linux/man-pages/man3/makedev.3.html).
+pub const fn makedev(major: u32, minor: u32) -> u64 {
+	let major = major as u64;
+	let minor = minor as u64;
+	((major & 0xfffff000) << 32) |
+		((major & 0x00000fff) << 8) |
+		((minor & 0xffffff00) << 12) |
+		(minor & 0x000000ff)
+}
+
+/// Get major device number from `dev_t`.
+pub const fn major(dev: u64) -> u32 {
+	(((dev >> 31 >> 1) & 0xfffff000) | ((dev >> 8) & 0x00000fff)) as u32
+}
+
+/// Get minor device number from `dev_t`.
+pub const fn minor(dev: u64) -> u32 {
+	(((dev >> 12) & 0xffffff00) | (dev & 0x000000ff)) as u32
+}
@@ -11,16 +11,19 @@ use MachineError::*;
 pub trait Machine {
 	/// Prepare the machine for calling `main` function.
 	///
-	/// - Write arguments, environment variables and aux vector starting at `default_sp` address.
-	/// - Initialize SP register with the updated stack pointer.
+	/// - Write the strings and the random bytes the aux vector refers to, arguments and environment
+	///   variables below `default_sp` address.
+	/// - Write `argc`, `argv`, `envp` and the aux vector below them.
+	/// - Initialize SP and A0 registers with the address of `argc` aligned to 16 bytes as required
+	///   by RISC-V psABI.
 	/// - Initialize RA register with `default_ra` return address.
-	/// - Initialize A0 register with the address of `argc`.
 	fn init<'argv, 'envp, I1, I2>(
 		&mut self,
 		default_sp: u64,
 		default_ra: u64,
 		argv: I1,
 		envp: I2,
+		auxv: &AuxVec,
 	) -> Result<(), MachineError>
 	where
 		I1: IntoIterator<Item = &'argv CStr>,
@@ -28,53 +31,132 @@ pub trait Machine {
 		I2: IntoIterator<Item = &'envp CStr>,
 		<I2 as IntoIterator>::IntoIter: ExactSizeIterator,
 	{
-		let argv = argv.into_iter();
-		let argc = argv.len() as u64;
-		let envp = envp.into_iter();
-		let envp_len = envp.len() as u64;
-		let auxv: &[(u64, u64)] = &[(AT_PAGESZ, 4096)];
-		let auxv_len = auxv.len() as u64;
-
 		let mut sp = default_sp;
+		let mut push = |machine: &mut Self, bytes: &[u8]| -> Result<u64, MachineError> {
+			sp = sp.checked_sub(bytes.len() as u64).ok_or(BadAddress)?;
+			machine.write_memory(sp, bytes)?;
+			Ok(sp)
+		};
+
+		let platform = push(self, auxv.platform.to_bytes_with_nul())?;
+		let execfn = push(self, auxv.execfn.to_bytes_with_nul())?;
+		let random = push(self, &auxv.random.unwrap_or_default())?;
+		let argv: Vec<u64> = argv
+			.into_iter()
+			.map(|arg| push(self, arg.to_bytes_with_nul()))
+			.collect::<Result<_, _>>()?;
+		let envp: Vec<u64> = envp
+			.into_iter()
+			.map(|var| push(self, var.to_bytes_with_nul()))
+			.collect::<Result<_, _>>()?;
+		let entries = auxv.entries(platform, execfn, random);
 
-		sp -= (1 + argc + 1 + envp_len + 1 + (auxv_len + 1) * 2) * 8;
-		let address_init = sp;
+		let num_words = 1 + argv.len() + 1 + envp.len() + 1 + (entries.len() + 1) * 2;
+		let address_init = sp
+			.checked_sub(num_words as u64 * 8)
+			.map(|address| address & !(STACK_ALIGN - 1))
+			.ok_or(BadAddress)?;
 
-		let mut p = sp;
-		self.write_u64(p, argc)?;
+		let mut p = address_init;
+		self.write_u64(p, argv.len() as u64)?;
 		p += 8;
 
-		for arg in argv {
-			let bytes = arg.to_bytes();
-			sp -= bytes.len() as u64 + 1;
-			self.write_memory(sp, bytes)?;
-			self.write_u64(p, sp)?;
+		for address in argv {
+			self.write_u64(p, address)?;
 			p += 8;
 		}
-		p += 8; // Null pointer.
+		self.write_u64(p, 0)?; // Null pointer.
+		p += 8;
 
-		for arg in envp {
-			let bytes = arg.to_bytes();
-			sp -= bytes.len() as u64 + 1;
-			self.write_memory(sp, bytes)?;
-			self.write_u64(p, sp)?;
+		for address in envp {
+			self.write_u64(p, address)?;
 			p += 8;
 		}
-		p += 8; // Null pointer.
+		self.write_u64(p, 0)?; // Null pointer.
+		p += 8;
 
-		for &(key, value) in auxv {
+		for (key, value) in entries.into_iter().chain([(AT_NULL, 0)]) {
 			self.write_u64(p, key)?;
 			p += 8;
 			self.write_u64(p, value)?;
 			p += 8;
 		}
 
-		self.set_reg(Reg::SP, sp);
+		self.set_reg(Reg::SP, address_init);
 		self.set_reg(Reg::A0, address_init);
 		self.set_reg(Reg::RA, default_ra);
 		Ok(())
 	}
 
+	/// Get the amount of gas consumed by the program so far.
+	///
+	/// This is the source of time in [`VirtualTime`](crate::VirtualTime) mode. The default
+	/// implementation returns zero, i.e. the time advances only when the program sleeps.
+	fn gas_consumed(&self) -> u64 {
+		0
+	}
+
+	/// Grow the heap by `size` bytes.
+	///
+	/// Returns the previous heap top, i.e. the start of the newly allocated zero-initialized
+	/// memory region. Zero `size` queries the current heap top. The default implementation doesn't
+	/// support growing the heap.
+	fn sbrk(&mut self, size: u64) -> Result<u64, MachineError> {
+		let _ = size;
+		Err(OutOfMemory)
+	}
+
+	/// Get the memory statistics of the machine.
+	///
+	/// Reported to the program via `/proc/meminfo`, see
+	/// [`ProcFileSystem`](crate::proc::ProcFileSystem). The default implementation reports zeroes,
+	/// i.e. the statistics are unknown.
+	fn memory_stats(&self) -> MemoryStats {
+		MemoryStats::default()
+	}
+
+	/// Get the address of the instruction the program resumes from after the syscall.
+	///
+	/// Used to switch between threads. The default implementation returns `None`, i.e. the
+	/// machine doesn't support threads and `clone` syscall fails with `ENOSYS`.
+	fn next_program_counter(&self) -> Option<u64> {
+		None
+	}
+
+	/// Set the address of the instruction the program resumes from after the syscall.
+	///
+	/// `pc` is either the value previously returned by
+	/// [`next_program_counter`](Self::next_program_counter) or the code address provided by the
+	/// program, e.g. the address of a signal handler. Only called if `next_program_counter` returns
+	/// `Some`.
+	fn set_next_program_counter(&mut self, pc: u64) {
+		let _ = pc;
+	}
+
+	/// Get the thread pointer of the running thread.
+	///
+	/// Used to switch between threads. The default implementation returns `None`, i.e. the machine
+	/// has no thread pointer and `clone` syscall fails with `EINVAL` if `CLONE_SETTLS` flag is set.
+	fn thread_pointer(&self) -> Option<u64> {
+		None
+	}
+
+	/// Set the thread pointer of the running thread.
+	///
+	/// Only called if [`thread_pointer`](Self::thread_pointer) returns `Some`.
+	fn set_thread_pointer(&mut self, tp: u64) {
+		let _ = tp;
+	}
+
+	/// Get the address signal handlers return to.
+	///
+	/// The code at this address is expected to invoke `rt_sigreturn` syscall. The default
+	/// implementation returns `None`, i.e. the machine doesn't support signal handlers and the
+	/// signals that have handlers installed are treated as if they have default disposition.
+	fn signal_trampoline(&self) -> Option<u64> {
+		None
+	}
+
 	/// Get register value.
 	fn reg(&self, name: Reg) -> u64;
 
@@ -149,11 +231,164 @@ pub trait Machine {
 	fn write_memory(&mut self, address: u64, slice: &[u8]) -> Result<(), MachineError>;
 }
 
+/// Auxiliary vector that [`Machine::init`] passes to the program.
+///
+/// See [getauxval(3)](https://man7.org/linux/man-pages/man3/getauxval.3.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuxVec {
+	/// `AT_PAGESZ`.
+	pub page_size: u64,
+	/// `AT_HWCAP`.
+	pub hwcap: u64,
+	/// `AT_CLKTCK`.
+	pub clock_ticks: u64,
+	/// `AT_UID`.
+	///
+	/// The IDs and `AT_SECURE` are filled from
+	/// [`KernelState::credentials`](crate::KernelState::credentials) by
+	/// [`Kernel::init`](crate::Kernel::init) when unset, [`Machine::init`] writes zeroes instead.
+	pub uid: Option<u32>,
+	/// `AT_EUID`.
+	pub euid: Option<u32>,
+	/// `AT_GID`.
+	pub gid: Option<u32>,
+	/// `AT_EGID`.
+	pub egid: Option<u32>,
+	/// `AT_SECURE`.
+	pub secure: Option<bool>,
+	/// The bytes `AT_RANDOM` points to.
+	///
+	/// [`Kernel::init`](crate::Kernel::init) draws them from
+	/// [`KernelState::random`](crate::KernelState::random) when unset, [`Machine::init`] writes
+	/// zero bytes instead.
+	pub random: Option<[u8; 16]>,
+	/// The string `AT_EXECFN` points to.
+	pub execfn: CString,
+	/// The string `AT_PLATFORM` points to.
+	pub platform: CString,
+}
+
+impl AuxVec {
+	/// Create aux vector for PolkaVM machine with unset IDs and random bytes.
+	pub fn new() -> Self {
+		Self {
+			page_size: PAGE_SIZE,
+			hwcap: DEFAULT_HWCAP,
+			clock_ticks: CLOCK_TICKS,
+			uid: None,
+			euid: None,
+			gid: None,
+			egid: None,
+			secure: None,
+			random: None,
+			execfn: CString::default(),
+			platform: c"riscv64".into(),
+		}
+	}
+
+	/// Set `AT_HWCAP`.
+	pub fn with_hwcap(mut self, hwcap: u64) -> Self {
+		self.hwcap = hwcap;
+		self
+	}
+
+	/// Set `AT_CLKTCK`.
+	pub fn with_clock_ticks(mut self, clock_ticks: u64) -> Self {
+		self.clock_ticks = clock_ticks;
+		self
+	}
+
+	/// Set `AT_UID` and `AT_EUID`.
+	pub fn with_uid(mut self, uid: u32, euid: u32) -> Self {
+		self.uid = Some(uid);
+		self.euid = Some(euid);
+		self
+	}
+
+	/// Set `AT_GID` and `AT_EGID`.
+	pub fn with_gid(mut self, gid: u32, egid: u32) -> Self {
+		self.gid = Some(gid);
+		self.egid = Some(egid);
+		self
+	}
+
+	/// Set `AT_SECURE`.
+	pub fn with_secure(mut self, secure: bool) -> Self {
+		self.secure = Some(secure);
+		self
+	}
+
+	/// Set the bytes `AT_RANDOM` points to.
+	pub fn with_random(mut self, random: [u8; 16]) -> Self {
+		self.random = Some(random);
+		self
+	}
+
+	/// Set the string `AT_EXECFN` points to.
+	pub fn with_execfn(mut self, execfn: &CStr) -> Self {
+		self.execfn = execfn.into();
+		self
+	}
+
+	/// Set the string `AT_PLATFORM` points to.
+	pub fn with_platform(mut self, platform: &CStr) -> Self {
+		self.platform = platform.into();
+		self
+	}
+
+	/// Get the entries given the addresses of the strings and the random bytes.
+	fn entries(&self, platform: u64, execfn: u64, random: u64) -> [(u64, u64); 11] {
+		[
+			(AT_HWCAP, self.hwcap),
+			(AT_PAGESZ, self.page_size),
+			(AT_CLKTCK, self.clock_ticks),
+			(AT_UID, self.uid.unwrap_or_default().into()),
+			(AT_EUID, self.euid.unwrap_or_default().into()),
+			(AT_GID, self.gid.unwrap_or_default().into()),
+			(AT_EGID, self.egid.unwrap_or_default().into()),
+			(AT_SECURE, self.secure.unwrap_or_default().into()),
+			(AT_RANDOM, random),
+			(AT_EXECFN, execfn),
+			(AT_PLATFORM, platform),
+		]
+	}
+}
+
+impl Default for AuxVec {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// RISC-V psABI stack alignment.
+const STACK_ALIGN: u64 = 16;
+
+/// `AT_HWCAP` bits of `rv64emac`, one bit per ISA extension letter.
+const DEFAULT_HWCAP: u64 = isa_bit(b'e') | isa_bit(b'm') | isa_bit(b'a') | isa_bit(b'c');
+
+/// The number of clock ticks per second (`sysconf(_SC_CLK_TCK)`).
+const CLOCK_TICKS: u64 = 100;
+
+const fn isa_bit(letter: u8) -> u64 {
+	1 << (letter - b'a')
+}
+
+/// Machine memory statistics in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+	/// The total amount of memory available to the program.
+	pub total: u64,
+	/// The amount of memory that is not used yet.
+	pub free: u64,
+}
+
 /// Machine-specific error.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MachineError {
 	/// Memory access error.
 	BadAddress,
+	/// Memory allocation error.
+	OutOfMemory,
 }
 
 impl core::fmt::Display for MachineError {
@@ -166,8 +401,11 @@ impl core::fmt::Display for MachineError {
 impl std::error::Error for MachineError {}
 
 impl From<MachineError> for crate::Error {
-	fn from(_e: MachineError) -> Self {
-		Self(EFAULT)
+	fn from(e: MachineError) -> Self {
+		match e {
+			BadAddress => Self(EFAULT),
+			OutOfMemory => Self(ENOMEM),
+		}
 	}
 }
 
@@ -189,3 +427,95 @@ pub enum Reg {
 	A4 = 11,
 	A5 = 12,
 }
+
+impl Reg {
+	/// All registers in the order of their indices.
+	pub const ALL: [Self; 13] = [
+		Self::RA,
+		Self::SP,
+		Self::T0,
+		Self::T1,
+		Self::T2,
+		Self::S0,
+		Self::S1,
+		Self::A0,
+		Self::A1,
+		Self::A2,
+		Self::A3,
+		Self::A4,
+		Self::A5,
+	];
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{in_memory::InMemoryFileSystem, testing::*};
+	use alloc::vec;
+
+	#[test]
+	fn initial_stack_layout() {
+		let mut machine = TestContext::new(InMemoryFileSystem::new());
+		let auxv = AuxVec::new()
+			.with_uid(1000, 1001)
+			.with_gid(5, 5)
+			.with_secure(true)
+			.with_random([7; 16])
+			.with_execfn(c"/bin/program");
+		let argv = [c"program", c"-v"];
+		machine.init(MEMORY_END, 0x1234, argv, [c"HOME=/"], &auxv).unwrap();
+		let sp = machine.reg(Reg::SP);
+		assert_eq!(sp, machine.reg(Reg::A0));
+		assert_eq!(0x1234, machine.reg(Reg::RA));
+		let (argv, envp, entries) = machine.read_initial_stack();
+		assert_eq!(vec![CString::from(c"program"), c"-v".into()], argv);
+		assert_eq!(vec![CString::from(c"HOME=/")], envp);
+		assert_eq!(11, entries.len());
+		assert_eq!(PAGE_SIZE, entries[&AT_PAGESZ]);
+		assert_eq!(DEFAULT_HWCAP, entries[&AT_HWCAP]);
+		assert_eq!(CLOCK_TICKS, entries[&AT_CLKTCK]);
+		assert_eq!(
+			[1000, 1001, 5, 5, 1],
+			[AT_UID, AT_EUID, AT_GID, AT_EGID, AT_SECURE].map(|key| entries[&key])
+		);
+		assert_eq!(Ok(vec![7; 16]), machine.read_memory(entries[&AT_RANDOM], 16));
+		assert_eq!(Ok(c"/bin/program".into()), machine.read_cstring(entries[&AT_EXECFN], 64));
+		assert_eq!(Ok(c"riscv64".into()), machine.read_cstring(entries[&AT_PLATFORM], 64));
+		// The strings are above the pointers.
+		assert!(entries[&AT_RANDOM] > sp && entries[&AT_PLATFORM] < MEMORY_END);
+	}
+
+	#[test]
+	fn unset_fields_are_zero() {
+		let mut machine = TestContext::new(InMemoryFileSystem::new());
+		machine.init(MEMORY_END, 0, [c"program"], [], &AuxVec::new()).unwrap();
+		let (_, envp, entries) = machine.read_initial_stack();
+		assert!(envp.is_empty());
+		assert_eq!([0; 5], [AT_UID, AT_EUID, AT_GID, AT_EGID, AT_SECURE].map(|key| entries[&key]));
+		assert_eq!(Ok(vec![0; 16]), machine.read_memory(entries[&AT_RANDOM], 16));
+		assert_eq!(Ok(CString::default()), machine.read_cstring(entries[&AT_EXECFN], 64));
+	}
+
+	#[test]
+	fn stack_pointer_is_aligned() {
+		let args = [c"a", c"bb", c"ccc", c"dddd", c"eeeee"];
+		for num_args in 0..args.len() {
+			for default_sp in MEMORY_END - 7..=MEMORY_END {
+				let mut machine = TestContext::new(InMemoryFileSystem::new());
+				let argv = args[..num_args].iter().copied();
+				machine.init(default_sp, 0, argv, [c"X=1"], &AuxVec::new()).unwrap();
+				assert_eq!(0, machine.reg(Reg::SP) % STACK_ALIGN);
+				assert_eq!(num_args, machine.read_initial_stack().0.len());
+			}
+		}
+	}
+
+	#[test]
+	fn stack_overflow_fails() {
+		let mut machine = TestContext::new(InMemoryFileSystem::new());
+		let result = machine.init(MEMORY_START + 64, 0, [c"program"], [], &AuxVec::new());
+		assert_eq!(Err(BadAddress), result);
+		let result = machine.init(4, 0, [c"program"], [], &AuxVec::new());
+		assert_eq!(Err(BadAddress), result);
+	}
+}
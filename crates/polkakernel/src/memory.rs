@@ -0,0 +1,398 @@
+use alloc::{collections::BTreeMap, ffi::CString, vec::Vec};
+
+use crate::{libc::*, Error, Machine};
+
+/// Virtual memory area.
+///
+/// See [mmap(2)](https://man7.org/linux/man-pages/man2/mmap.2.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+	/// The length of the area in bytes.
+	///
+	/// Always a multiple of the page size.
+	pub length: u64,
+	/// Memory protection flags (`PROT_*`).
+	///
+	/// These are recorded but not enforced.
+	pub prot: u64,
+	/// Mapping flags (`MAP_*`).
+	pub flags: u64,
+	/// The file which contents were copied into the area.
+	///
+	/// `None` for anonymous mappings.
+	pub file: Option<MappedFile>,
+}
+
+/// The source of the file-backed [`Mapping`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedFile {
+	/// Normalized file path.
+	pub path: CString,
+	/// File offset that corresponds to the start of the area.
+	pub offset: u64,
+}
+
+/// Virtual memory manager.
+///
+/// Serves anonymous mappings and the program break out of the heap regions allocated via
+/// [`Machine::sbrk`]. Unmapped memory is zeroed right away, hence new mappings never need
+/// initialization. The program break area starts at the heap top and can only grow while the
+/// memory after it is not mapped.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryMap {
+	/// Heap regions allocated via [`Machine::sbrk`].
+	///
+	/// Maps start address to end address. Adjacent regions are merged.
+	pub regions: BTreeMap<u64, u64>,
+	/// Active mappings by start address.
+	pub mappings: BTreeMap<u64, Mapping>,
+	/// The start of the program break area or zero if not initialized yet.
+	pub brk_start: u64,
+	/// The current program break.
+	pub brk: u64,
+}
+
+impl MemoryMap {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create new mapping.
+	///
+	/// `address` is a hint unless `MAP_FIXED` or `MAP_FIXED_NOREPLACE` flag is set. The area is
+	/// zero-initialized, i.e. copying the contents of the `file` is up to the caller.
+	pub fn map<M: Machine + ?Sized>(
+		&mut self,
+		machine: &mut M,
+		address: u64,
+		length: u64,
+		prot: u64,
+		flags: u64,
+		file: Option<MappedFile>,
+	) -> Result<u64, Error> {
+		if length == 0 {
+			return Err(Error(EINVAL));
+		}
+		let length = page_align_up(length).ok_or(Error(ENOMEM))?;
+		let address = if flags & (MAP_FIXED | MAP_FIXED_NOREPLACE) != 0 {
+			if !address.is_multiple_of(PAGE_SIZE) {
+				return Err(Error(EINVAL));
+			}
+			let end = address.checked_add(length).ok_or(Error(ENOMEM))?;
+			if !self.is_owned(address, end) {
+				return Err(Error(ENOMEM));
+			}
+			if self.overlaps(address, end) {
+				if flags & MAP_FIXED_NOREPLACE != 0 {
+					return Err(Error(EEXIST));
+				}
+				self.unmap(machine, address, length)?;
+			}
+			address
+		} else {
+			self.allocate(machine, length)?
+		};
+		self.mappings.insert(address, Mapping { length, prot, flags, file });
+		Ok(address)
+	}
+
+	/// Remove all mappings in the specified address range.
+	pub fn unmap<M: Machine + ?Sized>(
+		&mut self,
+		machine: &mut M,
+		address: u64,
+		length: u64,
+	) -> Result<(), Error> {
+		if !address.is_multiple_of(PAGE_SIZE) || length == 0 {
+			return Err(Error(EINVAL));
+		}
+		let length = page_align_up(length).ok_or(Error(EINVAL))?;
+		let end = address.checked_add(length).ok_or(Error(EINVAL))?;
+		self.split_at(address);
+		self.split_at(end);
+		let unmapped: Vec<u64> =
+			self.mappings.range(address..end).map(|(start, _)| *start).collect();
+		for start in unmapped {
+			if let Some(mapping) = self.mappings.remove(&start) {
+				zero_memory(machine, start, mapping.length)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Change protection flags of all mappings in the specified address range.
+	///
+	/// Fails with `ENOMEM` if some pages in the range are not mapped.
+	pub fn protect(&mut self, address: u64, length: u64, prot: u64) -> Result<(), Error> {
+		if !address.is_multiple_of(PAGE_SIZE) {
+			return Err(Error(EINVAL));
+		}
+		let length = page_align_up(length).ok_or(Error(ENOMEM))?;
+		let end = address.checked_add(length).ok_or(Error(ENOMEM))?;
+		if !self.is_mapped(address, end) {
+			return Err(Error(ENOMEM));
+		}
+		self.split_at(address);
+		self.split_at(end);
+		for (_, mapping) in self.mappings.range_mut(address..end) {
+			mapping.prot = prot;
+		}
+		Ok(())
+	}
+
+	/// Synchronize file-backed mappings in the specified address range with the underlying files.
+	///
+	/// This is a no-op apart from argument validation because shared writable file mappings are
+	/// not supported.
+	///
+	/// See [msync(2)](https://man7.org/linux/man-pages/man2/msync.2.html).
+	pub fn sync(&self, address: u64, length: u64, flags: u64) -> Result<(), Error> {
+		if !address.is_multiple_of(PAGE_SIZE) ||
+			flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0 ||
+			flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+		{
+			return Err(Error(EINVAL));
+		}
+		let length = page_align_up(length).ok_or(Error(ENOMEM))?;
+		let end = address.checked_add(length).ok_or(Error(ENOMEM))?;
+		if !self.is_mapped(address, end) {
+			return Err(Error(ENOMEM));
+		}
+		Ok(())
+	}
+
+	/// Set the program break.
+	///
+	/// Returns the new program break on success and the current one on failure.
+	///
+	/// See [brk(2)](https://man7.org/linux/man-pages/man2/brk.2.html).
+	pub fn set_brk<M: Machine + ?Sized>(&mut self, machine: &mut M, brk: u64) -> u64 {
+		if self.brk_start == 0 {
+			// The program break area starts at the current heap top.
+			match machine.sbrk(0).ok().and_then(page_align_up) {
+				Some(top) => {
+					self.brk_start = top;
+					self.brk = top;
+				},
+				None => return 0,
+			}
+		}
+		if brk < self.brk_start {
+			return self.brk;
+		}
+		let (Some(old_end), Some(new_end)) = (page_align_up(self.brk), page_align_up(brk)) else {
+			return self.brk;
+		};
+		if !self.is_brk_intact(old_end) {
+			// `munmap` or `MAP_FIXED` has replaced (a part of) the program break area.
+			return self.brk;
+		}
+		if new_end > old_end {
+			if self.overlaps(old_end, new_end) || self.extend(machine, old_end, new_end).is_err() {
+				return self.brk;
+			}
+			let last = self.mappings.range_mut(self.brk_start..old_end).next_back();
+			match last {
+				Some((_, mapping)) if mapping.prot == PROT_READ | PROT_WRITE =>
+					mapping.length += new_end - old_end,
+				_ => {
+					self.mappings.insert(
+						old_end,
+						Mapping {
+							length: new_end - old_end,
+							prot: PROT_READ | PROT_WRITE,
+							flags: MAP_PRIVATE | MAP_ANONYMOUS,
+							file: None,
+						},
+					);
+				},
+			}
+		} else if new_end < old_end {
+			if zero_memory(machine, new_end, old_end - new_end).is_err() {
+				return self.brk;
+			}
+			self.split_at(new_end);
+			let removed: Vec<u64> =
+				self.mappings.range(new_end..old_end).map(|(start, _)| *start).collect();
+			for start in removed {
+				self.mappings.remove(&start);
+			}
+		}
+		self.brk = brk;
+		brk
+	}
+
+	/// Check that the program break area up to `end` is still covered by its own mappings.
+	///
+	/// The area consists of adjacent anonymous mappings that `mprotect` may have split.
+	fn is_brk_intact(&self, end: u64) -> bool {
+		let mut next = self.brk_start;
+		for (&address, mapping) in self.mappings.range(self.brk_start..end) {
+			if address != next ||
+				mapping.flags != MAP_PRIVATE | MAP_ANONYMOUS ||
+				mapping.file.is_some()
+			{
+				return false;
+			}
+			next = address + mapping.length;
+		}
+		next == end
+	}
+
+	/// Find unmapped address range of the specified length growing the heap if necessary.
+	fn allocate<M: Machine + ?Sized>(
+		&mut self,
+		machine: &mut M,
+		length: u64,
+	) -> Result<u64, Error> {
+		if let Some(address) = self.find_free(length) {
+			return Ok(address);
+		}
+		self.grow(machine, length)?;
+		self.find_free(length).ok_or(Error(ENOMEM))
+	}
+
+	/// First-fit search of an unmapped address range in the heap regions.
+	fn find_free(&self, length: u64) -> Option<u64> {
+		for (&region_start, &region_end) in self.regions.iter() {
+			let mut start = region_start;
+			for (&address, mapping) in self.mappings.range(region_start..region_end) {
+				if address - start >= length {
+					return Some(start);
+				}
+				start = address + mapping.length;
+			}
+			if region_end - start >= length {
+				return Some(start);
+			}
+		}
+		None
+	}
+
+	/// Allocate new heap region that is at least `length` bytes long.
+	fn grow<M: Machine + ?Sized>(&mut self, machine: &mut M, length: u64) -> Result<(), Error> {
+		let top = machine.sbrk(0)?;
+		let start = page_align_up(top).ok_or(Error(ENOMEM))?;
+		let size = (start - top).checked_add(length).ok_or(Error(ENOMEM))?;
+		let old_top = machine.sbrk(size)?;
+		if old_top != top {
+			// Somebody else has allocated the memory in the meantime.
+			return Err(Error(ENOMEM));
+		}
+		self.add_region(start, start + length);
+		Ok(())
+	}
+
+	/// Make sure that the address range right after `end` up to `new_end` belongs to the heap.
+	fn extend<M: Machine + ?Sized>(
+		&mut self,
+		machine: &mut M,
+		end: u64,
+		new_end: u64,
+	) -> Result<(), Error> {
+		let owned_end = match self.regions.range(..=end).next_back() {
+			Some((_, &region_end)) if region_end >= end => region_end,
+			_ => end,
+		};
+		if owned_end >= new_end {
+			return Ok(());
+		}
+		let top = machine.sbrk(0)?;
+		if top > owned_end {
+			// The memory in between is used by somebody else.
+			return Err(Error(ENOMEM));
+		}
+		let old_top = machine.sbrk(new_end - top)?;
+		if old_top != top {
+			return Err(Error(ENOMEM));
+		}
+		self.add_region(owned_end, new_end);
+		Ok(())
+	}
+
+	fn add_region(&mut self, mut start: u64, mut end: u64) {
+		if let Some((&prev_start, &prev_end)) = self.regions.range(..start).next_back() {
+			if prev_end == start {
+				start = prev_start;
+			}
+		}
+		if let Some(next_end) = self.regions.remove(&end) {
+			end = next_end;
+		}
+		self.regions.insert(start, end);
+	}
+
+	/// Split the mapping that contains the specified address into two.
+	fn split_at(&mut self, address: u64) {
+		let Some((&start, mapping)) = self.mappings.range_mut(..address).next_back() else {
+			return;
+		};
+		let end = start + mapping.length;
+		if address >= end {
+			return;
+		}
+		let mut tail = mapping.clone();
+		mapping.length = address - start;
+		tail.length = end - address;
+		if let Some(ref mut file) = tail.file {
+			file.offset += mapping.length;
+		}
+		self.mappings.insert(address, tail);
+	}
+
+	/// Check if any page in the address range is mapped.
+	fn overlaps(&self, start: u64, end: u64) -> bool {
+		if self.mappings.range(start..end).next().is_some() {
+			return true;
+		}
+		self.mappings
+			.range(..start)
+			.next_back()
+			.is_some_and(|(&address, mapping)| address + mapping.length > start)
+	}
+
+	/// Check if all pages in the address range are mapped.
+	fn is_mapped(&self, start: u64, end: u64) -> bool {
+		let mut next = start;
+		if let Some((&address, mapping)) = self.mappings.range(..=start).next_back() {
+			next = next.max(address + mapping.length);
+		}
+		for (&address, mapping) in self.mappings.range(start..end) {
+			if address > next {
+				return false;
+			}
+			next = next.max(address + mapping.length);
+		}
+		next >= end
+	}
+
+	/// Check if the address range belongs to the heap.
+	fn is_owned(&self, start: u64, end: u64) -> bool {
+		self.regions
+			.range(..=start)
+			.next_back()
+			.is_some_and(|(_, &region_end)| region_end >= end)
+	}
+}
+
+fn zero_memory<M: Machine + ?Sized>(
+	machine: &mut M,
+	mut address: u64,
+	length: u64,
+) -> Result<(), Error> {
+	static ZEROES: [u8; PAGE_SIZE as usize] = [0_u8; PAGE_SIZE as usize];
+	let end = address + length;
+	while address < end {
+		let n = (end - address).min(PAGE_SIZE);
+		machine.write_memory(address, &ZEROES[..n as usize])?;
+		address += n;
+	}
+	Ok(())
+}
+
+pub(crate) const fn page_align_up(address: u64) -> Option<u64> {
+	match address.checked_add(PAGE_SIZE - 1) {
+		Some(x) => Some(x & !(PAGE_SIZE - 1)),
+		None => None,
+	}
+}
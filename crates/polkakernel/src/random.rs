@@ -0,0 +1,147 @@
+/// ChaCha20-based cryptographically secure pseudo-random number generator.
+///
+/// The output is fully determined by the seed, i.e. the program observes the same random numbers
+/// each time it runs with the same seed. The seed should be provided by the embedder, e.g. on
+/// CoreVM it can be derived from the work package.
+///
+/// See [RFC 8439](https://datatracker.ietf.org/doc/html/rfc8439).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChaChaRng {
+	/// ChaCha20 key.
+	pub key: [u32; 8],
+	/// The number of the next block.
+	pub counter: u64,
+	/// The current block.
+	pub block: [u8; BLOCK_LEN],
+	/// The number of the current block bytes that have been consumed.
+	pub position: usize,
+}
+
+impl ChaChaRng {
+	/// Create new generator from the seed.
+	pub fn new(seed: [u8; 32]) -> Self {
+		let mut key = [0_u32; 8];
+		for (word, chunk) in key.iter_mut().zip(seed.chunks_exact(4)) {
+			*word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+		}
+		Self { key, counter: 0, block: [0_u8; BLOCK_LEN], position: BLOCK_LEN }
+	}
+
+	/// Fill the buffer with random bytes.
+	pub fn fill(&mut self, buf: &mut [u8]) {
+		let mut offset = 0;
+		while offset < buf.len() {
+			if self.position == BLOCK_LEN {
+				self.block = chacha20_block(&self.key, self.counter, 0);
+				self.counter = self.counter.wrapping_add(1);
+				self.position = 0;
+			}
+			let n = (buf.len() - offset).min(BLOCK_LEN - self.position);
+			buf[offset..offset + n].copy_from_slice(&self.block[self.position..self.position + n]);
+			// Don't keep the output around.
+			self.block[self.position..self.position + n].fill(0_u8);
+			self.position += n;
+			offset += n;
+		}
+	}
+}
+
+impl Default for ChaChaRng {
+	fn default() -> Self {
+		Self::new([0_u8; 32])
+	}
+}
+
+/// ChaCha20 block length in bytes.
+const BLOCK_LEN: usize = 64;
+
+/// Compute ChaCha20 block with 64-bit counter and 64-bit nonce.
+fn chacha20_block(key: &[u32; 8], counter: u64, nonce: u64) -> [u8; BLOCK_LEN] {
+	// "expand 32-byte k"
+	let mut input = [0_u32; 16];
+	input[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+	input[4..12].copy_from_slice(key);
+	input[12] = counter as u32;
+	input[13] = (counter >> 32) as u32;
+	input[14] = nonce as u32;
+	input[15] = (nonce >> 32) as u32;
+	let mut state = input;
+	for _ in 0..10 {
+		quarter_round(&mut state, 0, 4, 8, 12);
+		quarter_round(&mut state, 1, 5, 9, 13);
+		quarter_round(&mut state, 2, 6, 10, 14);
+		quarter_round(&mut state, 3, 7, 11, 15);
+		quarter_round(&mut state, 0, 5, 10, 15);
+		quarter_round(&mut state, 1, 6, 11, 12);
+		quarter_round(&mut state, 2, 7, 8, 13);
+		quarter_round(&mut state, 3, 4, 9, 14);
+	}
+	let mut block = [0_u8; BLOCK_LEN];
+	for (i, chunk) in block.chunks_exact_mut(4).enumerate() {
+		chunk.copy_from_slice(&state[i].wrapping_add(input[i]).to_le_bytes());
+	}
+	block
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+	state[a] = state[a].wrapping_add(state[b]);
+	state[d] = (state[d] ^ state[a]).rotate_left(16);
+	state[c] = state[c].wrapping_add(state[d]);
+	state[b] = (state[b] ^ state[c]).rotate_left(12);
+	state[a] = state[a].wrapping_add(state[b]);
+	state[d] = (state[d] ^ state[a]).rotate_left(8);
+	state[c] = state[c].wrapping_add(state[d]);
+	state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn block_function_test_vector() {
+		// RFC 8439, section 2.3.2. The 32-bit counter and the first nonce word form our 64-bit
+		// counter, the remaining nonce words form our 64-bit nonce.
+		let mut key = [0_u32; 8];
+		for (i, word) in key.iter_mut().enumerate() {
+			let i = 4 * i as u8;
+			*word = u32::from_le_bytes([i, i + 1, i + 2, i + 3]);
+		}
+		let block = chacha20_block(&key, 1 | (0x09000000 << 32), 0x4a000000);
+		let expected = [
+			0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20,
+			0x71, 0xc4, 0xc7, 0xd1, 0xf4, 0xc7, 0x33, 0xc0, 0x68, 0x03, 0x04, 0x22, 0xaa, 0x9a,
+			0xc3, 0xd4, 0x6c, 0x4e, 0xd2, 0x82, 0x64, 0x46, 0x07, 0x9f, 0xaa, 0x09, 0x14, 0xc2,
+			0xd7, 0x05, 0xd9, 0x8b, 0x02, 0xa2, 0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9,
+			0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c, 0x4e,
+		];
+		assert_eq!(expected, block);
+	}
+
+	#[test]
+	fn rng_test_vectors() {
+		// RFC 8439, appendix A.1, test vectors #1 and #2 (all-zero key and nonce, counters 0 and
+		// 1).
+		let expected = [
+			0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86,
+			0xbd, 0x28, 0xbd, 0xd2, 0x19, 0xb8, 0xa0, 0x8d, 0xed, 0x1a, 0xa8, 0x36, 0xef, 0xcc,
+			0x8b, 0x77, 0x0d, 0xc7, 0xda, 0x41, 0x59, 0x7c, 0x51, 0x57, 0x48, 0x8d, 0x77, 0x24,
+			0xe0, 0x3f, 0xb8, 0xd8, 0x4a, 0x37, 0x6a, 0x43, 0xb8, 0xf4, 0x15, 0x18, 0xa1, 0x1c,
+			0xc3, 0x87, 0xb6, 0x69, 0xb2, 0xee, 0x65, 0x86, 0x9f, 0x07, 0xe7, 0xbe, 0x55, 0x51,
+			0x38, 0x7a, 0x98, 0xba, 0x97, 0x7c, 0x73, 0x2d, 0x08, 0x0d, 0xcb, 0x0f, 0x29, 0xa0,
+			0x48, 0xe3, 0x65, 0x69, 0x12, 0xc6, 0x53, 0x3e, 0x32, 0xee, 0x7a, 0xed, 0x29, 0xb7,
+			0x21, 0x76, 0x9c, 0xe6, 0x4e, 0x43, 0xd5, 0x71, 0x33, 0xb0, 0x74, 0xd8, 0x39, 0xd5,
+			0x31, 0xed, 0x1f, 0x28, 0x51, 0x0a, 0xfb, 0x45, 0xac, 0xe1, 0x0a, 0x1f, 0x4b, 0x79,
+			0x4d, 0x6f,
+		];
+		let mut rng = ChaChaRng::default();
+		let mut actual = [0_u8; 128];
+		// Uneven chunks cross the block boundary.
+		let (a, rest) = actual.split_at_mut(7);
+		let (b, c) = rest.split_at_mut(70);
+		rng.fill(a);
+		rng.fill(b);
+		rng.fill(c);
+		assert_eq!(expected, actual);
+	}
+}
@@ -0,0 +1,229 @@
+use core::time::Duration;
+
+use crate::{libc::*, Reg};
+
+/// The number of signals.
+pub const NUM_SIGNALS: usize = 64;
+
+/// Signal disposition.
+///
+/// See [sigaction(2)](https://man7.org/linux/man-pages/man2/sigaction.2.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SigAction {
+	/// Handler address, `SIG_DFL` or `SIG_IGN`.
+	pub handler: u64,
+	/// `SA_*` flags.
+	pub flags: u64,
+	/// Signals blocked while the handler runs.
+	pub mask: u64,
+}
+
+/// Alternate signal stack.
+///
+/// See [sigaltstack(2)](https://man7.org/linux/man-pages/man2/sigaltstack.2.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalStack {
+	/// The lowest address of the stack.
+	pub sp: u64,
+	/// `SS_*` flags.
+	pub flags: u32,
+	/// The size of the stack in bytes.
+	pub size: u64,
+}
+
+impl SignalStack {
+	/// Disabled alternate signal stack.
+	pub const DISABLED: Self = Self { sp: 0, flags: SS_DISABLE, size: 0 };
+
+	/// Check if the stack pointer points to this stack.
+	pub const fn contains(&self, sp: u64) -> bool {
+		self.flags & SS_DISABLE == 0 && sp > self.sp && sp - self.sp <= self.size
+	}
+}
+
+impl Default for SignalStack {
+	fn default() -> Self {
+		Self::DISABLED
+	}
+}
+
+/// Real-time interval timer that sends `SIGALRM`.
+///
+/// See [setitimer(2)](https://man7.org/linux/man-pages/man2/setitimer.2.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntervalTimer {
+	/// Monotonic clock value at which the timer expires.
+	pub deadline: Duration,
+	/// Timer period or zero for one-shot timers.
+	pub interval: Duration,
+}
+
+/// Process-wide signal state.
+///
+/// Per-thread signal mask and pending signals are stored in [`Thread`](crate::Thread).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signals {
+	/// Signal dispositions indexed by signal number minus one.
+	pub actions: [SigAction; NUM_SIGNALS],
+	/// Signals sent to the process as a whole.
+	pub pending: u64,
+	/// Armed `ITIMER_REAL` timer.
+	pub alarm: Option<IntervalTimer>,
+}
+
+impl Signals {
+	pub fn new() -> Self {
+		Self { actions: [SigAction::default(); NUM_SIGNALS], pending: 0, alarm: None }
+	}
+
+	/// Get the disposition of the specified signal.
+	pub fn action(&self, signal: u8) -> &SigAction {
+		&self.actions[usize::from(signal) - 1]
+	}
+
+	/// Get the disposition of the specified signal.
+	pub fn action_mut(&mut self, signal: u8) -> &mut SigAction {
+		&mut self.actions[usize::from(signal) - 1]
+	}
+
+	/// Check if the signal is discarded upon arrival.
+	pub fn is_ignored(&self, signal: u8) -> bool {
+		match self.action(signal).handler {
+			SIG_DFL => matches!(
+				DefaultAction::of(signal),
+				DefaultAction::Ignore | DefaultAction::Stop | DefaultAction::Continue
+			),
+			SIG_IGN => signal != SIGKILL,
+			_ => false,
+		}
+	}
+}
+
+impl Default for Signals {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// The action taken when the signal with default disposition is delivered.
+///
+/// See [signal(7)](https://man7.org/linux/man-pages/man7/signal.7.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+	/// Terminate the process.
+	Terminate,
+	/// Terminate the process and dump core.
+	CoreDump,
+	/// Ignore the signal.
+	Ignore,
+	/// Stop the process.
+	///
+	/// Job control is not supported, hence the signal is ignored.
+	Stop,
+	/// Continue the process if it is stopped.
+	Continue,
+}
+
+impl DefaultAction {
+	/// Get the default action of the specified signal.
+	pub const fn of(signal: u8) -> Self {
+		match signal {
+			SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGXCPU |
+			SIGXFSZ | SIGSYS => Self::CoreDump,
+			SIGCHLD | SIGURG | SIGWINCH => Self::Ignore,
+			SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => Self::Stop,
+			SIGCONT => Self::Continue,
+			_ => Self::Terminate,
+		}
+	}
+}
+
+/// Get signal set bit that corresponds to the signal.
+pub(crate) const fn sig_bit(signal: u8) -> u64 {
+	1_u64 << (signal - 1)
+}
+
+/// Signals that can't be blocked.
+pub(crate) const UNBLOCKABLE: u64 = sig_bit(SIGKILL) | sig_bit(SIGSTOP);
+
+/// Get the index of the register in `__gregs` array of `mcontext_t`.
+///
+/// The array is indexed by RISC-V register number, except for the first element that stores the
+/// program counter.
+pub(crate) const fn greg_index(reg: Reg) -> usize {
+	match reg {
+		Reg::RA => 1,
+		Reg::SP => 2,
+		Reg::T0 => 5,
+		Reg::T1 => 6,
+		Reg::T2 => 7,
+		Reg::S0 => 8,
+		Reg::S1 => 9,
+		Reg::A0 => 10,
+		Reg::A1 => 11,
+		Reg::A2 => 12,
+		Reg::A3 => 13,
+		Reg::A4 => 14,
+		Reg::A5 => 15,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn signal_bits() {
+		assert_eq!(1, sig_bit(SIGHUP));
+		assert_eq!(1 << 63, sig_bit(64));
+		assert_eq!(0, UNBLOCKABLE & sig_bit(SIGTERM));
+		assert_ne!(0, UNBLOCKABLE & sig_bit(SIGKILL));
+		assert_ne!(0, UNBLOCKABLE & sig_bit(SIGSTOP));
+	}
+
+	#[test]
+	fn default_actions() {
+		assert_eq!(DefaultAction::Terminate, DefaultAction::of(SIGALRM));
+		assert_eq!(DefaultAction::Terminate, DefaultAction::of(SIGKILL));
+		assert_eq!(DefaultAction::CoreDump, DefaultAction::of(SIGSEGV));
+		assert_eq!(DefaultAction::Ignore, DefaultAction::of(SIGCHLD));
+		assert_eq!(DefaultAction::Stop, DefaultAction::of(SIGTSTP));
+		assert_eq!(DefaultAction::Continue, DefaultAction::of(SIGCONT));
+		assert_eq!(DefaultAction::Terminate, DefaultAction::of(64));
+	}
+
+	#[test]
+	fn ignored_signals() {
+		let mut signals = Signals::new();
+		assert!(!signals.is_ignored(SIGTERM));
+		assert!(signals.is_ignored(SIGCHLD));
+		assert!(signals.is_ignored(SIGSTOP));
+		assert!(signals.is_ignored(SIGCONT));
+		signals.action_mut(SIGTERM).handler = SIG_IGN;
+		assert!(signals.is_ignored(SIGTERM));
+		signals.action_mut(SIGKILL).handler = SIG_IGN;
+		assert!(!signals.is_ignored(SIGKILL));
+		signals.action_mut(SIGCHLD).handler = 0x1000;
+		assert!(!signals.is_ignored(SIGCHLD));
+		assert_eq!(0x1000, signals.actions[usize::from(SIGCHLD) - 1].handler);
+	}
+
+	#[test]
+	fn signal_stack_bounds() {
+		assert!(!SignalStack::DISABLED.contains(0));
+		let stack = SignalStack { sp: 0x1000, flags: 0, size: 0x100 };
+		assert!(!stack.contains(0x1000));
+		assert!(stack.contains(0x1001));
+		assert!(stack.contains(0x1100));
+		assert!(!stack.contains(0x1101));
+		assert!(!SignalStack { flags: SS_DISABLE, ..stack }.contains(0x1080));
+	}
+
+	#[test]
+	fn greg_indices_follow_register_numbers() {
+		assert_eq!(1, greg_index(Reg::RA));
+		assert_eq!(2, greg_index(Reg::SP));
+		assert_eq!(10, greg_index(Reg::A0));
+		assert_eq!(15, greg_index(Reg::A5));
+	}
+}
@@ -0,0 +1,269 @@
+//! Test context that runs the kernel on a flat memory and an in-memory file system.
+
+use alloc::{collections::BTreeMap, ffi::CString, vec, vec::Vec};
+use core::{ffi::CStr, time::Duration};
+
+use crate::{
+	in_memory::{InMemoryFd, InMemoryFileSystem},
+	Clock, ClockId, Environment, Error, FileSystem, Kernel, KernelState, Machine, MachineError,
+	Metadata, Reg, SeekFrom,
+};
+
+/// Machine, environment, clock and file system in one.
+///
+/// The memory spans [`MEMORY_START`]..[`MEMORY_END`], the monotonic and real-time clocks advance
+/// only when the program sleeps or the test advances them.
+pub(crate) struct TestContext {
+	pub regs: [u64; Reg::ALL.len()],
+	pub memory: Vec<u8>,
+	pub fs: InMemoryFileSystem,
+	pub stdout: Vec<u8>,
+	pub clock: Duration,
+}
+
+/// The lowest valid address.
+pub(crate) const MEMORY_START: u64 = 0x10000;
+
+/// The address after the highest valid one.
+pub(crate) const MEMORY_END: u64 = 0x20000;
+
+impl TestContext {
+	pub fn new(fs: InMemoryFileSystem) -> Self {
+		Self {
+			regs: [0; Reg::ALL.len()],
+			memory: vec![0; (MEMORY_END - MEMORY_START) as usize],
+			fs,
+			stdout: Vec::new(),
+			clock: Duration::from_secs(1),
+		}
+	}
+
+	/// Parse the initial stack written by [`Machine::init`].
+	///
+	/// Returns the arguments, the environment variables and the aux vector entries.
+	pub fn read_initial_stack(&mut self) -> (Vec<CString>, Vec<CString>, BTreeMap<u64, u64>) {
+		let mut p = self.reg(Reg::SP);
+		let mut next = |machine: &mut Self| {
+			let word = machine.read_u64(p).unwrap();
+			p += 8;
+			word
+		};
+		let argc = next(self);
+		let argv = (0..argc).map(|_| next(self)).collect::<Vec<_>>();
+		assert_eq!(0, next(self));
+		let mut envp = Vec::new();
+		loop {
+			match next(self) {
+				0 => break,
+				address => envp.push(address),
+			}
+		}
+		let mut auxv = BTreeMap::new();
+		loop {
+			let key = next(self);
+			let value = next(self);
+			if key == crate::libc::AT_NULL {
+				break;
+			}
+			assert_eq!(None, auxv.insert(key, value));
+		}
+		let mut read_strings = |addresses: Vec<u64>| {
+			addresses
+				.into_iter()
+				.map(|address| self.read_cstring(address, 4096).unwrap())
+				.collect()
+		};
+		(read_strings(argv), read_strings(envp), auxv)
+	}
+
+	fn slice(&mut self, address: u64, len: usize) -> Result<&mut [u8], MachineError> {
+		let start = address.checked_sub(MEMORY_START).ok_or(MachineError::BadAddress)? as usize;
+		let end = start.checked_add(len).ok_or(MachineError::BadAddress)?;
+		self.memory.get_mut(start..end).ok_or(MachineError::BadAddress)
+	}
+}
+
+impl Machine for TestContext {
+	fn reg(&self, name: Reg) -> u64 {
+		self.regs[name as usize]
+	}
+
+	fn set_reg(&mut self, name: Reg, value: u64) {
+		self.regs[name as usize] = value;
+	}
+
+	fn read_u64(&mut self, address: u64) -> Result<u64, MachineError> {
+		let mut bytes = [0; 8];
+		self.read_memory_into(address, &mut bytes)?;
+		Ok(u64::from_le_bytes(bytes))
+	}
+
+	fn read_u32(&mut self, address: u64) -> Result<u32, MachineError> {
+		let mut bytes = [0; 4];
+		self.read_memory_into(address, &mut bytes)?;
+		Ok(u32::from_le_bytes(bytes))
+	}
+
+	fn read_u16(&mut self, address: u64) -> Result<u16, MachineError> {
+		let mut bytes = [0; 2];
+		self.read_memory_into(address, &mut bytes)?;
+		Ok(u16::from_le_bytes(bytes))
+	}
+
+	fn read_u8(&mut self, address: u64) -> Result<u8, MachineError> {
+		let mut bytes = [0; 1];
+		self.read_memory_into(address, &mut bytes)?;
+		Ok(bytes[0])
+	}
+
+	fn read_memory_into(&mut self, address: u64, buffer: &mut [u8]) -> Result<(), MachineError> {
+		buffer.copy_from_slice(self.slice(address, buffer.len())?);
+		Ok(())
+	}
+
+	fn write_u64(&mut self, address: u64, value: u64) -> Result<(), MachineError> {
+		self.write_memory(address, &value.to_le_bytes())
+	}
+
+	fn write_u32(&mut self, address: u64, value: u32) -> Result<(), MachineError> {
+		self.write_memory(address, &value.to_le_bytes())
+	}
+
+	fn write_u16(&mut self, address: u64, value: u16) -> Result<(), MachineError> {
+		self.write_memory(address, &value.to_le_bytes())
+	}
+
+	fn write_u8(&mut self, address: u64, value: u8) -> Result<(), MachineError> {
+		self.write_memory(address, &[value])
+	}
+
+	fn write_memory(&mut self, address: u64, slice: &[u8]) -> Result<(), MachineError> {
+		self.slice(address, slice.len())?.copy_from_slice(slice);
+		Ok(())
+	}
+}
+
+impl Environment for TestContext {
+	fn read_from_stdin(&mut self, _buf: &mut [u8]) -> Result<usize, Error> {
+		Ok(0)
+	}
+
+	fn write_to_stdout(&mut self, data: &[u8]) -> Result<u64, Error> {
+		self.stdout.extend_from_slice(data);
+		Ok(data.len() as u64)
+	}
+
+	fn write_to_stderr(&mut self, data: &[u8]) -> Result<u64, Error> {
+		Ok(data.len() as u64)
+	}
+}
+
+impl Clock for TestContext {
+	fn now(&mut self, _clock: ClockId) -> Result<Duration, Error> {
+		Ok(self.clock)
+	}
+
+	fn resolution(&mut self, _clock: ClockId) -> Result<Duration, Error> {
+		Ok(Duration::from_nanos(1))
+	}
+
+	fn sleep(&mut self, duration: Duration) -> Result<(), Error> {
+		self.clock += duration;
+		Ok(())
+	}
+}
+
+impl FileSystem for TestContext {
+	type Fd = InMemoryFd;
+
+	fn open(&mut self, path: &CStr, flags: u64, mode: u32) -> Result<Self::Fd, Error> {
+		self.fs.open(path, flags, mode)
+	}
+
+	fn seek(&mut self, fd: &mut Self::Fd, from: SeekFrom) -> Result<u64, Error> {
+		self.fs.seek(fd, from)
+	}
+
+	fn read(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		self.fs.read(fd, buf)
+	}
+
+	fn read_at(&mut self, fd: &mut Self::Fd, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+		self.fs.read_at(fd, buf, offset)
+	}
+
+	fn write(&mut self, fd: &mut Self::Fd, buf: &[u8]) -> Result<usize, Error> {
+		self.fs.write(fd, buf)
+	}
+
+	fn truncate(&mut self, fd: &mut Self::Fd, length: u64) -> Result<(), Error> {
+		self.fs.truncate(fd, length)
+	}
+
+	fn read_dir(&mut self, fd: &mut Self::Fd, buf: &mut [u8]) -> Result<usize, Error> {
+		self.fs.read_dir(fd, buf)
+	}
+
+	fn metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		self.fs.metadata(path)
+	}
+
+	fn symlink_metadata(&mut self, path: &CStr) -> Result<Metadata, Error> {
+		self.fs.symlink_metadata(path)
+	}
+
+	fn readlink(&mut self, path: &CStr) -> Result<CString, Error> {
+		self.fs.readlink(path)
+	}
+
+	fn symlink(&mut self, target: &CStr, path: &CStr) -> Result<(), Error> {
+		self.fs.symlink(target, path)
+	}
+
+	fn link(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		self.fs.link(old_path, new_path)
+	}
+
+	fn unlink(&mut self, path: &CStr) -> Result<(), Error> {
+		self.fs.unlink(path)
+	}
+
+	fn mkdir(&mut self, path: &CStr, mode: u32) -> Result<(), Error> {
+		self.fs.mkdir(path, mode)
+	}
+
+	fn rmdir(&mut self, path: &CStr) -> Result<(), Error> {
+		self.fs.rmdir(path)
+	}
+
+	fn rename(&mut self, old_path: &CStr, new_path: &CStr) -> Result<(), Error> {
+		self.fs.rename(old_path, new_path)
+	}
+
+	fn set_owner(&mut self, path: &CStr, uid: u32, gid: u32) -> Result<(), Error> {
+		self.fs.set_owner(path, uid, gid)
+	}
+
+	fn set_times(&mut self, path: &CStr, atime: Duration, mtime: Duration) -> Result<(), Error> {
+		self.fs.set_times(path, atime, mtime)
+	}
+}
+
+/// Create kernel with the default state.
+pub(crate) fn kernel(fs: InMemoryFileSystem) -> Kernel<TestContext> {
+	Kernel::new(TestContext::new(fs), KernelState::new())
+}
+
+/// Invoke the syscall and return its outcome and `A0` register value.
+pub(crate) fn syscall(
+	kernel: &mut Kernel<TestContext>,
+	number: u64,
+	args: &[u64],
+) -> (crate::SyscallOutcome, i64) {
+	kernel.context.regs[Reg::A0 as usize] = number;
+	for (i, reg) in [Reg::A1, Reg::A2, Reg::A3, Reg::A4, Reg::A5].into_iter().enumerate() {
+		kernel.context.regs[reg as usize] = args.get(i).copied().unwrap_or(0);
+	}
+	let outcome = kernel.handle_syscall().unwrap();
+	(outcome, kernel.context.regs[Reg::A0 as usize] as i64)
+}
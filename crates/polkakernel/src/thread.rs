@@ -0,0 +1,360 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::time::Duration;
+
+use crate::{libc::ESRCH, Error, Reg, SignalStack};
+
+/// The default identifier of the main thread.
+pub const MAIN_THREAD_ID: u32 = 1;
+
+/// A thread of the user-space program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+	/// Saved register values indexed by [`Reg`].
+	///
+	/// Only valid while the thread is not running.
+	pub regs: [u64; Reg::ALL.len()],
+	/// The address of the instruction the thread resumes from.
+	///
+	/// Only valid while the thread is not running.
+	pub pc: u64,
+	/// Thread pointer, see [`Machine::thread_pointer`](crate::Machine::thread_pointer).
+	///
+	/// Only valid while the thread is not running.
+	pub tls: u64,
+	/// The address that is zeroed and woken up via futex when the thread exits.
+	///
+	/// See [set_tid_address(2)](https://man7.org/linux/man-pages/man2/set_tid_address.2.html).
+	pub clear_child_tid: u64,
+	/// Blocked signals.
+	pub blocked_signals: u64,
+	/// Signals sent to this thread specifically.
+	pub pending_signals: u64,
+	/// Alternate signal stack.
+	pub signal_stack: SignalStack,
+	/// Scheduling state.
+	pub state: ThreadState,
+	/// Whether the thread executes the syscall it was blocked in again once it runs.
+	///
+	/// The saved `A0` register holds the syscall number in this case.
+	pub restart_syscall: bool,
+}
+
+impl Thread {
+	pub fn new() -> Self {
+		Self {
+			regs: [0; Reg::ALL.len()],
+			pc: 0,
+			tls: 0,
+			clear_child_tid: 0,
+			blocked_signals: 0,
+			pending_signals: 0,
+			signal_stack: SignalStack::DISABLED,
+			state: ThreadState::Runnable,
+			restart_syscall: false,
+		}
+	}
+}
+
+impl Default for Thread {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Thread scheduling state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+	/// The thread is running or ready to run.
+	Runnable,
+	/// The thread is blocked in `FUTEX_WAIT`.
+	FutexWait {
+		/// Futex address.
+		address: u64,
+		/// The mask of `FUTEX_WAIT_BITSET`.
+		bitset: u32,
+		/// Monotonic clock value at which the wait times out.
+		deadline: Option<Duration>,
+		/// Wait order that is used to wake up threads in FIFO order.
+		seq: u64,
+	},
+	/// The thread is blocked reading from an empty pipe or writing to a full one.
+	PipeWait {
+		/// Pipe identifier.
+		pipe: u64,
+	},
+	/// The thread sleeps in `nanosleep`, `clock_nanosleep` or `ppoll`.
+	Sleep {
+		/// Monotonic clock value at which the sleep ends.
+		deadline: Duration,
+		/// The address the remaining time is written to if the sleep is interrupted.
+		///
+		/// Zero means that the remaining time isn't reported.
+		remaining_address: u64,
+	},
+}
+
+/// Deterministic cooperative scheduler.
+///
+/// The threads are switched only when the running thread blocks, yields or exits. The next thread
+/// is chosen in round-robin order of thread identifiers. Exited threads are removed right away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheduler {
+	/// The identifier of the running thread.
+	pub current: u32,
+	/// All threads by identifier.
+	pub threads: BTreeMap<u32, Thread>,
+	/// The identifier of the next thread.
+	pub next_tid: u32,
+	/// The order of the next futex wait.
+	pub next_seq: u64,
+}
+
+impl Scheduler {
+	/// Create new scheduler with only the main thread.
+	pub fn new() -> Self {
+		Self::with_main_tid(MAIN_THREAD_ID)
+	}
+
+	/// Create new scheduler with only the main thread that has the specified identifier.
+	pub fn with_main_tid(tid: u32) -> Self {
+		let mut threads = BTreeMap::new();
+		threads.insert(tid, Thread::new());
+		Self { current: tid, threads, next_tid: tid + 1, next_seq: 0 }
+	}
+
+	/// Get the running thread.
+	///
+	/// Fails with `ESRCH` if the running thread has exited already.
+	pub fn current(&self) -> Result<&Thread, Error> {
+		self.threads.get(&self.current).ok_or(Error(ESRCH))
+	}
+
+	/// Get the running thread.
+	///
+	/// Fails with `ESRCH` if the running thread has exited already.
+	pub fn current_mut(&mut self) -> Result<&mut Thread, Error> {
+		self.threads.get_mut(&self.current).ok_or(Error(ESRCH))
+	}
+
+	/// Add new thread and return its identifier.
+	pub fn spawn(&mut self, thread: Thread) -> u32 {
+		let tid = self.next_tid;
+		self.next_tid += 1;
+		self.threads.insert(tid, thread);
+		tid
+	}
+
+	/// Block the running thread until the futex at the specified address is woken up.
+	pub fn wait(
+		&mut self,
+		address: u64,
+		bitset: u32,
+		deadline: Option<Duration>,
+	) -> Result<(), Error> {
+		let seq = self.next_seq;
+		self.current_mut()?.state = ThreadState::FutexWait { address, bitset, deadline, seq };
+		self.next_seq += 1;
+		Ok(())
+	}
+
+	/// Block the running thread until the monotonic clock reaches the deadline.
+	pub fn sleep(&mut self, deadline: Duration, remaining_address: u64) -> Result<(), Error> {
+		self.current_mut()?.state = ThreadState::Sleep { deadline, remaining_address };
+		Ok(())
+	}
+
+	/// Wake up at most `count` threads waiting on the futex at the specified address.
+	///
+	/// Only the threads which wait bitset intersects with `bitset` are woken up. Returns the
+	/// identifiers of the woken up threads in FIFO order.
+	pub fn wake(&mut self, address: u64, bitset: u32, count: usize) -> Vec<u32> {
+		let mut waiters: Vec<(u64, u32)> = self
+			.threads
+			.iter()
+			.filter_map(|(tid, thread)| match thread.state {
+				ThreadState::FutexWait { address: a, bitset: b, seq, .. }
+					if a == address && b & bitset != 0 =>
+					Some((seq, *tid)),
+				_ => None,
+			})
+			.collect();
+		waiters.sort_unstable();
+		waiters.truncate(count);
+		waiters
+			.into_iter()
+			.map(|(_, tid)| {
+				if let Some(thread) = self.threads.get_mut(&tid) {
+					thread.state = ThreadState::Runnable;
+				}
+				tid
+			})
+			.collect()
+	}
+
+	/// Wake up all threads waiting for the pipe with the specified identifier.
+	pub fn wake_pipe(&mut self, pipe: u64) {
+		for thread in self.threads.values_mut() {
+			if thread.state == (ThreadState::PipeWait { pipe }) {
+				thread.state = ThreadState::Runnable;
+			}
+		}
+	}
+
+	/// Move at most `count` threads waiting on the futex at `from` address to `to` address.
+	///
+	/// Returns the number of threads moved.
+	pub fn requeue(&mut self, from: u64, to: u64, count: usize) -> usize {
+		let mut waiters: Vec<(u64, u32)> = self
+			.threads
+			.iter()
+			.filter_map(|(tid, thread)| match thread.state {
+				ThreadState::FutexWait { address, seq, .. } if address == from => Some((seq, *tid)),
+				_ => None,
+			})
+			.collect();
+		waiters.sort_unstable();
+		waiters.truncate(count);
+		for (_, tid) in waiters.iter() {
+			if let Some(Thread { state: ThreadState::FutexWait { address, .. }, .. }) =
+				self.threads.get_mut(tid)
+			{
+				*address = to;
+			}
+		}
+		waiters.len()
+	}
+
+	/// Find the next runnable thread in round-robin order.
+	///
+	/// The running thread is considered last.
+	pub fn next_runnable(&self) -> Option<u32> {
+		let is_runnable = |(tid, thread): (&u32, &Thread)| {
+			(thread.state == ThreadState::Runnable).then_some(*tid)
+		};
+		self.threads
+			.range(self.current + 1..)
+			.find_map(is_runnable)
+			.or_else(|| self.threads.range(..=self.current).find_map(is_runnable))
+	}
+
+	/// Find the blocked thread with the earliest deadline.
+	///
+	/// Both the futex waits with timeout and the sleeps have deadlines.
+	pub fn next_deadline(&self) -> Option<(u32, Duration)> {
+		self.threads
+			.iter()
+			.filter_map(|(tid, thread)| match thread.state {
+				ThreadState::FutexWait { deadline: Some(deadline), .. } |
+				ThreadState::Sleep { deadline, .. } => Some((*tid, deadline)),
+				_ => None,
+			})
+			.min_by_key(|(_, deadline)| *deadline)
+	}
+}
+
+impl Default for Scheduler {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec;
+
+	#[test]
+	fn spawn_allocates_increasing_identifiers() {
+		let mut scheduler = Scheduler::with_main_tid(7);
+		assert_eq!(7, scheduler.current);
+		assert_eq!(8, scheduler.spawn(Thread::new()));
+		assert_eq!(9, scheduler.spawn(Thread::new()));
+		assert_eq!(3, scheduler.threads.len());
+	}
+
+	#[test]
+	fn next_runnable_is_round_robin() {
+		let mut scheduler = Scheduler::new();
+		scheduler.spawn(Thread::new());
+		scheduler.spawn(Thread::new());
+		assert_eq!(Some(2), scheduler.next_runnable());
+		scheduler.current = 3;
+		assert_eq!(Some(1), scheduler.next_runnable());
+		scheduler.current = 1;
+		scheduler.threads.get_mut(&2).unwrap().state = ThreadState::PipeWait { pipe: 0 };
+		assert_eq!(Some(3), scheduler.next_runnable());
+		scheduler.threads.get_mut(&3).unwrap().state = ThreadState::PipeWait { pipe: 0 };
+		assert_eq!(Some(1), scheduler.next_runnable());
+		scheduler.wait(0x1000, u32::MAX, None).unwrap();
+		assert_eq!(None, scheduler.next_runnable());
+		scheduler.wake_pipe(0);
+		assert_eq!(Some(2), scheduler.next_runnable());
+	}
+
+	#[test]
+	fn wake_in_fifo_order() {
+		let mut scheduler = Scheduler::new();
+		scheduler.spawn(Thread::new());
+		scheduler.spawn(Thread::new());
+		for tid in [3, 1, 2] {
+			scheduler.current = tid;
+			scheduler.wait(0x1000, u32::MAX, None).unwrap();
+		}
+		assert_eq!(vec![3, 1], scheduler.wake(0x1000, u32::MAX, 2));
+		assert_eq!(ThreadState::Runnable, scheduler.threads[&3].state);
+		assert_eq!(ThreadState::Runnable, scheduler.threads[&1].state);
+		assert_eq!(Vec::<u32>::new(), scheduler.wake(0x2000, u32::MAX, 1));
+		assert_eq!(vec![2], scheduler.wake(0x1000, u32::MAX, usize::MAX));
+	}
+
+	#[test]
+	fn wake_matches_bitset() {
+		let mut scheduler = Scheduler::new();
+		scheduler.spawn(Thread::new());
+		scheduler.wait(0x1000, 0b01, None).unwrap();
+		scheduler.current = 2;
+		scheduler.wait(0x1000, 0b10, None).unwrap();
+		assert_eq!(vec![2], scheduler.wake(0x1000, 0b110, usize::MAX));
+		assert_eq!(Vec::<u32>::new(), scheduler.wake(0x1000, 0b100, usize::MAX));
+		assert_eq!(vec![1], scheduler.wake(0x1000, 0b01, usize::MAX));
+	}
+
+	#[test]
+	fn requeue_moves_oldest_waiters() {
+		let mut scheduler = Scheduler::new();
+		scheduler.spawn(Thread::new());
+		scheduler.spawn(Thread::new());
+		for tid in [2, 3, 1] {
+			scheduler.current = tid;
+			scheduler.wait(0x1000, u32::MAX, None).unwrap();
+		}
+		assert_eq!(2, scheduler.requeue(0x1000, 0x2000, 2));
+		assert_eq!(0, scheduler.requeue(0x3000, 0x2000, 2));
+		assert_eq!(vec![1], scheduler.wake(0x1000, u32::MAX, usize::MAX));
+		assert_eq!(vec![2, 3], scheduler.wake(0x2000, u32::MAX, usize::MAX));
+	}
+
+	#[test]
+	fn next_deadline_includes_sleeps() {
+		let mut scheduler = Scheduler::new();
+		scheduler.spawn(Thread::new());
+		scheduler.spawn(Thread::new());
+		assert_eq!(None, scheduler.next_deadline());
+		scheduler.wait(0x1000, u32::MAX, None).unwrap();
+		assert_eq!(None, scheduler.next_deadline());
+		scheduler.current = 2;
+		scheduler.wait(0x1000, u32::MAX, Some(Duration::from_secs(5))).unwrap();
+		assert_eq!(Some((2, Duration::from_secs(5))), scheduler.next_deadline());
+		scheduler.current = 3;
+		scheduler.sleep(Duration::from_secs(3), 0).unwrap();
+		assert_eq!(Some((3, Duration::from_secs(3))), scheduler.next_deadline());
+	}
+
+	#[test]
+	fn exited_current_thread() {
+		let mut scheduler = Scheduler::new();
+		scheduler.threads.remove(&MAIN_THREAD_ID);
+		assert_eq!(Err(Error(ESRCH)), scheduler.current().map(|_| ()));
+		assert_eq!(Err(Error(ESRCH)), scheduler.sleep(Duration::ZERO, 0));
+	}
+}
@@ -0,0 +1,156 @@
+use core::time::Duration;
+
+use crate::{libc::*, Error};
+
+/// System clock.
+///
+/// See [clock_gettime(2)](https://man7.org/linux/man-pages/man2/clock_gettime.2.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClockId {
+	/// Wall-clock time since the Unix epoch.
+	Realtime,
+	/// Time since some unspecified point in the past that never jumps backwards.
+	Monotonic,
+	/// CPU time consumed by the process.
+	ProcessCpuTime,
+	/// CPU time consumed by the calling thread.
+	ThreadCpuTime,
+}
+
+impl ClockId {
+	/// Convert from Linux clock id.
+	///
+	/// Coarse, raw and boot-time variants are mapped to the respective base clocks.
+	pub const fn from_raw(clock_id: u64) -> Option<Self> {
+		Some(match clock_id {
+			CLOCK_REALTIME | CLOCK_REALTIME_COARSE => Self::Realtime,
+			CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME =>
+				Self::Monotonic,
+			CLOCK_PROCESS_CPUTIME_ID => Self::ProcessCpuTime,
+			CLOCK_THREAD_CPUTIME_ID => Self::ThreadCpuTime,
+			_ => return None,
+		})
+	}
+}
+
+/// Time source of a user-space program.
+///
+/// Readings of a real clock differ between runs. Hosts that need reproducible time should enable
+/// [`VirtualTime`] instead, in which case the clock isn't consulted at all.
+pub trait Clock {
+	/// Get the current value of the specified clock.
+	fn now(&mut self, clock: ClockId) -> Result<Duration, Error>;
+
+	/// Get the resolution of the specified clock.
+	///
+	/// See [clock_getres(2)](https://man7.org/linux/man-pages/man2/clock_getres.2.html).
+	fn resolution(&mut self, clock: ClockId) -> Result<Duration, Error>;
+
+	/// Suspend the execution of the program for the specified duration.
+	///
+	/// Fails with `EAGAIN` if the host prefers to suspend the program instead of blocking
+	/// (see [`WaitReason::Timer`](crate::WaitReason::Timer)).
+	///
+	/// See [nanosleep(2)](https://man7.org/linux/man-pages/man2/nanosleep.2.html).
+	fn sleep(&mut self, duration: Duration) -> Result<(), Error>;
+}
+
+/// Deterministic time model that derives time from the gas consumed by the program.
+///
+/// When enabled via [`Kernel::with_virtual_time`](crate::Kernel::with_virtual_time), the kernel
+/// doesn't consult [`Clock`] at all. Instead the monotonic clock equals the gas reported by
+/// [`Machine::gas_consumed`](crate::Machine::gas_consumed) multiplied by
+/// [`nanos_per_gas`](Self::nanos_per_gas) plus the total time the program has slept. Sleeping
+/// and waiting with a timeout complete instantly by advancing the latter. The real-time clock is
+/// the monotonic clock offset by [`epoch`](Self::epoch), and CPU-time clocks exclude sleeping.
+///
+/// This way all clocks produce identical readings when the program is replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualTime {
+	/// Real-time clock value at the start of the program.
+	pub epoch: Duration,
+	/// The time it takes to execute the instructions that consume one unit of gas.
+	pub nanos_per_gas: u64,
+}
+
+impl VirtualTime {
+	/// Get the value of the specified clock.
+	///
+	/// `slept` is the total time the program has slept.
+	pub fn now(&self, clock: ClockId, gas_consumed: u64, slept: Duration) -> Duration {
+		let busy = Duration::from_nanos(gas_consumed.saturating_mul(self.nanos_per_gas));
+		match clock {
+			ClockId::Realtime => self.epoch.saturating_add(busy).saturating_add(slept),
+			ClockId::Monotonic => busy.saturating_add(slept),
+			ClockId::ProcessCpuTime | ClockId::ThreadCpuTime => busy,
+		}
+	}
+
+	/// Get the resolution of all clocks.
+	pub fn resolution(&self) -> Duration {
+		Duration::from_nanos(self.nanos_per_gas.max(1))
+	}
+}
+
+/// A [`Clock`] that uses Rust standard library.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct StdClock {
+	start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdClock {
+	pub fn new() -> Self {
+		Self { start: std::time::Instant::now() }
+	}
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+	fn now(&mut self, clock: ClockId) -> Result<Duration, Error> {
+		match clock {
+			ClockId::Realtime => std::time::SystemTime::now()
+				.duration_since(std::time::UNIX_EPOCH)
+				.map_err(|_| Error(EINVAL)),
+			ClockId::Monotonic => Ok(self.start.elapsed()),
+			ClockId::ProcessCpuTime => libc_clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID),
+			ClockId::ThreadCpuTime => libc_clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID),
+		}
+	}
+
+	fn resolution(&mut self, clock: ClockId) -> Result<Duration, Error> {
+		let clock_id = match clock {
+			ClockId::Realtime => libc::CLOCK_REALTIME,
+			ClockId::Monotonic => libc::CLOCK_MONOTONIC,
+			ClockId::ProcessCpuTime => libc::CLOCK_PROCESS_CPUTIME_ID,
+			ClockId::ThreadCpuTime => libc::CLOCK_THREAD_CPUTIME_ID,
+		};
+		let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+		if unsafe { libc::clock_getres(clock_id, &mut ts) } < 0 {
+			return Err(Error(EINVAL));
+		}
+		Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+	}
+
+	fn sleep(&mut self, duration: Duration) -> Result<(), Error> {
+		std::thread::sleep(duration);
+		Ok(())
+	}
+}
+
+#[cfg(feature = "std")]
+fn libc_clock_gettime(clock_id: libc::clockid_t) -> Result<Duration, Error> {
+	let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+	if unsafe { libc::clock_gettime(clock_id, &mut ts) } < 0 {
+		return Err(Error(EINVAL));
+	}
+	Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}